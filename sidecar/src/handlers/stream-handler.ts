@@ -12,6 +12,7 @@ import { discoverSkillsSafely, filterDisabledSkills } from "../skills/catalog"
 import type { ReasoningEffort } from "../type"
 import { compactMessages } from "../utils/message-compaction"
 import { buildProviderOptions } from "../utils/provider-options"
+import { getAbortMessage } from "../utils/lifecycle"
 import { buildSystemPrompt } from "../utils/system-prompt-builder"
 import { loadWorkspacePromptContextSafely } from "../workspace"
 
@@ -140,7 +141,7 @@ export async function createStreamResponse(options: StreamHandlerOptions) {
       // Handle abort as normal control flow
       if (error instanceof Error && error.name === "AbortError") {
         console.info("[sidecar] Request aborted by client or server shutdown")
-        return "Request cancelled"
+        return getAbortMessage(abortSignal)
       }
       if (NoSuchToolError.isInstance(error)) {
         return "Error: The model tried to call a unknown tool."
@@ -0,0 +1,64 @@
+import { Hono } from "hono"
+import { describe, expect, it } from "vitest"
+import { createAuthMiddleware } from "../auth"
+
+function createApp(apiToken: string) {
+  const app = new Hono()
+  app.use(createAuthMiddleware(apiToken))
+  app.get("/health", c => c.json({ status: "ok" }))
+  app.get("/api/local-image", c => c.text("image"))
+  app.get("/api/remote-image", c => c.text("image"))
+  app.get("/api/providers", c => c.text("providers"))
+  app.post("/api/chat", c => c.text("chat"))
+  return app
+}
+
+describe("auth middleware", () => {
+  it("keeps the health check public", async () => {
+    const response = await createApp("secret-token").request("/health")
+
+    expect(response.status).toBe(200)
+  })
+
+  it("requires the bearer token on API routes", async () => {
+    const app = createApp("secret-token")
+
+    const missing = await app.request("/api/chat", { method: "POST" })
+    const wrong = await app.request("/api/chat", {
+      method: "POST",
+      headers: { Authorization: "Bearer other-token" }
+    })
+    const valid = await app.request("/api/chat", {
+      method: "POST",
+      headers: { Authorization: "Bearer secret-token" }
+    })
+
+    expect(missing.status).toBe(401)
+    expect(await missing.json()).toEqual({
+      error: "Missing or invalid sidecar API token",
+      code: "SIDECAR_UNAUTHORIZED"
+    })
+    expect(wrong.status).toBe(401)
+    expect(valid.status).toBe(200)
+  })
+
+  it("accepts the token as a query parameter only for GET requests of images", async () => {
+    const app = createApp("secret-token")
+
+    const localImage = await app.request("/api/local-image?token=secret-token")
+    const remoteImage = await app.request("/api/remote-image?token=secret-token")
+    const providers = await app.request("/api/providers?token=secret-token")
+    const chat = await app.request("/api/chat?token=secret-token", { method: "POST" })
+
+    expect(localImage.status).toBe(200)
+    expect(remoteImage.status).toBe(200)
+    expect(providers.status).toBe(401)
+    expect(chat.status).toBe(401)
+  })
+
+  it("rejects every API request when no token is configured", async () => {
+    const response = await createApp("").request("/api/local-image?token=")
+
+    expect(response.status).toBe(401)
+  })
+})
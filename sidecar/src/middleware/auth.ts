@@ -0,0 +1,66 @@
+import { timingSafeEqual } from "node:crypto"
+import type { Context, MiddlewareHandler } from "hono"
+import { mapErrorToResponse, UnauthorizedError } from "../utils/http-errors"
+
+const SIDECAR_API_TOKEN_ENV_KEY = "MINDFLAYER_SIDECAR_API_TOKEN"
+/** Query parameter carrying the token for image requests, which cannot set headers */
+export const SIDECAR_API_TOKEN_QUERY_PARAM = "token"
+
+/** Routes callable without the token */
+const PUBLIC_PATHS = new Set(["/health"])
+/** Routes loaded by URL, e.g. from `<img>`, that may pass the token as a query parameter */
+const QUERY_TOKEN_PATHS = new Set(["/api/local-image", "/api/remote-image"])
+
+/** Per-launch secret generated by Tauri, required by every route except the health check */
+export function getSidecarApiToken(): string {
+  return process.env[SIDECAR_API_TOKEN_ENV_KEY]?.trim() ?? ""
+}
+
+function readRequestToken(c: Context): string | null {
+  const authorization = c.req.header("Authorization")
+  if (authorization?.startsWith("Bearer ")) {
+    return authorization.slice("Bearer ".length).trim()
+  }
+  if (c.req.method === "GET" && QUERY_TOKEN_PATHS.has(c.req.path)) {
+    return c.req.query(SIDECAR_API_TOKEN_QUERY_PARAM) ?? null
+  }
+  return null
+}
+
+function isSameToken(provided: string, expected: string): boolean {
+  const providedBytes = Buffer.from(provided)
+  const expectedBytes = Buffer.from(expected)
+  return (
+    providedBytes.length === expectedBytes.length && timingSafeEqual(providedBytes, expectedBytes)
+  )
+}
+
+/**
+ * Create middleware rejecting requests without the sidecar API token.
+ * Without a configured token every protected request is rejected.
+ *
+ * @param apiToken - Expected token, read from the environment by default
+ * @returns Auth middleware
+ */
+export function createAuthMiddleware(apiToken = getSidecarApiToken()): MiddlewareHandler {
+  if (!apiToken) {
+    console.error(`[sidecar] ${SIDECAR_API_TOKEN_ENV_KEY} is not set, API requests are rejected`)
+  }
+
+  return async (c, next) => {
+    // CORS preflights never carry credentials
+    if (c.req.method === "OPTIONS" || PUBLIC_PATHS.has(c.req.path)) {
+      return next()
+    }
+
+    const token = readRequestToken(c)
+    if (!apiToken || !token || !isSameToken(token, apiToken)) {
+      const errorResponse = mapErrorToResponse(
+        new UnauthorizedError("Missing or invalid sidecar API token", "SIDECAR_UNAUTHORIZED")
+      )
+      return c.json(errorResponse.body, errorResponse.statusCode)
+    }
+
+    return next()
+  }
+}
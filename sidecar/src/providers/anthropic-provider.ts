@@ -2,7 +2,7 @@ import { createAnthropic } from "@ai-sdk/anthropic"
 import type { LanguageModel } from "ai"
 import { MODEL_PROVIDERS } from "../config/constants"
 import type { ProviderConfig } from "../type"
-import type { IProvider } from "./base"
+import type { IProvider, ProviderRuntimeOptions } from "./base"
 
 /**
  * Anthropic provider implementation using the official AI SDK provider.
@@ -10,12 +10,18 @@ import type { IProvider } from "./base"
 export class AnthropicProvider implements IProvider {
   readonly name = "anthropic"
 
-  createModel(modelId: string, config: ProviderConfig): LanguageModel {
+  createModel(
+    modelId: string,
+    config: ProviderConfig,
+    options?: ProviderRuntimeOptions
+  ): LanguageModel {
     const baseUrl = config.baseUrl || MODEL_PROVIDERS.anthropic.defaultBaseUrl
 
     const anthropic = createAnthropic({
       apiKey: config.apiKey,
-      baseURL: baseUrl
+      baseURL: baseUrl,
+      ...(config.apiVersion ? { headers: { "anthropic-version": config.apiVersion } } : {}),
+      ...(options?.fetch ? { fetch: options.fetch } : {})
     })
 
     return anthropic(modelId)
@@ -3,6 +3,8 @@ import type { ProviderConfig } from "../type"
 
 export interface ProviderRuntimeOptions {
   deepSeekReasoningReplayMessages?: UIMessage[]
+  /** Fetch used for API requests, e.g. to fail over between credentials */
+  fetch?: typeof globalThis.fetch
 }
 
 /**
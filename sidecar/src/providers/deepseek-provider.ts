@@ -18,8 +18,8 @@ export class DeepSeekProvider implements IProvider {
   ): LanguageModel {
     const baseUrl = config.baseUrl || MODEL_PROVIDERS.deepseek.defaultBaseUrl
     const fetch = options?.deepSeekReasoningReplayMessages
-      ? createDeepSeekReasoningReplayFetch(options.deepSeekReasoningReplayMessages)
-      : undefined
+      ? createDeepSeekReasoningReplayFetch(options.deepSeekReasoningReplayMessages, options.fetch)
+      : options?.fetch
 
     const deepseek = createDeepSeek({
       apiKey: config.apiKey,
@@ -2,7 +2,7 @@ import type { LanguageModel } from "ai"
 import { createMinimax } from "vercel-minimax-ai-provider"
 import { MODEL_PROVIDERS } from "../config/constants"
 import type { ProviderConfig } from "../type"
-import type { IProvider } from "./base"
+import type { IProvider, ProviderRuntimeOptions } from "./base"
 
 /**
  * MiniMax AI provider implementation.
@@ -11,12 +11,17 @@ import type { IProvider } from "./base"
 export class MinimaxProvider implements IProvider {
   readonly name = "minimax"
 
-  createModel(modelId: string, config: ProviderConfig): LanguageModel {
+  createModel(
+    modelId: string,
+    config: ProviderConfig,
+    options?: ProviderRuntimeOptions
+  ): LanguageModel {
     const baseUrl = config.baseUrl || MODEL_PROVIDERS.minimax.defaultBaseUrl
 
     const minimax = createMinimax({
       baseURL: baseUrl,
-      apiKey: config.apiKey
+      apiKey: config.apiKey,
+      ...(options?.fetch ? { fetch: options.fetch } : {})
     })
 
     return minimax(modelId)
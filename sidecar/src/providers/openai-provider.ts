@@ -2,7 +2,7 @@ import { createOpenAI } from "@ai-sdk/openai"
 import type { LanguageModel } from "ai"
 import { MODEL_PROVIDERS } from "../config/constants"
 import type { ProviderConfig } from "../type"
-import type { IProvider } from "./base"
+import type { IProvider, ProviderRuntimeOptions } from "./base"
 
 /**
  * OpenAI provider implementation using the official AI SDK provider.
@@ -10,12 +10,19 @@ import type { IProvider } from "./base"
 export class OpenAIProvider implements IProvider {
   readonly name = "openai"
 
-  createModel(modelId: string, config: ProviderConfig): LanguageModel {
+  createModel(
+    modelId: string,
+    config: ProviderConfig,
+    options?: ProviderRuntimeOptions
+  ): LanguageModel {
     const baseUrl = config.baseUrl || MODEL_PROVIDERS.openai.defaultBaseUrl
 
     const openai = createOpenAI({
       apiKey: config.apiKey,
-      baseURL: baseUrl
+      baseURL: baseUrl,
+      ...(config.organization ? { organization: config.organization } : {}),
+      ...(config.project ? { project: config.project } : {}),
+      ...(options?.fetch ? { fetch: options.fetch } : {})
     })
 
     return openai(modelId)
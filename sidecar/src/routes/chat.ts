@@ -5,7 +5,13 @@ import type { ChannelRuntimeConfigService } from "../services/channel-runtime-co
 import { providerService } from "../services/provider-service"
 import { toolService } from "../services/tool-service"
 import type { WebSearchMode } from "../type"
-import { BadRequestError, mapErrorToResponse, UnauthorizedError } from "../utils/http-errors"
+import {
+  BadRequestError,
+  LockedError,
+  mapErrorToResponse,
+  UnauthorizedError
+} from "../utils/http-errors"
+import { getAbortMessage } from "../utils/lifecycle"
 import { buildToolChoice } from "../utils/tool-choice"
 
 /**
@@ -27,7 +33,8 @@ export async function handleChat(
       typeof body.modelProviderLabel === "string" ? body.modelProviderLabel : undefined
     const modelProviderLabel =
       (modelProviderLabelHeader || modelProviderLabelBody)?.trim() || undefined
-    const modelId = c.req.header("x-model-id") || body.model
+    const modelId =
+      c.req.header("x-model-id") || body.model || providerService.getConfig(provider)?.defaultModel
     const modelLabelHeader = c.req.header("x-model-label")
     const modelLabelBody = typeof body.modelLabel === "string" ? body.modelLabel : undefined
     const modelLabel = (modelLabelHeader || modelLabelBody)?.trim() || undefined
@@ -50,6 +57,12 @@ export async function handleChat(
     }
 
     // Check provider configuration
+    if (providerService.isLocked()) {
+      throw new LockedError(
+        "Provider keys are locked. Enter your master passphrase to unlock them.",
+        "PROVIDER_CONFIG_LOCKED"
+      )
+    }
     if (!providerService.hasConfig(provider)) {
       console.error(`[sidecar] API key not found for provider: ${provider}`)
       throw new UnauthorizedError(
@@ -110,7 +123,7 @@ export async function handleChat(
     // Handle abort errors at info level
     if (error instanceof Error && error.name === "AbortError") {
       console.info("[sidecar] Request aborted")
-      return c.json({ error: "Request cancelled" }, 400)
+      return c.json({ error: getAbortMessage(globalAbortController.signal) }, 400)
     }
 
     console.error("[sidecar] Chat error:", error)
@@ -1,4 +1,5 @@
 import type { Context } from "hono"
+import { getSidecarApiToken, SIDECAR_API_TOKEN_QUERY_PARAM } from "../middleware/auth"
 import {
   discoverSkills,
   getSkillById,
@@ -22,6 +23,11 @@ function toLocalImageUrl(c: Context, imagePath: string | null): string | null {
 
   const localImageUrl = new URL("/api/local-image", c.req.url)
   localImageUrl.searchParams.set("path", imagePath)
+  // Icons are loaded by <img> tags, which cannot send the Authorization header
+  const apiToken = getSidecarApiToken()
+  if (apiToken) {
+    localImageUrl.searchParams.set(SIDECAR_API_TOKEN_QUERY_PARAM, apiToken)
+  }
   return localImageUrl.toString()
 }
 
@@ -1,7 +1,12 @@
 import { generateText } from "ai"
 import type { Context } from "hono"
 import { providerService } from "../services/provider-service"
-import { BadRequestError, mapErrorToResponse, UnauthorizedError } from "../utils/http-errors"
+import {
+  BadRequestError,
+  LockedError,
+  mapErrorToResponse,
+  UnauthorizedError
+} from "../utils/http-errors"
 
 const TITLE_MAX_LENGTH = 20
 
@@ -45,7 +50,8 @@ export async function handleTitleGenerator(c: Context) {
     const body = await c.req.json()
 
     const provider = (c.req.header("x-model-provider") || body.provider || "minimax").toLowerCase()
-    const modelId = c.req.header("x-model-id") || body.model
+    const modelId =
+      c.req.header("x-model-id") || body.model || providerService.getConfig(provider)?.defaultModel
     const messageText = body?.messageText as string
 
     if (!modelId) {
@@ -55,6 +61,12 @@ export async function handleTitleGenerator(c: Context) {
       throw new BadRequestError("messageText is required")
     }
 
+    if (providerService.isLocked()) {
+      throw new LockedError(
+        "Provider keys are locked. Enter your master passphrase to unlock them.",
+        "PROVIDER_CONFIG_LOCKED"
+      )
+    }
     if (!providerService.hasConfig(provider)) {
       throw new UnauthorizedError(
         `Please configure your ${provider} API key in settings`,
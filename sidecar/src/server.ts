@@ -1,6 +1,7 @@
-import { serve } from "@hono/node-server"
+import { createAdaptorServer, serve } from "@hono/node-server"
 import { Hono } from "hono"
 import { ProxyAgent, setGlobalDispatcher } from "undici"
+import { createAuthMiddleware } from "./middleware/auth"
 import { createCorsMiddleware } from "./middleware/cors"
 import { errorHandler } from "./middleware/error-handler"
 import { registerRoutes } from "./routes"
@@ -13,7 +14,9 @@ import { toolService } from "./services/tool-service"
 import { cleanupTransientSandboxes } from "./tools/bash-exec/sandbox"
 import type { ConfigUpdateMessage } from "./type"
 import { createShutdownHandler, setupStdinListener } from "./utils/lifecycle"
+import { sendToHost } from "./utils/protocol"
 import { getConfiguredProxyUrl } from "./utils/proxy-url"
+import { getSidecarSocketPath, listenOnUnixSocket } from "./utils/unix-socket"
 
 function setupGlobalProxyIfConfigured() {
   const { rawProxyUrl, proxyUrl } = getConfiguredProxyUrl()
@@ -46,8 +49,10 @@ export async function startSidecar() {
   setupGlobalProxyIfConfigured()
 
   const app = new Hono()
-  // Use the SIDECAR_PORT environment variable set by the Rust sidecar setup
+  // Use the SIDECAR_PORT environment variable set by the Rust sidecar setup, unless Tauri
+  // asked for a Unix socket
   const PORT = process.env.SIDECAR_PORT
+  const SOCKET_PATH = getSidecarSocketPath()
   const globalAbortController = new AbortController()
   const channelRuntimeConfigService = new ChannelRuntimeConfigService()
   const telegramSessionStore = createTelegramSessionStoreFromEnv()
@@ -62,6 +67,7 @@ export async function startSidecar() {
 
   // Register middleware
   app.use(createCorsMiddleware())
+  app.use(createAuthMiddleware())
   app.use(errorHandler)
 
   // Register routes
@@ -74,16 +80,24 @@ export async function startSidecar() {
   )
 
   // Start server
-  const server = serve(
-    {
-      fetch: app.fetch,
-      port: Number(PORT)
-    },
-    () => {
-      console.log(`Sidecar running on http://localhost:${PORT}`)
-      console.log(`API endpoint: http://localhost:${PORT}/api/chat`)
-    }
-  )
+  const onListening = (address: string) => {
+    console.log(`Sidecar running on ${address}`)
+    console.log(`API endpoint: ${address}/api/chat`)
+    sendToHost({ type: "status", event: "listening", message: address })
+  }
+  let server: ReturnType<typeof serve>
+  if (SOCKET_PATH) {
+    server = createAdaptorServer({ fetch: app.fetch })
+    listenOnUnixSocket(server, SOCKET_PATH, () => onListening(`unix:${SOCKET_PATH}`))
+  } else {
+    server = serve(
+      {
+        fetch: app.fetch,
+        port: Number(PORT)
+      },
+      () => onListening(`http://localhost:${PORT}`)
+    )
+  }
 
   server.on("error", (error: NodeJS.ErrnoException) => {
     const code = String(error.code ?? "UNKNOWN")
@@ -92,30 +106,34 @@ export async function startSidecar() {
     process.exit(1)
   })
 
-  // Setup stdin listener for config updates
-  setupStdinListener((message: unknown) => {
-    const configMessage = message as ConfigUpdateMessage
-    const lastParallelApiKey = providerService.getConfig("parallel")?.apiKey ?? ""
-    const newParallelApiKey = configMessage.configs.parallel?.apiKey ?? ""
-
-    if (lastParallelApiKey !== newParallelApiKey) {
-      console.log("[sidecar] Parallel API key updated, refreshing web search tool")
-      toolService.updateToolConfig("webSearch", newParallelApiKey)
-    }
-
-    providerService.updateConfigs(configMessage)
-    void telegramBotService.refresh()
-  })
-
   // Register shutdown handlers
   const shutdown = createShutdownHandler(globalAbortController, server, async () => {
     await agentSessionService.stopAll()
     await telegramBotService.stop()
     await cleanupTransientSandboxes()
   })
-  process.on("SIGTERM", shutdown)
-  process.on("SIGINT", shutdown)
+  process.on("SIGTERM", () => shutdown())
+  process.on("SIGINT", () => shutdown())
   process.on("exit", () => {
     console.log("Sidecar process exiting...")
+    sendToHost({ type: "status", event: "exiting" })
   })
+
+  // Setup stdin listener for config updates and shutdown requests
+  setupStdinListener(
+    (message: unknown) => {
+      const configMessage = message as ConfigUpdateMessage
+      const lastParallelApiKey = providerService.getConfig("parallel")?.apiKey ?? ""
+      const newParallelApiKey = configMessage.configs.parallel?.apiKey ?? ""
+
+      if (lastParallelApiKey !== newParallelApiKey) {
+        console.log("[sidecar] Parallel API key updated, refreshing web search tool")
+        toolService.updateToolConfig("webSearch", newParallelApiKey)
+      }
+
+      providerService.updateConfigs(configMessage)
+      void telegramBotService.refresh()
+    },
+    reason => shutdown(reason)
+  )
 }
@@ -119,6 +119,22 @@ describe("ProviderService", () => {
     })
   })
 
+  describe("isLocked", () => {
+    it("should report locked configs until an unlocked update arrives", () => {
+      service.updateConfigs({ type: "config_update", configs: {}, locked: true })
+      expect(service.isLocked()).toBe(true)
+      expect(service.hasConfig("minimax")).toBe(false)
+
+      service.updateConfigs({
+        type: "config_update",
+        configs: { minimax: { apiKey: "test-key" } },
+        locked: false
+      })
+      expect(service.isLocked()).toBe(false)
+      expect(service.hasConfig("minimax")).toBe(true)
+    })
+  })
+
   describe("createModel", () => {
     it("should throw error if provider not configured", () => {
       expect(() => {
@@ -1,6 +1,11 @@
 import type { LanguageModel } from "ai"
 import { type ProviderRuntimeOptions, providerRegistry } from "../providers"
 import type { ConfigUpdateMessage, ProviderConfig } from "../type"
+import {
+  type CredentialFailoverState,
+  createCredentialFailoverFetch
+} from "../utils/credential-failover"
+import { createProviderRequestFetch } from "../utils/provider-request"
 
 /**
  * Service for managing provider configurations and model creation.
@@ -8,6 +13,8 @@ import type { ConfigUpdateMessage, ProviderConfig } from "../type"
  */
 export class ProviderService {
   private apiKeyCache = new Map<string, ProviderConfig>()
+  private locked = false
+  private failoverStates = new Map<string, CredentialFailoverState>()
 
   /**
    * Get provider configuration.
@@ -29,6 +36,15 @@ export class ProviderService {
     return this.apiKeyCache.has(provider)
   }
 
+  /**
+   * Check if stored provider keys are locked behind the master passphrase.
+   *
+   * @returns True if the last config update was sent while locked
+   */
+  isLocked(): boolean {
+    return this.locked
+  }
+
   /**
    * Update provider configurations from stdin message.
    * Replaces all existing configurations with new ones.
@@ -39,6 +55,12 @@ export class ProviderService {
     console.log("[ProviderService] Updating provider configurations")
 
     this.apiKeyCache.clear()
+    this.failoverStates.clear()
+    this.locked = message.locked === true
+
+    if (this.locked) {
+      console.log("[ProviderService] Provider configurations are locked")
+    }
 
     for (const [provider, config] of Object.entries(message.configs)) {
       this.apiKeyCache.set(provider, config)
@@ -48,6 +70,8 @@ export class ProviderService {
 
   /**
    * Create a language model instance using the registered provider.
+   * With several active credentials, requests rejected with 401/429 are retried with the
+   * next key.
    *
    * @param provider - Provider name (e.g., "minimax")
    * @param modelId - Model identifier (e.g., "abab6.5s-chat")
@@ -62,7 +86,31 @@ export class ProviderService {
     }
 
     const providerPlugin = providerRegistry.get(provider)
-    return providerPlugin.createModel(modelId, config, options)
+    const requestFetch = createProviderRequestFetch(config, options?.fetch)
+    const credentials = (config.credentials ?? []).filter(
+      credential => credential.active && credential.apiKey
+    )
+    if (credentials.length < 2) {
+      return providerPlugin.createModel(modelId, config, {
+        ...options,
+        ...(requestFetch ? { fetch: requestFetch } : {})
+      })
+    }
+
+    let failoverState = this.failoverStates.get(provider)
+    if (!failoverState) {
+      failoverState = { index: 0 }
+      this.failoverStates.set(provider, failoverState)
+    }
+
+    return providerPlugin.createModel(
+      modelId,
+      { ...config, apiKey: credentials[0].apiKey },
+      {
+        ...options,
+        fetch: createCredentialFailoverFetch(credentials, failoverState, requestFetch)
+      }
+    )
   }
 }
 
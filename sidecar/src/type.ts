@@ -1,6 +1,28 @@
+export interface ProviderCredential {
+  label: string
+  apiKey: string
+  active: boolean
+  /** Milliseconds since the Unix epoch, when the user set an expiry */
+  expiresAt?: number | null
+}
+
 export interface ProviderConfig {
+  /** Key of the first active credential */
   apiKey: string
   baseUrl?: string
+  /** All labelled keys in failover order */
+  credentials?: ProviderCredential[]
+  /** Additional HTTP headers sent with every request */
+  extraHeaders?: Record<string, string>
+  /** OpenAI organization ID */
+  organization?: string | null
+  /** OpenAI project ID */
+  project?: string | null
+  requestTimeoutMs?: number | null
+  /** Model used when a request does not name one */
+  defaultModel?: string | null
+  /** API version header for providers that version per request (Anthropic) */
+  apiVersion?: string | null
 }
 
 export interface SelectedModelRuntime {
@@ -28,7 +50,20 @@ export type ReasoningEffort = "default" | "low" | "medium" | "high" | "xhigh"
 
 export type ProviderType = "minimax" | "anthropic" | "deepseek" | "openai"
 
+/** Sent by Tauri before it stops or restarts the sidecar */
+export interface ShutdownMessage {
+  type: "shutdown"
+  id?: number
+  /** Reported to requests that are cancelled by the shutdown */
+  reason?: string
+}
+
 export interface ConfigUpdateMessage {
   type: "config_update"
+  id?: number
+  /** Increases with every update; acknowledged back to Tauri on stdout */
+  seq?: number
   configs: Record<string, ProviderConfig>
+  /** Stored keys are protected by a master passphrase that has not been entered yet */
+  locked?: boolean
 }
@@ -0,0 +1,83 @@
+import { describe, expect, it, vi } from "vitest"
+import type { ProviderCredential } from "../../type"
+import { createCredentialFailoverFetch } from "../credential-failover"
+
+const credentials: ProviderCredential[] = [
+  { label: "personal", apiKey: "personal-key", active: true },
+  { label: "org", apiKey: "org-key", active: true }
+]
+
+function createFetchMock(statusByKey: Record<string, number>) {
+  return vi.fn(
+    async (
+      _input: Parameters<typeof globalThis.fetch>[0],
+      init?: Parameters<typeof globalThis.fetch>[1]
+    ) => {
+      const authorization = new Headers(init?.headers).get("authorization") ?? ""
+      const apiKey = authorization.replace("Bearer ", "")
+      return new Response(apiKey, { status: statusByKey[apiKey] ?? 200 })
+    }
+  )
+}
+
+function authorizationOf(call: unknown[]): string | null {
+  return new Headers((call[1] as RequestInit | undefined)?.headers).get("authorization")
+}
+
+describe("credential failover fetch", () => {
+  it("uses the first credential while it is accepted", async () => {
+    const fetchMock = createFetchMock({})
+    const failoverFetch = createCredentialFailoverFetch(credentials, { index: 0 }, fetchMock)
+
+    const response = await failoverFetch("https://api.example.com", {
+      headers: { Authorization: "Bearer personal-key" }
+    })
+
+    expect(await response.text()).toBe("personal-key")
+    expect(fetchMock).toHaveBeenCalledTimes(1)
+  })
+
+  it.each([401, 429])("retries with the next credential on %i", async status => {
+    const fetchMock = createFetchMock({ "personal-key": status })
+    const state = { index: 0 }
+    const failoverFetch = createCredentialFailoverFetch(credentials, state, fetchMock)
+
+    const response = await failoverFetch("https://api.example.com", {
+      method: "POST",
+      headers: { Authorization: "Bearer personal-key" },
+      body: "{}"
+    })
+
+    expect(response.status).toBe(200)
+    expect(fetchMock).toHaveBeenCalledTimes(2)
+    expect(authorizationOf(fetchMock.mock.calls[1])).toBe("Bearer org-key")
+    expect(state.index).toBe(1)
+  })
+
+  it("starts later requests with the credential that worked", async () => {
+    const fetchMock = createFetchMock({ "personal-key": 429 })
+    const failoverFetch = createCredentialFailoverFetch(credentials, { index: 1 }, fetchMock)
+
+    await failoverFetch("https://api.example.com", {
+      headers: { "x-api-key": "personal-key", Authorization: "Bearer personal-key" }
+    })
+
+    expect(fetchMock).toHaveBeenCalledTimes(1)
+    const headers = new Headers(fetchMock.mock.calls[0][1]?.headers)
+    expect(headers.get("x-api-key")).toBe("org-key")
+  })
+
+  it("returns the last rejection when every credential fails", async () => {
+    const fetchMock = createFetchMock({ "personal-key": 401, "org-key": 401 })
+    const state = { index: 0 }
+    const failoverFetch = createCredentialFailoverFetch(credentials, state, fetchMock)
+
+    const response = await failoverFetch("https://api.example.com", {
+      headers: { Authorization: "Bearer personal-key" }
+    })
+
+    expect(response.status).toBe(401)
+    expect(fetchMock).toHaveBeenCalledTimes(2)
+    expect(state.index).toBe(0)
+  })
+})
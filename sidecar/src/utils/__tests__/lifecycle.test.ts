@@ -0,0 +1,119 @@
+import { afterEach, describe, expect, it, vi } from "vitest"
+import {
+  createConfigUpdateHandler,
+  createHostMessageHandler,
+  createLineReader,
+  createShutdownHandler,
+  getAbortMessage,
+  SidecarShutdownError
+} from "../lifecycle"
+
+describe("lifecycle", () => {
+  afterEach(() => {
+    vi.useRealTimers()
+  })
+
+  it("reports the shutdown reason to aborted requests", () => {
+    // The handler schedules a forced exit that must not fire during the test
+    vi.useFakeTimers()
+    const controller = new AbortController()
+    const requestSignal = AbortSignal.any([new AbortController().signal, controller.signal])
+    const shutdown = createShutdownHandler(controller, { close: () => {} })
+
+    shutdown("The sidecar is restarting")
+
+    expect(requestSignal.reason).toBeInstanceOf(SidecarShutdownError)
+    expect((requestSignal.reason as Error).name).toBe("AbortError")
+    expect(getAbortMessage(requestSignal)).toBe("Request cancelled: The sidecar is restarting")
+  })
+
+  it("keeps the generic message for requests cancelled by the client", () => {
+    const controller = new AbortController()
+    controller.abort()
+
+    expect(getAbortMessage(controller.signal)).toBe("Request cancelled")
+  })
+
+  it("acknowledges config updates and skips ones that were already applied", () => {
+    const onConfigUpdate = vi.fn()
+    const sendAck = vi.fn()
+    const handleConfigUpdate = createConfigUpdateHandler(onConfigUpdate, sendAck)
+
+    handleConfigUpdate({ seq: 1 })
+    handleConfigUpdate({ seq: 2 })
+    handleConfigUpdate({ seq: 2 })
+
+    expect(onConfigUpdate).toHaveBeenCalledTimes(2)
+    expect(sendAck.mock.calls).toEqual([
+      [{ type: "config_ack", seq: 1 }],
+      [{ type: "config_ack", seq: 2 }],
+      [{ type: "config_ack", seq: 2 }]
+    ])
+  })
+
+  it("reports config updates that fail to apply", () => {
+    const sendAck = vi.fn()
+    const handleConfigUpdate = createConfigUpdateHandler(() => {
+      throw new Error("invalid configs")
+    }, sendAck)
+
+    handleConfigUpdate({ seq: 3 })
+
+    expect(sendAck).toHaveBeenCalledWith({ type: "config_ack", seq: 3, error: "invalid configs" })
+  })
+
+  it("answers hello and ping requests with their id", () => {
+    const send = vi.fn()
+    const handleLine = createHostMessageHandler(() => {}, undefined, send)
+
+    handleLine(JSON.stringify({ type: "hello", id: 1, protocolVersion: 1, minProtocolVersion: 1 }))
+    handleLine(JSON.stringify({ type: "ping", id: 2 }))
+
+    expect(send.mock.calls).toEqual([
+      [{ type: "hello", id: 1, protocolVersion: 1 }],
+      [{ type: "pong", id: 2 }]
+    ])
+  })
+
+  it("handles a message split across stdin chunks once it is complete", () => {
+    const onConfigUpdate = vi.fn()
+    const send = vi.fn()
+    const readChunk = createLineReader(createHostMessageHandler(onConfigUpdate, undefined, send))
+    const message = JSON.stringify({ type: "config_update", id: 1, seq: 1, configs: {} })
+    const splitAt = Math.floor(message.length / 2)
+
+    readChunk(message.slice(0, splitAt))
+    expect(onConfigUpdate).not.toHaveBeenCalled()
+    readChunk(`${message.slice(splitAt)}\n${JSON.stringify({ type: "ping", id: 2 })}\n`)
+
+    expect(onConfigUpdate).toHaveBeenCalledTimes(1)
+    expect(send.mock.calls).toEqual([
+      [{ type: "config_ack", id: 1, seq: 1 }],
+      [{ type: "pong", id: 2 }]
+    ])
+  })
+
+  it("rejects hosts whose protocol versions do not overlap", () => {
+    const send = vi.fn()
+    const handleLine = createHostMessageHandler(() => {}, undefined, send)
+
+    handleLine(JSON.stringify({ type: "hello", id: 1, protocolVersion: 9, minProtocolVersion: 9 }))
+
+    expect(send).toHaveBeenCalledWith(
+      expect.objectContaining({ type: "error", id: 1, code: "incompatible_protocol" })
+    )
+  })
+
+  it("reports malformed and unknown messages", () => {
+    const send = vi.fn()
+    const handleLine = createHostMessageHandler(() => {}, undefined, send)
+
+    handleLine("not json")
+    handleLine(JSON.stringify({ type: "reload", id: 4 }))
+
+    expect(send.mock.calls).toEqual([
+      [expect.objectContaining({ type: "error", code: "invalid_message" })],
+      [expect.objectContaining({ type: "error", id: 4, code: "unknown_message" })]
+    ])
+  })
+})
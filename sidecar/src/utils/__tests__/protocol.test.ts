@@ -0,0 +1,14 @@
+import { describe, expect, it } from "vitest"
+import { negotiateProtocolVersion, PROTOCOL_VERSION } from "../protocol"
+
+describe("protocol", () => {
+  it("picks the newest version both sides speak", () => {
+    expect(negotiateProtocolVersion(PROTOCOL_VERSION, 1)).toBe(PROTOCOL_VERSION)
+    expect(negotiateProtocolVersion(PROTOCOL_VERSION + 1, 1)).toBe(PROTOCOL_VERSION)
+  })
+
+  it("fails when the version ranges do not overlap", () => {
+    expect(negotiateProtocolVersion(PROTOCOL_VERSION + 2, PROTOCOL_VERSION + 1)).toBeNull()
+    expect(negotiateProtocolVersion(0, 0)).toBeNull()
+  })
+})
@@ -0,0 +1,69 @@
+import { describe, expect, it, vi } from "vitest"
+import { createProviderRequestFetch } from "../provider-request"
+
+describe("provider request fetch", () => {
+  it("keeps the default fetch when nothing is configured", () => {
+    expect(createProviderRequestFetch({ apiKey: "key" })).toBeUndefined()
+  })
+
+  it("adds extra headers without dropping SDK headers", async () => {
+    const fetchMock = vi.fn(
+      async (
+        _input: Parameters<typeof globalThis.fetch>[0],
+        _init?: Parameters<typeof globalThis.fetch>[1]
+      ) => new Response("ok")
+    )
+    const requestFetch = createProviderRequestFetch(
+      { apiKey: "key", extraHeaders: { "X-Gateway": "team-a" } },
+      fetchMock
+    )
+
+    await requestFetch?.("https://api.example.com", {
+      headers: { Authorization: "Bearer key" }
+    })
+
+    const headers = new Headers(fetchMock.mock.calls[0][1]?.headers)
+    expect(headers.get("x-gateway")).toBe("team-a")
+    expect(headers.get("authorization")).toBe("Bearer key")
+  })
+
+  it("aborts requests that exceed the timeout", async () => {
+    const fetchMock = vi.fn(
+      (
+        _input: Parameters<typeof globalThis.fetch>[0],
+        init?: Parameters<typeof globalThis.fetch>[1]
+      ) =>
+        new Promise<Response>((_resolve, reject) => {
+          init?.signal?.addEventListener("abort", () => reject(init.signal?.reason))
+        })
+    )
+    const requestFetch = createProviderRequestFetch(
+      { apiKey: "key", requestTimeoutMs: 10 },
+      fetchMock
+    )
+
+    await expect(requestFetch?.("https://api.example.com")).rejects.toThrow()
+  })
+
+  it("stops the timeout once the response headers arrive", async () => {
+    let signal: AbortSignal | null | undefined
+    const fetchMock = vi.fn(
+      async (
+        _input: Parameters<typeof globalThis.fetch>[0],
+        init?: Parameters<typeof globalThis.fetch>[1]
+      ) => {
+        signal = init?.signal
+        return new Response("streamed")
+      }
+    )
+    const requestFetch = createProviderRequestFetch(
+      { apiKey: "key", requestTimeoutMs: 10 },
+      fetchMock
+    )
+
+    await requestFetch?.("https://api.example.com")
+    await new Promise(resolve => setTimeout(resolve, 30))
+
+    expect(signal?.aborted).toBe(false)
+  })
+})
@@ -0,0 +1,34 @@
+import { mkdtempSync, rmSync, statSync, writeFileSync } from "node:fs"
+import { createServer, type Server } from "node:net"
+import { tmpdir } from "node:os"
+import { join } from "node:path"
+import { afterEach, describe, expect, it } from "vitest"
+import { listenOnUnixSocket } from "../unix-socket"
+
+describe.skipIf(process.platform === "win32")("listenOnUnixSocket", () => {
+  let server: Server | null = null
+  let tempDir: string | null = null
+
+  afterEach(async () => {
+    await new Promise<void>(resolve => (server ? server.close(() => resolve()) : resolve()))
+    server = null
+    if (tempDir) {
+      rmSync(tempDir, { recursive: true, force: true })
+      tempDir = null
+    }
+  })
+
+  it("replaces a stale socket and restricts it to the current user", async () => {
+    tempDir = mkdtempSync(join(tmpdir(), "sidecar-socket-"))
+    const socketPath = join(tempDir, "sidecar.sock")
+    writeFileSync(socketPath, "stale")
+    const previousUmask = process.umask()
+
+    server = createServer()
+    await new Promise<void>(resolve => listenOnUnixSocket(server as Server, socketPath, resolve))
+
+    expect(statSync(socketPath).isSocket()).toBe(true)
+    expect(statSync(socketPath).mode & 0o777).toBe(0o600)
+    expect(process.umask()).toBe(previousUmask)
+  })
+})
@@ -0,0 +1,79 @@
+import type { ProviderCredential } from "../type"
+
+type FetchFunction = typeof globalThis.fetch
+
+/**
+ * Status codes meaning the key was rejected or ran out of quota.
+ */
+const FAILOVER_STATUS_CODES = new Set([401, 429])
+
+export interface CredentialFailoverState {
+  /** Index of the credential the next request starts with */
+  index: number
+}
+
+function withApiKey(
+  init: RequestInit | undefined,
+  sdkApiKey: string,
+  apiKey: string
+): RequestInit | undefined {
+  if (apiKey === sdkApiKey || !sdkApiKey) {
+    return init
+  }
+
+  const headers = new Headers(init?.headers)
+  for (const [name, value] of [...headers.entries()]) {
+    if (value.includes(sdkApiKey)) {
+      headers.set(name, value.replaceAll(sdkApiKey, apiKey))
+    }
+  }
+
+  return { ...init, headers }
+}
+
+/**
+ * Create a fetch that retries a rejected request with the next credential.
+ *
+ * Provider SDKs are created with the first credential, so other keys are swapped in by
+ * rewriting whichever header carries it (`Authorization`, `x-api-key`, ...).
+ * The state is shared between requests: once a key is rejected, later requests start
+ * with the key that worked.
+ *
+ * @param credentials - Active credentials in failover order
+ * @param state - Failover position shared by all models of the provider
+ * @param fetchImpl - Underlying fetch implementation
+ */
+export function createCredentialFailoverFetch(
+  credentials: ProviderCredential[],
+  state: CredentialFailoverState,
+  fetchImpl: FetchFunction = globalThis.fetch
+): FetchFunction {
+  const sdkApiKey = credentials[0]?.apiKey ?? ""
+
+  return async (input, init) => {
+    let response: Response | undefined
+
+    for (let attempt = 0; attempt < credentials.length; attempt++) {
+      const index = (state.index + attempt) % credentials.length
+      const credential = credentials[index]
+      response = await fetchImpl(input, withApiKey(init, sdkApiKey, credential.apiKey))
+
+      if (!FAILOVER_STATUS_CODES.has(response.status)) {
+        if (index !== state.index) {
+          console.log(`[CredentialFailover] Switched to API key '${credential.label}'`)
+          state.index = index
+        }
+        return response
+      }
+
+      if (attempt < credentials.length - 1) {
+        console.warn(
+          `[CredentialFailover] API key '${credential.label}' rejected with ${response.status}, trying next key`
+        )
+        await response.body?.cancel()
+      }
+    }
+
+    return response as Response
+  }
+}
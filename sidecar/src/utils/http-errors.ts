@@ -1,4 +1,4 @@
-type HttpStatusCode = 400 | 401 | 403 | 404 | 409 | 500
+type HttpStatusCode = 400 | 401 | 403 | 404 | 409 | 423 | 500
 
 /**
  * Base class for HTTP errors with status codes.
@@ -59,6 +59,15 @@ export class ConflictError extends HttpError<409> {
   }
 }
 
+/**
+ * 423 Locked - Provider keys are locked behind the master passphrase
+ */
+export class LockedError extends HttpError<423> {
+  constructor(message: string, code = "LOCKED") {
+    super(message, 423, code)
+  }
+}
+
 /**
  * 500 Internal Server Error - Server-side error
  */
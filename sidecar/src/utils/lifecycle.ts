@@ -1,3 +1,40 @@
+import {
+  type ConfigAckMessage,
+  type HostMessage,
+  logToHost,
+  MIN_PROTOCOL_VERSION,
+  negotiateProtocolVersion,
+  PROTOCOL_VERSION,
+  type SidecarMessage,
+  sendToHost
+} from "./protocol"
+
+const DEFAULT_SHUTDOWN_REASON = "The sidecar is shutting down"
+
+/**
+ * Abort reason of requests cancelled because the sidecar shuts down.
+ * Named like the DOM abort error so existing abort handling still applies.
+ */
+export class SidecarShutdownError extends Error {
+  constructor(reason: string) {
+    super(reason)
+    this.name = "AbortError"
+  }
+}
+
+/**
+ * Message returned to clients whose request was aborted.
+ * Includes the shutdown reason when the sidecar itself cancelled the request.
+ *
+ * @param signal - Signal that aborted the request
+ */
+export function getAbortMessage(signal: AbortSignal): string {
+  if (signal.reason instanceof SidecarShutdownError) {
+    return `Request cancelled: ${signal.reason.message}`
+  }
+  return "Request cancelled"
+}
+
 /**
  * Graceful shutdown handler.
  * Aborts active requests and closes server with timeout.
@@ -5,6 +42,7 @@
  * @param globalAbortController - Controller for aborting active requests
  * @param server - HTTP server instance
  * @param preShutdown - Optional best-effort callback before abort/close
+ * @returns Handler taking an optional reason that is reported to aborted requests
  */
 export function createShutdownHandler(
   globalAbortController: AbortController,
@@ -13,29 +51,32 @@ export function createShutdownHandler(
 ) {
   let shutdownStarted = false
 
-  return () => {
+  return (reason = DEFAULT_SHUTDOWN_REASON) => {
     if (shutdownStarted) {
       return
     }
     shutdownStarted = true
 
     void (async () => {
-      console.log("Shutting down gracefully...")
+      console.log(`Shutting down gracefully: ${reason}`)
+      sendToHost({ type: "status", event: "shutting_down", message: reason })
 
       if (preShutdown) {
         try {
           await preShutdown()
         } catch (error) {
           console.error("[sidecar] pre-shutdown hook failed:", error)
+          logToHost("warn", `Pre-shutdown hook failed: ${String(error)}`)
         }
       }
 
       // Abort all active AI requests
-      globalAbortController.abort()
+      globalAbortController.abort(new SidecarShutdownError(reason))
       console.info("[sidecar] All active requests cancelled")
 
       server.close(() => {
         console.log("Server closed, port released")
+        sendToHost({ type: "status", event: "server_closed" })
         process.exit(0)
       })
 
@@ -49,30 +90,150 @@ export function createShutdownHandler(
 }
 
 /**
- * Setup stdin listener for configuration updates from Tauri.
+ * Apply sequence-numbered config updates and acknowledge each one to Tauri.
+ * Updates Tauri sends again after a missed acknowledgement are acknowledged without
+ * being applied twice.
  *
- * @param onConfigUpdate - Callback for config update messages
+ * @param onConfigUpdate - Callback applying a config update message
+ * @param sendAck - Writes the acknowledgement, to stdout by default
  */
-export function setupStdinListener(onConfigUpdate: (message: unknown) => void) {
-  process.stdin.setEncoding("utf8")
+export function createConfigUpdateHandler(
+  onConfigUpdate: (message: unknown) => void,
+  sendAck: (ack: ConfigAckMessage) => void = sendToHost
+) {
+  let lastAppliedSeq = 0
+
+  return (message: { id?: unknown; seq?: unknown }) => {
+    const id = typeof message.id === "number" ? message.id : undefined
+    const seq = typeof message.seq === "number" ? message.seq : undefined
+    if (seq !== undefined && seq <= lastAppliedSeq) {
+      sendAck({ type: "config_ack", id, seq })
+      return
+    }
 
-  process.stdin.on("data", (data: string) => {
     try {
-      console.log("[sidecar] Received stdin data:", data.substring(0, 200))
-      const lines = data.trim().split("\n")
+      onConfigUpdate(message)
+    } catch (error) {
+      console.error("[sidecar] Failed to apply config update:", error)
+      if (seq !== undefined) {
+        const reason = error instanceof Error ? error.message : String(error)
+        sendAck({ type: "config_ack", id, seq, error: reason })
+      }
+      return
+    }
 
-      for (const line of lines) {
-        if (!line.trim()) continue
+    if (seq !== undefined) {
+      lastAppliedSeq = seq
+      sendAck({ type: "config_ack", id, seq })
+    }
+  }
+}
+
+/**
+ * Handle one stdin line from Tauri, answering requests on stdout.
+ *
+ * @param onConfigUpdate - Callback for config update messages, acknowledged once it returns
+ * @param onShutdown - Callback for shutdown requests, with the reason given by Tauri
+ * @param send - Writes replies, to stdout by default
+ */
+export function createHostMessageHandler(
+  onConfigUpdate: (message: unknown) => void,
+  onShutdown?: (reason: string | undefined) => void,
+  send: (message: SidecarMessage) => void = sendToHost
+) {
+  const handleConfigUpdate = createConfigUpdateHandler(onConfigUpdate, send)
 
-        const message = JSON.parse(line)
-        console.log("[sidecar] Parsed message type:", message.type)
+  return (line: string) => {
+    let message: HostMessage
+    try {
+      message = JSON.parse(line)
+    } catch (error) {
+      console.error("[sidecar] Error parsing stdin message:", error)
+      send({ type: "error", code: "invalid_message", message: "Stdin line is not valid JSON" })
+      return
+    }
 
-        if (message.type === "config_update" && message.configs) {
-          onConfigUpdate(message)
+    switch (message.type) {
+      case "hello": {
+        const { id, protocolVersion, minProtocolVersion } = message
+        const version = negotiateProtocolVersion(protocolVersion, minProtocolVersion)
+        if (version === null) {
+          const sidecarRange = `v${MIN_PROTOCOL_VERSION} to v${PROTOCOL_VERSION}`
+          const hostRange = `v${minProtocolVersion} to v${protocolVersion}`
+          send({
+            type: "error",
+            id,
+            code: "incompatible_protocol",
+            message: `the sidecar speaks ${sidecarRange}, the app ${hostRange}`
+          })
+        } else {
+          send({ type: "hello", id, protocolVersion: version })
         }
+        break
+      }
+      case "config_update":
+        if (message.configs) {
+          handleConfigUpdate(message)
+        } else {
+          send({
+            type: "error",
+            id: message.id,
+            code: "invalid_message",
+            message: "Config update without configs"
+          })
+        }
+        break
+      case "shutdown":
+        onShutdown?.(typeof message.reason === "string" ? message.reason : undefined)
+        break
+      case "ping":
+        send({ type: "pong", id: message.id })
+        break
+      default: {
+        const { id, type } = message as { id?: number; type?: unknown }
+        send({
+          type: "error",
+          id: typeof id === "number" ? id : undefined,
+          code: "unknown_message",
+          message: `Unknown message type: ${String(type)}`
+        })
       }
-    } catch (error) {
-      console.error("[sidecar] Error parsing stdin message:", error)
     }
-  })
+  }
+}
+
+/**
+ * Split a stream of text chunks into lines. A line split across chunks is kept until its
+ * newline arrives, since pipes do not preserve message boundaries.
+ *
+ * @param onLine - Called with each complete, non-empty line
+ * @returns Function to call with every received chunk
+ */
+export function createLineReader(onLine: (line: string) => void) {
+  let pending = ""
+
+  return (chunk: string) => {
+    const lines = (pending + chunk).split("\n")
+    pending = lines.pop() ?? ""
+
+    for (const line of lines) {
+      if (!line.trim()) continue
+      onLine(line)
+    }
+  }
+}
+
+/**
+ * Setup stdin listener for configuration updates from Tauri.
+ *
+ * @param onConfigUpdate - Callback for config update messages, acknowledged once it returns
+ * @param onShutdown - Callback for shutdown requests, with the reason given by Tauri
+ */
+export function setupStdinListener(
+  onConfigUpdate: (message: unknown) => void,
+  onShutdown?: (reason: string | undefined) => void
+) {
+  const readChunk = createLineReader(createHostMessageHandler(onConfigUpdate, onShutdown))
+  process.stdin.setEncoding("utf8")
+  process.stdin.on("data", readChunk)
 }
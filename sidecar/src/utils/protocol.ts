@@ -0,0 +1,69 @@
+import type { ConfigUpdateMessage, ShutdownMessage } from "../type"
+
+/**
+ * JSON-lines protocol between Tauri and the sidecar.
+ * Tauri writes HostMessages to stdin, the sidecar prints SidecarMessages to stdout, one JSON
+ * object per line. Replies carry the `id` of the request they answer.
+ */
+
+/** Protocol version spoken by this sidecar */
+export const PROTOCOL_VERSION = 1
+/** Oldest Tauri protocol version this sidecar still understands */
+export const MIN_PROTOCOL_VERSION = 1
+
+export interface HelloMessage {
+  type: "hello"
+  id: number
+  protocolVersion: number
+  minProtocolVersion: number
+}
+
+export interface PingMessage {
+  type: "ping"
+  id: number
+}
+
+export type HostMessage = HelloMessage | ConfigUpdateMessage | ShutdownMessage | PingMessage
+
+export type SidecarLogLevel = "debug" | "info" | "warn" | "error"
+export type SidecarStatusEvent = "listening" | "shutting_down" | "server_closed" | "exiting"
+export type ProtocolErrorCode = "incompatible_protocol" | "invalid_message" | "unknown_message"
+
+export interface ConfigAckMessage {
+  type: "config_ack"
+  id?: number
+  seq: number
+  /** Set when the update could not be applied */
+  error?: string
+}
+
+export type SidecarMessage =
+  | { type: "hello"; id: number; protocolVersion: number }
+  | ConfigAckMessage
+  | { type: "pong"; id: number }
+  | { type: "log"; level: SidecarLogLevel; message: string }
+  | { type: "status"; event: SidecarStatusEvent; message?: string }
+  | { type: "error"; id?: number; code: ProtocolErrorCode; message: string }
+
+/** Print a protocol message for Tauri */
+export function sendToHost(message: SidecarMessage) {
+  process.stdout.write(`${JSON.stringify(message)}\n`)
+}
+
+/** Record a message in the Tauri host log */
+export function logToHost(level: SidecarLogLevel, message: string) {
+  sendToHost({ type: "log", level, message })
+}
+
+/**
+ * Pick the newest protocol version both sides speak.
+ *
+ * @returns The agreed version, or null when the version ranges do not overlap
+ */
+export function negotiateProtocolVersion(
+  hostVersion: number,
+  hostMinVersion: number
+): number | null {
+  const version = Math.min(hostVersion, PROTOCOL_VERSION)
+  return version >= Math.max(hostMinVersion, MIN_PROTOCOL_VERSION) ? version : null
+}
@@ -0,0 +1,52 @@
+import type { ProviderConfig } from "../type"
+
+type FetchFunction = typeof globalThis.fetch
+
+/**
+ * Create a fetch that applies the per-provider request settings: extra headers and the
+ * request timeout. The timeout covers the wait for the response headers only, so a long
+ * streamed answer is not cut off. Returns `fetchImpl` unchanged when neither is
+ * configured, so providers keep their default fetch.
+ *
+ * @param config - Provider configuration from Tauri
+ * @param fetchImpl - Underlying fetch implementation
+ */
+export function createProviderRequestFetch(
+  config: ProviderConfig,
+  fetchImpl?: FetchFunction
+): FetchFunction | undefined {
+  const extraHeaders = Object.entries(config.extraHeaders ?? {})
+  const timeoutMs = config.requestTimeoutMs
+
+  if (extraHeaders.length === 0 && !timeoutMs) {
+    return fetchImpl
+  }
+
+  return async (input, init) => {
+    const headers = new Headers(init?.headers)
+    for (const [name, value] of extraHeaders) {
+      headers.set(name, value)
+    }
+
+    const timeout = new AbortController()
+    const timer = timeoutMs
+      ? setTimeout(
+          () => timeout.abort(new DOMException("Request timed out", "TimeoutError")),
+          timeoutMs
+        )
+      : undefined
+    const signals = [init?.signal, timeoutMs ? timeout.signal : undefined].filter(
+      (signal): signal is AbortSignal => !!signal
+    )
+
+    try {
+      return await (fetchImpl ?? globalThis.fetch)(input, {
+        ...init,
+        headers,
+        ...(signals.length > 0 ? { signal: AbortSignal.any(signals) } : {})
+      })
+    } finally {
+      clearTimeout(timer)
+    }
+  }
+}
@@ -0,0 +1,29 @@
+import { chmodSync, rmSync } from "node:fs"
+import type { Server } from "node:net"
+
+const SIDECAR_SOCKET_PATH_ENV_KEY = "MINDFLAYER_SIDECAR_SOCKET_PATH"
+
+/** Socket path set by Tauri when the Unix socket transport is selected, otherwise null */
+export function getSidecarSocketPath(): string | null {
+  return process.env[SIDECAR_SOCKET_PATH_ENV_KEY]?.trim() || null
+}
+
+/**
+ * Listen on a Unix domain socket only the current user can connect to.
+ * A socket left behind by a killed sidecar is removed first.
+ */
+export function listenOnUnixSocket(server: Server, socketPath: string, onListening: () => void) {
+  rmSync(socketPath, { force: true })
+
+  // Create the socket without group or other permissions, so there is no window in which
+  // another user could connect before the chmod below
+  const previousUmask = process.umask(0o177)
+  try {
+    server.listen(socketPath, () => {
+      chmodSync(socketPath, 0o600)
+      onListening()
+    })
+  } finally {
+    process.umask(previousUmask)
+  }
+}
@@ -4,9 +4,7 @@ use std::{fs, path::PathBuf};
 pub const CUSTOM_APP_SUPPORT_DIR_NAME: &str = "Mind Flayer";
 
 pub fn resolve_custom_app_support_dir() -> Result<PathBuf, String> {
-    let app_support_dir = dirs::data_local_dir()
-        .ok_or_else(|| "Failed to get local app data directory".to_string())?
-        .join(CUSTOM_APP_SUPPORT_DIR_NAME);
+    let app_support_dir = custom_app_support_dir()?;
 
     fs::create_dir_all(&app_support_dir).map_err(|e| {
         let message = format!(
@@ -20,3 +18,10 @@ pub fn resolve_custom_app_support_dir() -> Result<PathBuf, String> {
 
     Ok(app_support_dir)
 }
+
+/// Path of the app support directory, which may not exist yet
+pub fn custom_app_support_dir() -> Result<PathBuf, String> {
+    Ok(dirs::data_local_dir()
+        .ok_or_else(|| "Failed to get local app data directory".to_string())?
+        .join(CUSTOM_APP_SUPPORT_DIR_NAME))
+}
@@ -1,291 +1,2084 @@
-use aes_gcm::{
-    aead::{Aead, KeyInit},
-    Aes256Gcm, Nonce,
-};
-use base64::{engine::general_purpose, Engine as _};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod audit;
+mod bundle;
+mod connection_test;
+mod env_import;
+mod envelope;
+mod file_store;
+mod machine_key;
+mod passphrase;
+mod provider_config;
+#[cfg(target_os = "linux")]
+mod secret_service_store;
+
+use audit::AuditLog;
+pub use audit::{AuditAction, AuditEvent, AuditFilter, AuditVerification};
+pub use bundle::{ImportReport, MergeStrategy};
+pub use connection_test::ConnectionTestResult;
+pub use env_import::{EnvImportSelection, EnvKeyPreview};
+pub use file_store::{EncryptedFileStore, QuarantinedConfig};
+use provider_config::CachedConnectionTest;
+pub use provider_config::{
+    CredentialUpdate, ProviderConfig, ProviderConfigSummary, ProviderConfigUpdate,
+};
+#[cfg(target_os = "linux")]
+pub use secret_service_store::SecretServiceStore;
 
 const CONFIG_FILE_NAME: &str = "provider_configs.dat";
-const NONCE: &[u8; 12] = b"mind-flayer!"; // Fixed nonce for simplicity
+const BACKEND_PREFERENCE_FILE_NAME: &str = "secret_store.json";
 
-#[cfg(test)]
-use std::sync::Mutex;
+/// Storage backends available for provider secrets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretStoreBackend {
+    /// AES-GCM encrypted file in the app support directory
+    #[default]
+    EncryptedFile,
+    /// freedesktop Secret Service over D-Bus (Linux only)
+    SecretService,
+}
 
-#[cfg(test)]
-static TEST_FILE_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+/// Errors returned by the provider config store, serialized for the frontend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum KeychainError {
+    /// Stored keys exist but could not be decrypted; the file was moved aside for recovery
+    #[serde(rename_all = "camelCase")]
+    Unreadable {
+        message: String,
+        quarantine_path: String,
+    },
+    /// Stored keys are protected by a master passphrase that has not been entered yet
+    #[serde(rename_all = "camelCase")]
+    Locked { message: String },
+    /// A provider config field failed validation; `field` uses the frontend (camelCase) name
+    #[serde(rename_all = "camelCase")]
+    Invalid { field: String, message: String },
+    #[serde(rename_all = "camelCase")]
+    Other { message: String },
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ProviderConfig {
-    pub api_key: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub base_url: Option<String>,
+impl std::fmt::Display for KeychainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeychainError::Unreadable { message, .. }
+            | KeychainError::Locked { message }
+            | KeychainError::Invalid { message, .. }
+            | KeychainError::Other { message } => f.write_str(message),
+        }
+    }
 }
 
-/// Get the config file path
-fn get_config_file_path() -> Result<PathBuf, String> {
-    #[cfg(test)]
-    {
-        let guard = TEST_FILE_PATH.lock().unwrap();
-        if let Some(path) = guard.as_ref() {
-            return Ok(path.clone());
+impl From<String> for KeychainError {
+    fn from(message: String) -> Self {
+        KeychainError::Other { message }
+    }
+}
+
+impl From<KeychainError> for String {
+    fn from(error: KeychainError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Builds the configuration to save from the stored one, or `None` to leave it as it is,
+/// see [`SecretStore::upsert`]
+pub type UpsertConfig<'a> =
+    dyn FnMut(Option<&ProviderConfig>) -> Result<Option<ProviderConfig>, KeychainError> + Send + 'a;
+
+/// Storage backend for provider configurations
+pub trait SecretStore: Send + Sync {
+    /// Which backend this store implements
+    fn backend(&self) -> SecretStoreBackend;
+
+    /// Load every stored provider configuration
+    fn load_all(&self) -> Result<HashMap<String, ProviderConfig>, KeychainError>;
+
+    /// Insert or replace the configuration for one provider
+    fn save(&self, provider: &str, config: &ProviderConfig) -> Result<(), KeychainError>;
+
+    /// Remove the configuration for one provider, succeeding if it does not exist
+    fn delete(&self, provider: &str) -> Result<(), KeychainError>;
+
+    /// Change the stored configuration of one provider, doing nothing if it is not stored.
+    /// `update` returns whether it changed anything, otherwise nothing is written.
+    ///
+    /// Backends that can lock their storage read and write under one lock, so a concurrent
+    /// save is never overwritten with the state from before it.
+    fn update(
+        &self,
+        provider: &str,
+        update: &mut (dyn FnMut(&mut ProviderConfig) -> bool + Send),
+    ) -> Result<(), KeychainError> {
+        let Some(mut config) = self.load_all()?.remove(provider) else {
+            return Ok(());
+        };
+        if update(&mut config) {
+            self.save(provider, &config)?;
         }
+        Ok(())
     }
 
-    let app_dir = crate::app_support::resolve_custom_app_support_dir()?;
-    Ok(app_dir.join(CONFIG_FILE_NAME))
+    /// Save the configuration `upsert` builds from the stored one, `None` if the provider is
+    /// not stored yet. Nothing is written if `upsert` fails or returns `None`.
+    ///
+    /// Like [`SecretStore::update`], backends that can lock their storage hold the lock from
+    /// the read to the write.
+    fn upsert(&self, provider: &str, upsert: &mut UpsertConfig<'_>) -> Result<(), KeychainError> {
+        let stored = self.load_all()?.remove(provider);
+        match upsert(stored.as_ref())? {
+            Some(config) => self.save(provider, &config),
+            None => Ok(()),
+        }
+    }
 }
 
-/// Get encryption key derived from machine-specific data
-fn get_encryption_key() -> [u8; 32] {
-    // Derive a machine-specific key
-    let machine_id = whoami::devicename();
-    let mut hasher = Sha256::new();
-    hasher.update(b"mind-flayer-v1");
-    hasher.update(machine_id.as_bytes());
-    let result = hasher.finalize();
-    result.into()
+/// Active and available secret store backends, returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretStoreStatus {
+    pub active: SecretStoreBackend,
+    pub available: Vec<SecretStoreBackend>,
 }
 
-/// Save provider configuration to encrypted local storage
-pub fn save_config(provider: &str, config: &ProviderConfig) -> Result<(), String> {
-    info!("[Storage] Saving config for provider: {}", provider);
+/// Master passphrase state of the encrypted file store, returned to the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PassphraseStatus {
+    pub enabled: bool,
+    pub locked: bool,
+}
 
-    let mut all_configs = get_all_configs_internal()?;
-    all_configs.insert(provider.to_string(), config.clone());
-    save_all_configs_internal(&all_configs)?;
+/// Credential that expired or is about to, sent to the frontend in an event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiringCredential {
+    pub provider: String,
+    pub label: String,
+    /// Milliseconds since the Unix epoch
+    pub expires_at: u64,
+    pub expired: bool,
+}
 
-    info!("[Storage] Successfully saved config for {}", provider);
-    Ok(())
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BackendPreference {
+    #[serde(default)]
+    backend: SecretStoreBackend,
 }
 
-/// Get provider configuration from encrypted local storage
-pub fn get_config(provider: &str) -> Result<ProviderConfig, String> {
-    let all_configs = get_all_configs_internal()?;
-    all_configs
-        .get(provider)
-        .cloned()
-        .ok_or_else(|| format!("Provider '{}' not found", provider))
+/// Modification time, length and identity of the config file when it was read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    /// Device and inode. Every write replaces the file, so a new inode reveals a write that
+    /// kept the length within the modification time's resolution.
+    #[cfg(unix)]
+    file_id: (u64, u64),
 }
 
-/// Delete provider configuration from encrypted local storage
-pub fn delete_config(provider: &str) -> Result<(), String> {
-    info!("[Storage] Deleting config for provider: {}", provider);
+/// Decrypted configs together with the state of the file they were read from
+struct CachedConfigs {
+    stamp: Option<FileStamp>,
+    configs: HashMap<String, ProviderConfig>,
+}
+
+/// Provider config storage, created once and managed in Tauri state.
+///
+/// Reads of the encrypted file backend are cached in memory. The cache is dropped on every
+/// write through the store, and a read first compares the file's modification time, length
+/// and inode, so changes made by another process are picked up. Secret Service entries are
+/// not cached because other applications can change them without touching any file.
+pub struct ConfigStore {
+    config_path: PathBuf,
+    preference_path: PathBuf,
+    audit_log: AuditLog,
+    /// Also serializes writes, so a read never caches a file that is being replaced
+    cache: Mutex<Option<CachedConfigs>>,
+    /// [`ProviderConfig::push_fingerprint`] of every provider in the last sidecar push
+    pushed: Mutex<BTreeMap<String, String>>,
+    /// Master passphrase key while the encrypted file is unlocked
+    unlocked_key: Arc<passphrase::UnlockedKey>,
+}
 
-    let mut all_configs = get_all_configs_internal()?;
-    all_configs.remove(provider);
-    save_all_configs_internal(&all_configs)?;
+/// Test a provider config against the provider's API. Only the network is used, the result
+/// is cached with [`ConfigStore::record_test_result`].
+pub async fn test_config(
+    provider: &str,
+    config: &ProviderConfig,
+    proxy_url: Option<&str>,
+) -> Result<ConnectionTestResult, KeychainError> {
+    config.validate()?;
 
-    info!("[Storage] Successfully deleted config for {}", provider);
-    Ok(())
+    let result = connection_test::test_connection(provider, config, proxy_url).await?;
+    info!(
+        "[Storage] Connection test for {}: {:?} in {} ms",
+        provider, result.status, result.latency_ms
+    );
+    Ok(result)
 }
 
-/// Get all provider configurations from encrypted local storage
-pub fn get_all_configs_providers() -> HashMap<String, ProviderConfig> {
-    debug!("[Storage] Getting all configs...");
-    match get_all_configs_internal() {
-        Ok(configs) => {
-            info!(
-                "[Storage] Retrieved configs from {} providers",
-                configs.len()
+/// Check whether a backend can be used on this machine
+fn is_backend_available(backend: SecretStoreBackend) -> bool {
+    match backend {
+        SecretStoreBackend::EncryptedFile => true,
+        #[cfg(target_os = "linux")]
+        SecretStoreBackend::SecretService => SecretServiceStore::is_available(),
+        #[cfg(not(target_os = "linux"))]
+        SecretStoreBackend::SecretService => false,
+    }
+}
+
+/// Milliseconds since the Unix epoch
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Read the current stamp of a file, `None` if it does not exist
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    fs::metadata(path).ok().map(|metadata| FileStamp {
+        modified: metadata.modified().ok(),
+        len: metadata.len(),
+        #[cfg(unix)]
+        file_id: {
+            use std::os::unix::fs::MetadataExt;
+            (metadata.dev(), metadata.ino())
+        },
+    })
+}
+
+impl ConfigStore {
+    /// Create a store for the config file at `config_path`, remembering the active backend
+    /// in `preference_path`
+    pub fn new(config_path: PathBuf, preference_path: PathBuf) -> Self {
+        Self {
+            audit_log: AuditLog::new(config_path.with_extension("audit.jsonl")),
+            config_path,
+            preference_path,
+            cache: Mutex::new(None),
+            pushed: Mutex::new(BTreeMap::new()),
+            unlocked_key: Arc::default(),
+        }
+    }
+
+    /// Create the store for the files in the app support directory.
+    ///
+    /// The directory is only created when a file is written, so a missing or unwritable
+    /// directory fails the commands that need it rather than app startup.
+    pub fn from_app_support_dir() -> Result<Self, String> {
+        let app_dir = crate::app_support::custom_app_support_dir()?;
+        Ok(Self::new(
+            app_dir.join(CONFIG_FILE_NAME),
+            app_dir.join(BACKEND_PREFERENCE_FILE_NAME),
+        ))
+    }
+
+    fn lock_cache(&self) -> MutexGuard<'_, Option<CachedConfigs>> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run a write and drop the cache afterwards, whether or not the write succeeded
+    fn write<T>(
+        &self,
+        write: impl FnOnce() -> Result<T, KeychainError>,
+    ) -> Result<T, KeychainError> {
+        let mut cache = self.lock_cache();
+        let result = write();
+        *cache = None;
+        result
+    }
+
+    /// Add an entry to the audit log. A change that already happened is not rolled back
+    /// if this fails, so the failure is only logged.
+    pub fn record_event(&self, command: &str, action: AuditAction, provider: Option<&str>) {
+        if let Err(e) = self.audit_log.append(command, action, provider) {
+            error!(
+                "[Storage] Failed to record {:?} in audit log: {}",
+                action, e
             );
-            configs
         }
-        Err(e) => {
-            error!("[Storage] Failed to get configs: {}", e);
-            HashMap::new()
+    }
+
+    /// Note that the active credentials of `pushed` were just handed to the sidecar.
+    ///
+    /// Unchanged credentials are pushed again on every config change and sidecar restart,
+    /// so only providers whose credentials differ from the previous push are audited and
+    /// get a new `last_pushed_at`.
+    pub fn record_push<'a>(
+        &self,
+        command: &str,
+        pushed: impl IntoIterator<Item = (&'a String, &'a ProviderConfig)>,
+    ) -> Result<(), KeychainError> {
+        let fingerprints: BTreeMap<String, String> = pushed
+            .into_iter()
+            .map(|(provider, config)| (provider.clone(), config.push_fingerprint()))
+            .collect();
+        let mut last_pushed = self.pushed.lock().unwrap_or_else(|e| e.into_inner());
+        let changed: Vec<(&String, &String)> = fingerprints
+            .iter()
+            .filter(|(provider, fingerprint)| last_pushed.get(*provider) != Some(*fingerprint))
+            .collect();
+        for (provider, _) in &changed {
+            self.record_event(command, AuditAction::PushedToSidecar, Some(provider));
         }
+        let result = if changed.is_empty() {
+            Ok(())
+        } else {
+            self.mark_pushed(&changed)
+        };
+        *last_pushed = fingerprints;
+        result
     }
-}
 
-/// List all configured providers
-pub fn list_all_providers() -> Vec<String> {
-    get_all_configs_providers().keys().cloned().collect()
-}
+    /// Store the push time on the active credentials of each provider, unless its
+    /// credentials changed since the push with the given fingerprint
+    fn mark_pushed(&self, pushed: &[(&String, &String)]) -> Result<(), KeychainError> {
+        let now = now_ms();
+        let store = self.active_store()?;
+        self.write(|| {
+            for (provider, fingerprint) in pushed {
+                // Not a user edit, so `updated_at` is left alone
+                store.update(provider, &mut |config| {
+                    if config.push_fingerprint() != **fingerprint {
+                        return false;
+                    }
+                    let mut changed = false;
+                    for credential in config.credentials.iter_mut().filter(|c| c.active) {
+                        credential.metadata.last_pushed_at = Some(now);
+                        changed = true;
+                    }
+                    changed
+                })?;
+            }
+            Ok(())
+        })
+    }
 
-/// Internal: Get all configs from encrypted file
-fn get_all_configs_internal() -> Result<HashMap<String, ProviderConfig>, String> {
-    let config_path = get_config_file_path()?;
+    /// Audit log entries matching `filter`, oldest first
+    pub fn list_audit_events(
+        &self,
+        filter: &AuditFilter,
+    ) -> Result<Vec<AuditEvent>, KeychainError> {
+        self.audit_log.list(filter)
+    }
 
-    if !config_path.exists() {
-        debug!("[Storage] Config file does not exist, returning empty map");
-        return Ok(HashMap::new());
+    /// Check that no audit log entry was modified, removed or reordered
+    pub fn verify_audit_log(&self) -> Result<AuditVerification, KeychainError> {
+        self.audit_log.verify()
     }
 
-    // Read as string (base64 encoded text)
-    let encrypted_data = fs::read_to_string(&config_path).map_err(|e| {
-        error!("[Storage] Failed to read config file: {}", e);
-        e.to_string()
-    })?;
+    fn load_backend_preference(&self) -> SecretStoreBackend {
+        let raw = match fs::read_to_string(&self.preference_path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return SecretStoreBackend::default();
+            }
+            Err(e) => {
+                warn!(
+                    "[Storage] Failed to read secret store preference '{}': {}",
+                    self.preference_path.display(),
+                    e
+                );
+                return SecretStoreBackend::default();
+            }
+        };
 
-    if encrypted_data.is_empty() {
-        debug!("[Storage] Config file is empty, returning empty map");
-        return Ok(HashMap::new());
+        match serde_json::from_str::<BackendPreference>(&raw) {
+            Ok(preference) => preference.backend,
+            Err(e) => {
+                warn!(
+                    "[Storage] Failed to parse secret store preference '{}': {}",
+                    self.preference_path.display(),
+                    e
+                );
+                SecretStoreBackend::default()
+            }
+        }
     }
 
-    // Decode from base64
-    let encrypted_bytes = general_purpose::STANDARD
-        .decode(encrypted_data.trim())
-        .map_err(|e| {
-            error!("[Storage] Failed to decode base64: {}", e);
-            error!("[Storage] Config file may be corrupted, deleting and starting fresh");
-            // Delete corrupted file
-            let _ = fs::remove_file(&config_path);
-            "Config file corrupted, please try again".to_string()
-        })?;
+    fn save_backend_preference(&self, backend: SecretStoreBackend) -> Result<(), String> {
+        let serialized = serde_json::to_string_pretty(&BackendPreference { backend })
+            .map_err(|e| format!("Failed to serialize secret store preference: {}", e))?;
+
+        file_store::create_parent_dir(&self.preference_path)?;
+        fs::write(&self.preference_path, serialized).map_err(|e| {
+            format!(
+                "Failed to write secret store preference '{}': {}",
+                self.preference_path.display(),
+                e
+            )
+        })
+    }
+
+    fn file_store(&self) -> EncryptedFileStore {
+        EncryptedFileStore::new(self.config_path.clone(), Arc::clone(&self.unlocked_key))
+    }
+
+    /// Create the store implementing the given backend
+    fn open_store(&self, backend: SecretStoreBackend) -> Result<Box<dyn SecretStore>, String> {
+        match backend {
+            SecretStoreBackend::EncryptedFile => Ok(Box::new(self.file_store())),
+            #[cfg(target_os = "linux")]
+            SecretStoreBackend::SecretService => Ok(Box::new(SecretServiceStore::new())),
+            #[cfg(not(target_os = "linux"))]
+            SecretStoreBackend::SecretService => {
+                Err("Secret Service backend is only supported on Linux".to_string())
+            }
+        }
+    }
+
+    /// Remove every provider configuration from a backend after its contents were migrated
+    fn clear_store(&self, backend: SecretStoreBackend) -> Result<(), String> {
+        match backend {
+            SecretStoreBackend::EncryptedFile => self.file_store().clear(),
+            #[cfg(target_os = "linux")]
+            SecretStoreBackend::SecretService => SecretServiceStore::new().clear(),
+            #[cfg(not(target_os = "linux"))]
+            SecretStoreBackend::SecretService => Ok(()),
+        }
+    }
+
+    /// Open the store for the currently selected backend
+    fn active_store(&self) -> Result<Box<dyn SecretStore>, String> {
+        self.open_store(self.load_backend_preference())
+    }
+
+    /// Load every provider configuration from the active backend, using the cache if the
+    /// config file has not changed since it was filled
+    fn load_all(&self) -> Result<HashMap<String, ProviderConfig>, KeychainError> {
+        let backend = self.load_backend_preference();
+        if backend != SecretStoreBackend::EncryptedFile {
+            return self.open_store(backend)?.load_all();
+        }
+
+        let mut cache = self.lock_cache();
+        // Taken before reading, so a file replaced during the read is read again next time
+        let stamp = file_stamp(&self.config_path);
+        if let Some(cached) = cache.as_ref().filter(|cached| cached.stamp == stamp) {
+            return Ok(cached.configs.clone());
+        }
+
+        *cache = None;
+        let configs = self.file_store().load_all()?;
+        debug!("[Storage] Cached {} provider configs", configs.len());
+        *cache = Some(CachedConfigs {
+            stamp,
+            configs: configs.clone(),
+        });
+        Ok(configs)
+    }
+
+    /// Load the configuration for one provider
+    fn load(&self, provider: &str) -> Result<Option<ProviderConfig>, KeychainError> {
+        Ok(self.load_all()?.remove(provider))
+    }
+
+    /// Save provider configuration to the active secret store, stamping its timestamps
+    pub fn save_config(
+        &self,
+        command: &str,
+        provider: &str,
+        config: &ProviderConfig,
+    ) -> Result<(), KeychainError> {
+        self.save_with(command, provider, |_| Ok(config.clone()))
+    }
 
-    // Decrypt
-    let key = get_encryption_key();
-    let cipher = Aes256Gcm::new(&key.into());
-    let nonce = Nonce::from_slice(NONCE);
-
-    let decrypted_data = cipher
-        .decrypt(nonce, encrypted_bytes.as_ref())
-        .map_err(|e| {
-            error!("[Storage] Failed to decrypt config: {}", e);
-            error!("[Storage] Config file may be corrupted, deleting and starting fresh");
-            // Delete corrupted file
-            let _ = fs::remove_file(&config_path);
-            "Failed to decrypt config, please try again".to_string()
+    /// Save the configuration `build` makes from the stored one, stamping its timestamps.
+    ///
+    /// The stored entry is read and the result written under the store lock, so an edit made
+    /// by another instance in between is never lost.
+    fn save_with(
+        &self,
+        command: &str,
+        provider: &str,
+        mut build: impl FnMut(Option<&ProviderConfig>) -> Result<ProviderConfig, KeychainError> + Send,
+    ) -> Result<(), KeychainError> {
+        self.upsert_with(command, provider, |stored| build(stored).map(Some))
+            .map(|_| ())
+    }
+
+    /// Like [`ConfigStore::save_with`], but `build` may return `None` to leave the stored
+    /// entry alone. Returns whether the provider was created or updated, `None` if not.
+    fn upsert_with(
+        &self,
+        command: &str,
+        provider: &str,
+        mut build: impl FnMut(Option<&ProviderConfig>) -> Result<Option<ProviderConfig>, KeychainError>
+            + Send,
+    ) -> Result<Option<AuditAction>, KeychainError> {
+        let store = self.active_store()?;
+        info!(
+            "[Storage] Saving config for provider: {} ({:?})",
+            provider,
+            store.backend()
+        );
+
+        let mut action = None;
+        self.write(|| {
+            store.upsert(provider, &mut |stored| {
+                action = None;
+                let Some(mut config) = build(stored)? else {
+                    return Ok(None);
+                };
+                config.validate()?;
+
+                let now = now_ms();
+                config.created_at.get_or_insert(now);
+                config.updated_at = Some(now);
+                config.stamp_credentials(stored, now);
+                action = Some(match stored {
+                    Some(_) => AuditAction::Updated,
+                    None => AuditAction::Created,
+                });
+                Ok(Some(config))
+            })
         })?;
+        let Some(action) = action else {
+            info!("[Storage] Left config for {} unchanged", provider);
+            return Ok(None);
+        };
+        self.record_event(command, action, Some(provider));
+
+        info!("[Storage] Successfully saved config for {}", provider);
+        Ok(Some(action))
+    }
+
+    /// Set the primary API key of a provider, keeping any other credentials it has
+    pub fn save_api_key(
+        &self,
+        command: &str,
+        provider: &str,
+        api_key: String,
+        base_url: Option<String>,
+    ) -> Result<(), KeychainError> {
+        self.save_with(command, provider, |stored| {
+            let mut config = stored.cloned().unwrap_or_default();
+            config.set_primary_api_key(api_key.clone());
+            config.base_url = base_url.clone();
+            Ok(config)
+        })
+    }
+
+    /// Change the base URL of a stored provider, keeping its keys
+    pub fn save_base_url(
+        &self,
+        command: &str,
+        provider: &str,
+        base_url: Option<String>,
+    ) -> Result<(), KeychainError> {
+        self.save_with(command, provider, |stored| {
+            let mut config = stored
+                .cloned()
+                .ok_or_else(|| format!("Provider '{}' not found", provider))?;
+            config.base_url = base_url.clone();
+            Ok(config)
+        })
+    }
 
-    // Parse JSON
-    let json_str = String::from_utf8(decrypted_data).map_err(|e| {
-        error!("[Storage] Failed to parse decrypted data as UTF-8: {}", e);
-        e.to_string()
-    })?;
+    /// Replace the labelled API keys of a provider, keeping its other settings.
+    ///
+    /// Credentials sent without a key keep the stored key with the same label.
+    pub fn save_credentials(
+        &self,
+        command: &str,
+        provider: &str,
+        credentials: Vec<CredentialUpdate>,
+        base_url: Option<String>,
+    ) -> Result<(), KeychainError> {
+        self.save_with(command, provider, |stored| {
+            let mut config = stored.cloned().unwrap_or_default();
+            config.apply_credential_updates(credentials.clone());
+            config.base_url = base_url.clone();
+            Ok(config)
+        })
+    }
+
+    /// Replace the whole configuration of a provider.
+    ///
+    /// Credentials sent without a key keep the stored key with the same label. Fields the
+    /// caller does not know about are carried over from the stored entry, so older
+    /// frontends cannot drop settings written by newer versions.
+    pub fn update_config(
+        &self,
+        command: &str,
+        provider: &str,
+        update: ProviderConfigUpdate,
+    ) -> Result<(), KeychainError> {
+        self.save_with(command, provider, |stored| {
+            let update = update.clone();
+            let mut config = ProviderConfig {
+                credentials: stored
+                    .map(|stored| stored.credentials.clone())
+                    .unwrap_or_default(),
+                base_url: update.base_url,
+                extra_headers: update.extra_headers,
+                organization: update.organization,
+                project: update.project,
+                request_timeout_ms: update.request_timeout_ms,
+                default_model: update.default_model,
+                api_version: update.api_version,
+                unknown_fields: update.unknown_fields,
+                ..Default::default()
+            };
+            config.apply_credential_updates(update.credentials);
+            if let Some(stored) = stored {
+                config.created_at = stored.created_at;
+                // Dropped by the fingerprint check if the connection settings changed
+                config.last_test = stored.last_test.clone();
+                for (key, value) in &stored.unknown_fields {
+                    config
+                        .unknown_fields
+                        .entry(key.clone())
+                        .or_insert_with(|| value.clone());
+                }
+            }
+            Ok(config)
+        })
+    }
+
+    /// Get provider configuration from the active secret store
+    pub fn get_config(&self, provider: &str) -> Result<ProviderConfig, KeychainError> {
+        self.load(provider)?
+            .ok_or_else(|| format!("Provider '{}' not found", provider).into())
+    }
+
+    /// Get provider configuration with every API key masked, or `None` if it is not stored
+    pub fn get_config_summary(
+        &self,
+        provider: &str,
+    ) -> Result<Option<ProviderConfigSummary>, KeychainError> {
+        Ok(self.load(provider)?.map(|config| config.summary(provider)))
+    }
+
+    /// Return the plaintext key of one credential, defaulting to the primary key.
+    ///
+    /// When a master passphrase is set it has to be entered again, even while unlocked.
+    pub fn reveal_api_key(
+        &self,
+        command: &str,
+        provider: &str,
+        label: Option<&str>,
+        passphrase: Option<&str>,
+    ) -> Result<String, KeychainError> {
+        if self.get_passphrase_status()?.enabled {
+            let passphrase = passphrase.ok_or_else(|| KeychainError::Locked {
+                message: "Enter the master passphrase to reveal API keys".to_string(),
+            })?;
+            self.passphrase_store()?.confirm_passphrase(passphrase)?;
+        }
+
+        let config = self.get_config(provider)?;
+        let api_key = match label {
+            Some(label) => config
+                .credentials
+                .into_iter()
+                .find(|credential| credential.label == label)
+                .map(|credential| credential.api_key)
+                .ok_or_else(|| {
+                    format!(
+                        "Provider '{}' has no API key labelled '{}'",
+                        provider, label
+                    )
+                })?,
+            None => config
+                .api_key()
+                .map(str::to_string)
+                .ok_or_else(|| format!("Provider '{}' has no active API key", provider))?,
+        };
 
-    let configs: HashMap<String, ProviderConfig> =
-        serde_json::from_str(&json_str).map_err(|e| {
-            error!("[Storage] Failed to deserialize configs: {}", e);
-            e.to_string()
+        // Unlike a change, a reveal that cannot be audited is refused
+        self.audit_log
+            .append(command, AuditAction::Revealed, Some(provider))?;
+        info!("[Storage] Revealed API key for provider: {}", provider);
+        Ok(api_key)
+    }
+
+    /// Cache the result of [`test_config`] with the stored config of `provider`.
+    ///
+    /// The result is only cached if the tested connection settings match the stored config,
+    /// so an unsaved config only updates the cache once it is saved unchanged and tested
+    /// again. A failure is only logged, the test itself succeeded.
+    pub fn record_test_result(
+        &self,
+        provider: &str,
+        tested: &ProviderConfig,
+        result: &ConnectionTestResult,
+    ) {
+        if let Err(e) = self.cache_test_result(provider, tested, result) {
+            warn!(
+                "[Storage] Failed to cache connection test for {}: {}",
+                provider, e
+            );
+        }
+    }
+
+    fn cache_test_result(
+        &self,
+        provider: &str,
+        tested: &ProviderConfig,
+        result: &ConnectionTestResult,
+    ) -> Result<(), KeychainError> {
+        let fingerprint = tested.connection_fingerprint();
+        let tested_key = tested.api_key();
+        let store = self.active_store()?;
+        // Compared with the config stored right now, so a key saved during the test is kept
+        // and the result is only cached if it still applies
+        self.write(|| {
+            store.update(provider, &mut |stored| {
+                if stored.connection_fingerprint() != fingerprint {
+                    return false;
+                }
+                stored.last_test = Some(CachedConnectionTest {
+                    fingerprint: fingerprint.clone(),
+                    result: result.clone(),
+                });
+                if result.status == connection_test::ConnectionTestStatus::Valid {
+                    for credential in &mut stored.credentials {
+                        if Some(credential.api_key.as_str()) == tested_key {
+                            credential.metadata.last_validated_at = Some(result.tested_at);
+                        }
+                    }
+                }
+                // Not a user edit, so `updated_at` is left alone
+                true
+            })
+        })
+    }
+
+    /// List provider keys found in the process environment and an optional `.env` file
+    pub fn discover_env_keys(
+        &self,
+        env_file: Option<&Path>,
+    ) -> Result<Vec<EnvKeyPreview>, KeychainError> {
+        let stored = self.load_all()?;
+        Ok(env_import::discover(std::env::vars(), env_file)?
+            .iter()
+            .map(|key| key.preview(stored.get(&key.provider)))
+            .collect())
+    }
+
+    /// Import the selected discovered keys as the primary key of their providers.
+    ///
+    /// The sources are scanned again so plaintext keys never travel through the frontend.
+    /// Returns the imported providers.
+    pub fn import_env_keys(
+        &self,
+        command: &str,
+        env_file: Option<&Path>,
+        selections: &[EnvImportSelection],
+    ) -> Result<Vec<String>, KeychainError> {
+        self.import_discovered_keys(
+            command,
+            env_import::discover(std::env::vars(), env_file)?,
+            selections,
+        )
+    }
+
+    fn import_discovered_keys(
+        &self,
+        command: &str,
+        discovered: Vec<env_import::DiscoveredKey>,
+        selections: &[EnvImportSelection],
+    ) -> Result<Vec<String>, KeychainError> {
+        let mut selected = Vec::new();
+        for selection in selections {
+            if selected
+                .iter()
+                .any(|key: &env_import::DiscoveredKey| key.provider == selection.provider)
+            {
+                return Err(format!(
+                    "Provider '{}' was selected from more than one source",
+                    selection.provider
+                )
+                .into());
+            }
+            let key = discovered
+                .iter()
+                .find(|key| key.provider == selection.provider && key.source == selection.source)
+                .ok_or_else(|| {
+                    format!(
+                        "No key for provider '{}' found in {:?}",
+                        selection.provider, selection.source
+                    )
+                })?;
+            selected.push(key.clone());
+        }
+
+        let mut imported = Vec::new();
+        for key in selected {
+            // A stored base URL is kept when the source sets none
+            self.save_with(command, &key.provider, |stored| {
+                let mut config = stored.cloned().unwrap_or_default();
+                config.set_primary_api_key(key.api_key.clone());
+                if key.base_url.is_some() {
+                    config.base_url = key.base_url.clone();
+                }
+                Ok(config)
+            })?;
+            info!(
+                "[Storage] Imported API key for {} from {}",
+                key.provider, key.variable
+            );
+            imported.push(key.provider);
+        }
+        Ok(imported)
+    }
+
+    /// Write the selected providers, or all of them, to a passphrase-encrypted bundle.
+    ///
+    /// Unlike the config file the bundle is not bound to this machine. Returns the exported
+    /// providers.
+    pub fn export_configs(
+        &self,
+        command: &str,
+        path: &Path,
+        bundle_passphrase: &str,
+        providers: Option<&[String]>,
+    ) -> Result<Vec<String>, KeychainError> {
+        let mut configs = self.load_all()?;
+        let selected: BTreeMap<String, ProviderConfig> = match providers {
+            Some(providers) => providers
+                .iter()
+                .map(|provider| {
+                    configs
+                        .remove(provider)
+                        .map(|config| (provider.clone(), config))
+                        .ok_or_else(|| format!("Provider '{}' not found", provider))
+                })
+                .collect::<Result<_, _>>()?,
+            None => configs.into_iter().collect(),
+        };
+        if selected.is_empty() {
+            return Err("No provider configs to export".to_string().into());
+        }
+
+        let bundle = bundle::seal(selected.clone(), bundle_passphrase)?;
+        // Keys leave the store here, so like a reveal this is refused if it cannot be audited
+        for provider in selected.keys() {
+            self.audit_log
+                .append(command, AuditAction::Exported, Some(provider))?;
+        }
+        file_store::write_atomically(path, &bundle)?;
+
+        info!(
+            "[Storage] Exported {} provider configs to '{}'",
+            selected.len(),
+            path.display()
+        );
+        Ok(selected.into_keys().collect())
+    }
+
+    /// Import providers from a bundle written by [`ConfigStore::export_configs`].
+    ///
+    /// With `dry_run` nothing is written and the report only shows what would happen.
+    pub fn import_configs(
+        &self,
+        command: &str,
+        path: &Path,
+        bundle_passphrase: &str,
+        strategy: MergeStrategy,
+        providers: Option<&[String]>,
+        dry_run: bool,
+    ) -> Result<ImportReport, KeychainError> {
+        let raw = fs::read(path)
+            .map_err(|e| format!("Failed to read export bundle '{}': {}", path.display(), e))?;
+        let mut incoming = bundle::open(&raw, bundle_passphrase)?;
+        if let Some(providers) = providers {
+            if let Some(missing) = providers
+                .iter()
+                .find(|provider| !incoming.contains_key(*provider))
+            {
+                return Err(format!("Provider '{}' is not in the export bundle", missing).into());
+            }
+            incoming.retain(|provider, _| providers.contains(provider));
+        }
+
+        let mut entries = bundle::plan_import(&self.load_all()?, &incoming, strategy);
+        if !dry_run {
+            // Check the planned writes first, so an invalid entry fails before anything is
+            // imported. Providers are written one at a time, so a write that fails leaves
+            // the ones before it imported.
+            for entry in &entries {
+                if entry.action != bundle::ImportAction::Skip {
+                    incoming[&entry.provider].validate()?;
+                }
+            }
+            for entry in &mut entries {
+                let config = &incoming[&entry.provider];
+                let mut action = entry.action;
+                self.upsert_with(command, &entry.provider, |stored| {
+                    // Decided again under the store lock, in case another instance changed
+                    // the provider since the plan was made
+                    action = bundle::import_action(stored, config, strategy);
+                    Ok((action != bundle::ImportAction::Skip).then(|| config.clone()))
+                })?;
+                entry.action = action;
+            }
+            info!(
+                "[Storage] Imported provider configs from '{}' ({:?})",
+                path.display(),
+                strategy
+            );
+        }
+
+        Ok(ImportReport { dry_run, entries })
+    }
+
+    /// Delete provider configuration from the active secret store
+    pub fn delete_config(&self, command: &str, provider: &str) -> Result<(), KeychainError> {
+        let store = self.active_store()?;
+        info!(
+            "[Storage] Deleting config for provider: {} ({:?})",
+            provider,
+            store.backend()
+        );
+
+        self.write(|| store.delete(provider))?;
+        self.record_event(command, AuditAction::Deleted, Some(provider));
+
+        info!("[Storage] Successfully deleted config for {}", provider);
+        Ok(())
+    }
+
+    /// Get all provider configurations from the active secret store.
+    ///
+    /// Fails instead of returning an empty map, so a store that cannot be read is never
+    /// mistaken for one without providers.
+    pub fn get_all_configs_providers(
+        &self,
+    ) -> Result<HashMap<String, ProviderConfig>, KeychainError> {
+        debug!("[Storage] Getting all configs...");
+        let configs = self.load_all().inspect_err(|e| {
+            error!("[Storage] Failed to get configs: {}", e);
         })?;
+        info!(
+            "[Storage] Retrieved configs from {} providers",
+            configs.len()
+        );
+        Ok(configs)
+    }
 
-    Ok(configs)
-}
+    /// Summaries of every stored provider, including credential metadata, sorted by provider
+    pub fn list_config_summaries(&self) -> Result<Vec<ProviderConfigSummary>, KeychainError> {
+        let configs: BTreeMap<String, ProviderConfig> = self.load_all()?.into_iter().collect();
+        Ok(configs
+            .iter()
+            .map(|(provider, config)| config.summary(provider))
+            .collect())
+    }
 
-/// Internal: Save all configs to encrypted file
-fn save_all_configs_internal(configs: &HashMap<String, ProviderConfig>) -> Result<(), String> {
-    debug!("[Storage] Saving configs for {} providers", configs.len());
+    /// Credentials that expire within `within_ms`, or already have, soonest first
+    pub fn expiring_credentials(
+        &self,
+        within_ms: u64,
+    ) -> Result<Vec<ExpiringCredential>, KeychainError> {
+        let now = now_ms();
+        let mut expiring: Vec<ExpiringCredential> = self
+            .load_all()?
+            .into_iter()
+            .flat_map(|(provider, config)| {
+                config
+                    .credentials
+                    .into_iter()
+                    .filter_map(move |credential| {
+                        let expires_at = credential.metadata.expires_at?;
+                        (expires_at <= now.saturating_add(within_ms)).then(|| ExpiringCredential {
+                            provider: provider.clone(),
+                            label: credential.label,
+                            expires_at,
+                            expired: expires_at <= now,
+                        })
+                    })
+            })
+            .collect();
+        expiring.sort_by(|a, b| {
+            (a.expires_at, &a.provider, &a.label).cmp(&(b.expires_at, &b.provider, &b.label))
+        });
+        Ok(expiring)
+    }
 
-    let config_path = get_config_file_path()?;
+    /// List all configured providers, reporting stored keys that could not be read
+    pub fn list_all_providers(&self) -> Result<Vec<String>, KeychainError> {
+        Ok(self.load_all()?.into_keys().collect())
+    }
 
-    // Serialize to JSON
-    let json_str = serde_json::to_string(configs).map_err(|e| {
-        error!("[Storage] Failed to serialize configs: {}", e);
-        e.to_string()
-    })?;
+    /// List config files that were moved aside because they could not be decrypted
+    pub fn list_quarantined_configs(&self) -> Result<Vec<QuarantinedConfig>, KeychainError> {
+        self.file_store().list_quarantined()
+    }
 
-    // Encrypt
-    let key = get_encryption_key();
-    let cipher = Aes256Gcm::new(&key.into());
-    let nonce = Nonce::from_slice(NONCE);
+    /// Retry a quarantined config file, defaulting to the newest one, and restore its
+    /// providers
+    pub fn recover_configs(
+        &self,
+        command: &str,
+        file_name: Option<&str>,
+        passphrase: Option<&str>,
+    ) -> Result<Vec<String>, KeychainError> {
+        let restored = self.write(|| self.file_store().recover(file_name, passphrase))?;
+        for provider in &restored {
+            self.record_event(command, AuditAction::Recovered, Some(provider));
+        }
+        Ok(restored)
+    }
 
-    let encrypted_data = cipher.encrypt(nonce, json_str.as_bytes()).map_err(|e| {
-        error!("[Storage] Failed to encrypt config: {}", e);
-        e.to_string()
-    })?;
+    /// Open the encrypted file store for master passphrase operations
+    fn passphrase_store(&self) -> Result<EncryptedFileStore, KeychainError> {
+        if self.load_backend_preference() != SecretStoreBackend::EncryptedFile {
+            return Err(
+                "A master passphrase is only supported by the encrypted file backend"
+                    .to_string()
+                    .into(),
+            );
+        }
+        Ok(self.file_store())
+    }
 
-    // Encode to base64
-    let encoded_data = general_purpose::STANDARD.encode(&encrypted_data);
+    /// Report whether a master passphrase is set and whether it still needs to be entered
+    pub fn get_passphrase_status(&self) -> Result<PassphraseStatus, KeychainError> {
+        if self.load_backend_preference() != SecretStoreBackend::EncryptedFile {
+            return Ok(PassphraseStatus {
+                enabled: false,
+                locked: false,
+            });
+        }
+        self.file_store().passphrase_status()
+    }
 
-    // Write to file
-    fs::write(&config_path, encoded_data).map_err(|e| {
-        error!("[Storage] Failed to write config file: {}", e);
-        e.to_string()
-    })?;
+    /// Check whether stored provider keys are waiting for the master passphrase
+    pub fn is_locked(&self) -> bool {
+        match self.get_passphrase_status() {
+            Ok(status) => status.locked,
+            Err(e) => {
+                warn!("[Storage] Failed to read master passphrase status: {}", e);
+                false
+            }
+        }
+    }
 
-    info!(
-        "[Storage] Successfully saved configs for {} providers to file",
-        configs.len()
-    );
-    Ok(())
+    /// Protect stored provider keys with a master passphrase
+    pub fn set_master_passphrase(
+        &self,
+        command: &str,
+        passphrase: &str,
+    ) -> Result<(), KeychainError> {
+        let store = self.passphrase_store()?;
+        self.write(|| store.set_passphrase(passphrase))?;
+        self.record_event(command, AuditAction::PassphraseChanged, None);
+        Ok(())
+    }
+
+    /// Re-encrypt stored provider keys under a new master passphrase
+    pub fn change_master_passphrase(
+        &self,
+        command: &str,
+        current_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<(), KeychainError> {
+        let store = self.passphrase_store()?;
+        self.write(|| store.change_passphrase(current_passphrase, new_passphrase))?;
+        self.record_event(command, AuditAction::PassphraseChanged, None);
+        Ok(())
+    }
+
+    /// Stop using a master passphrase and go back to the machine-local key
+    pub fn remove_master_passphrase(
+        &self,
+        command: &str,
+        current_passphrase: &str,
+    ) -> Result<(), KeychainError> {
+        let store = self.passphrase_store()?;
+        self.write(|| store.remove_passphrase(current_passphrase))?;
+        self.record_event(command, AuditAction::PassphraseChanged, None);
+        Ok(())
+    }
+
+    /// Unlock stored provider keys for the rest of this session
+    pub fn unlock(&self, passphrase: &str) -> Result<(), KeychainError> {
+        let store = self.passphrase_store()?;
+        self.write(|| store.unlock(passphrase))
+    }
+
+    /// Forget the master passphrase so stored provider keys need to be unlocked again
+    pub fn lock(&self) -> Result<(), KeychainError> {
+        // The cached plaintext has to go with the key
+        self.write(|| {
+            self.file_store().lock();
+            Ok(())
+        })
+    }
+
+    /// Report the active backend and the backends usable on this machine
+    pub fn get_backend_status(&self) -> SecretStoreStatus {
+        let available = [
+            SecretStoreBackend::EncryptedFile,
+            SecretStoreBackend::SecretService,
+        ]
+        .into_iter()
+        .filter(|backend| is_backend_available(*backend))
+        .collect();
+
+        SecretStoreStatus {
+            active: self.load_backend_preference(),
+            available,
+        }
+    }
+
+    /// Switch the active backend without moving any stored configuration.
+    ///
+    /// Refused while the active backend still holds providers, which would disappear from
+    /// the app; those are moved with [`ConfigStore::migrate_backend`] instead. A backend
+    /// that is no longer reachable can always be left, its entries stay where they are.
+    pub fn set_backend(
+        &self,
+        command: &str,
+        backend: SecretStoreBackend,
+    ) -> Result<(), KeychainError> {
+        let source_backend = self.load_backend_preference();
+        if source_backend != backend
+            && is_backend_available(source_backend)
+            && !self.load_all()?.is_empty()
+        {
+            return Err(format!(
+                "Secret store backend {:?} still holds provider keys. Use migrate_secret_store to move them to {:?}",
+                source_backend, backend
+            )
+            .into());
+        }
+        if !is_backend_available(backend) {
+            return Err(format!("Secret store backend {:?} is not available", backend).into());
+        }
+
+        self.save_backend_preference(backend)?;
+        self.record_event(command, AuditAction::BackendChanged, None);
+        info!("[Storage] Active secret store backend set to {:?}", backend);
+        Ok(())
+    }
+
+    /// Move every provider configuration from the active backend into `target` and switch
+    /// to it.
+    ///
+    /// The source is only cleared after all entries have been written and read back from the
+    /// target, so a failure part-way leaves the original backend intact and still active.
+    /// Returns the number of migrated providers.
+    pub fn migrate_backend(
+        &self,
+        command: &str,
+        target: SecretStoreBackend,
+    ) -> Result<usize, KeychainError> {
+        let source_backend = self.load_backend_preference();
+        if source_backend == target {
+            return Ok(0);
+        }
+        if !is_backend_available(target) {
+            return Err(format!("Secret store backend {:?} is not available", target).into());
+        }
+
+        info!(
+            "[Storage] Migrating provider configs from {:?} to {:?}",
+            source_backend, target
+        );
+
+        let source = self.open_store(source_backend)?;
+        let destination = self.open_store(target)?;
+        let configs = source.load_all()?;
+
+        self.write(|| {
+            for (provider, config) in &configs {
+                destination.save(provider, config)?;
+            }
+
+            let migrated = destination.load_all()?;
+            if let Some(missing) = configs
+                .keys()
+                .find(|provider| !migrated.contains_key(*provider))
+            {
+                return Err(format!(
+                    "Migration verification failed: provider '{}' missing from {:?}",
+                    missing, target
+                )
+                .into());
+            }
+
+            self.save_backend_preference(target)?;
+            self.record_event(command, AuditAction::BackendChanged, None);
+            if let Err(e) = self.clear_store(source_backend) {
+                warn!(
+                    "[Storage] Migrated to {:?} but failed to clear {:?}: {}",
+                    target, source_backend, e
+                );
+            }
+
+            info!(
+                "[Storage] Migrated {} provider configs to {:?}",
+                configs.len(),
+                target
+            );
+            Ok(configs.len())
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Mutex;
-
-    static TEST_LOCK: Mutex<()> = Mutex::new(());
+    use provider_config::ProviderCredential;
 
-    fn setup_test() -> std::sync::MutexGuard<'static, ()> {
-        let guard = TEST_LOCK.lock().unwrap();
+    const COMMAND: &str = "test";
 
-        // Create a temporary test file
-        let temp_dir = std::env::temp_dir();
-        let test_file = temp_dir.join(format!("mind_flayer_test_{}.dat", std::process::id()));
+    /// Each test gets its own files, so tests can run in parallel
+    fn setup_test(name: &str) -> ConfigStore {
+        let test_file = std::env::temp_dir().join(format!(
+            "mind_flayer_test_{}_{}.dat",
+            name,
+            std::process::id()
+        ));
 
-        {
-            let mut path_guard = TEST_FILE_PATH.lock().unwrap();
-            *path_guard = Some(test_file.clone());
-        }
+        // Clean up existing test files if they exist
+        let store = ConfigStore::new(test_file.clone(), test_file.with_extension("backend.json"));
+        cleanup_test(&store);
 
-        // Clean up existing test file if it exists
-        let _ = fs::remove_file(&test_file);
+        store
+    }
 
-        guard
+    fn cleanup_test(store: &ConfigStore) {
+        let path = &store.config_path;
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(&store.preference_path);
+        let _ = fs::remove_file(path.with_extension("audit.jsonl"));
+        let _ = fs::remove_file(path.with_extension("key"));
+        let _ = fs::remove_file(path.with_extension("lock"));
     }
 
-    fn cleanup_test() {
-        let guard = TEST_FILE_PATH.lock().unwrap();
-        if let Some(path) = guard.as_ref() {
-            let _ = fs::remove_file(path);
-        }
+    #[test]
+    fn creates_missing_store_directory_on_first_write() {
+        let dir = std::env::temp_dir().join(format!(
+            "mind_flayer_test_missing_dir_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let config_path = dir.join("nested").join("provider_configs.dat");
+        let store = ConfigStore::new(
+            config_path.clone(),
+            config_path.with_extension("backend.json"),
+        );
+
+        assert!(store.list_all_providers().unwrap().is_empty());
+        store
+            .save_api_key(COMMAND, "openai", "sk-test".to_string(), None)
+            .unwrap();
+        assert_eq!(store.list_all_providers().unwrap(), vec!["openai"]);
+
+        let _ = fs::remove_dir_all(&dir);
     }
 
     #[test]
     fn test_save_and_get_config() {
-        let _guard = setup_test();
+        let store = setup_test("save_and_get");
 
         let provider = "test_provider";
-        let config = ProviderConfig {
-            api_key: "test_key_123".to_string(),
-            base_url: Some("https://api.test.com".to_string()),
-        };
+        let config = ProviderConfig::from_api_key(
+            "test_key_123".to_string(),
+            Some("https://api.test.com".to_string()),
+        );
 
         // Save
-        assert!(save_config(provider, &config).is_ok());
+        assert!(store.save_config(COMMAND, provider, &config).is_ok());
 
         // Get
-        let retrieved = get_config(provider).unwrap();
-        assert_eq!(retrieved.api_key, config.api_key);
+        let retrieved = store.get_config(provider).unwrap();
+        assert_eq!(retrieved.api_key(), config.api_key());
         assert_eq!(retrieved.base_url, config.base_url);
 
-        cleanup_test();
+        cleanup_test(&store);
     }
 
     #[test]
     fn test_multiple_providers() {
-        let _guard = setup_test();
+        let store = setup_test("multiple_providers");
 
-        let config1 = ProviderConfig {
-            api_key: "key1".to_string(),
-            base_url: None,
-        };
-        let config2 = ProviderConfig {
-            api_key: "key2".to_string(),
-            base_url: Some("https://api.example.com".to_string()),
-        };
+        let config1 = ProviderConfig::from_api_key("key1".to_string(), None);
+        let config2 = ProviderConfig::from_api_key(
+            "key2".to_string(),
+            Some("https://api.example.com".to_string()),
+        );
 
         // Save two providers
-        save_config("provider1", &config1).unwrap();
-        save_config("provider2", &config2).unwrap();
+        store.save_config(COMMAND, "provider1", &config1).unwrap();
+        store.save_config(COMMAND, "provider2", &config2).unwrap();
 
         // Get all
-        let all_configs = get_all_configs_providers();
+        let all_configs = store.get_all_configs_providers().unwrap();
         assert_eq!(all_configs.len(), 2);
 
         // Delete one
-        delete_config("provider1").unwrap();
-        let all_configs = get_all_configs_providers();
+        store.delete_config(COMMAND, "provider1").unwrap();
+        let all_configs = store.get_all_configs_providers().unwrap();
         assert_eq!(all_configs.len(), 1);
 
-        cleanup_test();
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_unreadable_configs_are_an_error_not_an_empty_map() {
+        let store = setup_test("unreadable_all");
+
+        store
+            .save_api_key(COMMAND, "openai", "sk-test".to_string(), None)
+            .unwrap();
+        fs::remove_file(store.config_path.with_extension("key")).unwrap();
+
+        assert!(matches!(
+            store.get_all_configs_providers(),
+            Err(KeychainError::Unreadable { .. })
+        ));
+
+        for entry in store.list_quarantined_configs().unwrap() {
+            let _ = fs::remove_file(store.config_path.with_file_name(entry.file_name));
+        }
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_cache_picks_up_writes_from_other_processes() {
+        let store = setup_test("cache_other_processes");
+        // A second store on the same files stands in for another app instance
+        let other = ConfigStore::new(store.config_path.clone(), store.preference_path.clone());
+
+        store
+            .save_api_key(COMMAND, "openai", "first-key".to_string(), None)
+            .unwrap();
+        assert_eq!(
+            store.get_config("openai").unwrap().api_key(),
+            Some("first-key")
+        );
+        assert!(store.lock_cache().is_some());
+
+        other
+            .save_api_key(COMMAND, "openai", "other-key".to_string(), None)
+            .unwrap();
+        // Same length and possibly the same modification time, told apart by the inode
+        assert_eq!(
+            store.get_config("openai").unwrap().api_key(),
+            Some("other-key")
+        );
+
+        other.delete_config(COMMAND, "openai").unwrap();
+        assert!(store.list_all_providers().unwrap().is_empty());
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_concurrent_edits_of_one_provider_are_all_kept() {
+        let store = setup_test("concurrent_edits");
+        store
+            .save_api_key(COMMAND, "openai", "sk-primary".to_string(), None)
+            .unwrap();
+
+        // Every thread stands in for another app instance editing the same provider
+        std::thread::scope(|scope| {
+            for writer in 0..4 {
+                let other =
+                    ConfigStore::new(store.config_path.clone(), store.preference_path.clone());
+                scope.spawn(move || {
+                    for round in 0..3 {
+                        other
+                            .save_with(COMMAND, "openai", |stored| {
+                                let mut config = stored.cloned().unwrap_or_default();
+                                config.credentials.push(ProviderCredential {
+                                    label: format!("backup-{}-{}", writer, round),
+                                    api_key: format!("sk-{}-{}", writer, round),
+                                    active: false,
+                                    metadata: Default::default(),
+                                });
+                                Ok(config)
+                            })
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let config = store.get_config("openai").unwrap();
+        assert_eq!(config.credentials.len(), 13);
+        assert!(config
+            .credentials
+            .iter()
+            .all(|credential| credential.metadata.created_at.is_some()));
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_audit_log_records_changes_without_secrets() {
+        let store = setup_test("audit");
+
+        store
+            .save_api_key(
+                "save_provider_config",
+                "openai",
+                "sk-audit-secret".to_string(),
+                None,
+            )
+            .unwrap();
+        store
+            .save_api_key(
+                "save_provider_config",
+                "openai",
+                "sk-audit-rotated".to_string(),
+                None,
+            )
+            .unwrap();
+        store
+            .reveal_api_key("reveal_provider_api_key", "openai", None, None)
+            .unwrap();
+        store
+            .delete_config("delete_provider_config", "openai")
+            .unwrap();
+
+        let events = store.list_audit_events(&AuditFilter::default()).unwrap();
+        let actions: Vec<AuditAction> = events.iter().map(|event| event.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::Created,
+                AuditAction::Updated,
+                AuditAction::Revealed,
+                AuditAction::Deleted
+            ]
+        );
+        assert_eq!(events[2].command, "reveal_provider_api_key");
+        assert!(store.verify_audit_log().unwrap().valid);
+
+        let raw = fs::read_to_string(store.config_path.with_extension("audit.jsonl")).unwrap();
+        assert!(!raw.contains("sk-audit"));
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_audits_pushes_only_when_credentials_change() {
+        let store = setup_test("push_audit");
+        let pushes = || {
+            store
+                .list_audit_events(&AuditFilter {
+                    actions: Some(vec![AuditAction::PushedToSidecar]),
+                    ..Default::default()
+                })
+                .unwrap()
+                .len()
+        };
+
+        let mut configs = HashMap::new();
+        configs.insert(
+            "openai".to_string(),
+            ProviderConfig::from_api_key("openai-key".to_string(), None),
+        );
+        store.record_push(COMMAND, &configs).unwrap();
+        store.record_push(COMMAND, &configs).unwrap();
+        assert_eq!(pushes(), 1);
+
+        configs.insert(
+            "openai".to_string(),
+            ProviderConfig::from_api_key("rotated-key".to_string(), None),
+        );
+        store.record_push(COMMAND, &configs).unwrap();
+        assert_eq!(pushes(), 2);
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_save_api_key_keeps_backup_credentials() {
+        let store = setup_test("backup_credentials");
+
+        let config = ProviderConfig {
+            credentials: vec![
+                ProviderCredential {
+                    label: "org".to_string(),
+                    api_key: "org-key".to_string(),
+                    active: true,
+                    metadata: Default::default(),
+                },
+                ProviderCredential {
+                    label: "backup".to_string(),
+                    api_key: "backup-key".to_string(),
+                    active: true,
+                    metadata: Default::default(),
+                },
+            ],
+            ..Default::default()
+        };
+        store.save_config(COMMAND, "openai", &config).unwrap();
+
+        store
+            .save_api_key(COMMAND, "openai", "rotated-key".to_string(), None)
+            .unwrap();
+        let retrieved = store.get_config("openai").unwrap();
+        assert_eq!(retrieved.api_key(), Some("rotated-key"));
+        assert_eq!(retrieved.credentials.len(), 2);
+        assert_eq!(retrieved.credentials[1].api_key, "backup-key");
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_save_credentials_without_keys_keeps_stored_keys() {
+        let store = setup_test("keyless_credentials");
+
+        store
+            .save_credentials(
+                COMMAND,
+                "openai",
+                vec![
+                    CredentialUpdate {
+                        label: "org".to_string(),
+                        api_key: Some("org-key".to_string()),
+                        active: true,
+                        metadata: Default::default(),
+                    },
+                    CredentialUpdate {
+                        label: "backup".to_string(),
+                        api_key: Some("backup-key".to_string()),
+                        active: true,
+                        metadata: Default::default(),
+                    },
+                ],
+                None,
+            )
+            .unwrap();
+
+        // Reorder, add a note and deactivate without sending any key
+        let edit: Vec<CredentialUpdate> = serde_json::from_str(
+            r#"[
+                {"label": "backup", "notes": "rotate in May"},
+                {"label": "org", "active": false}
+            ]"#,
+        )
+        .unwrap();
+        store
+            .save_credentials(COMMAND, "openai", edit, None)
+            .unwrap();
+
+        let retrieved = store.get_config("openai").unwrap();
+        assert_eq!(retrieved.credentials[0].api_key, "backup-key");
+        assert_eq!(
+            retrieved.credentials[0].metadata.notes.as_deref(),
+            Some("rotate in May")
+        );
+        assert_eq!(retrieved.credentials[1].api_key, "org-key");
+        assert!(!retrieved.credentials[1].active);
+
+        // A new label still needs a key
+        let new_label: Vec<CredentialUpdate> =
+            serde_json::from_str(r#"[{"label": "backup"}, {"label": "spare"}]"#).unwrap();
+        assert!(matches!(
+            store.save_credentials(COMMAND, "openai", new_label, None),
+            Err(KeychainError::Invalid { field, .. }) if field == "credentials"
+        ));
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_tracks_credential_pushes_and_expiry() {
+        let store = setup_test("credential_metadata");
+
+        let mut config = ProviderConfig::from_api_key("openai-key".to_string(), None);
+        config.credentials[0].metadata.expires_at = Some(now_ms() + 60_000);
+        store.save_config(COMMAND, "openai", &config).unwrap();
+        store
+            .save_api_key(COMMAND, "deepseek", "deepseek-key".to_string(), None)
+            .unwrap();
+
+        let openai_config = store.get_config("openai").unwrap();
+        store
+            .record_push(COMMAND, [(&"openai".to_string(), &openai_config)])
+            .unwrap();
+        // Repeated pushes of the same credentials leave the file alone
+        let stored = fs::read(&store.config_path).unwrap();
+        store
+            .record_push(COMMAND, [(&"openai".to_string(), &openai_config)])
+            .unwrap();
+        assert_eq!(fs::read(&store.config_path).unwrap(), stored);
+
+        // The push time is stored, so it survives a restart
+        let reopened = ConfigStore::new(store.config_path.clone(), store.preference_path.clone());
+        let summaries = reopened.list_config_summaries().unwrap();
+        assert_eq!(summaries[0].provider, "deepseek");
+        assert_eq!(summaries[0].credentials[0].metadata.last_pushed_at, None);
+        let openai = &summaries[1].credentials[0].metadata;
+        assert!(openai.last_pushed_at.is_some());
+        assert_eq!(
+            openai.last_pushed_at.max(openai.updated_at),
+            openai.last_pushed_at
+        );
+
+        let expiring = store.expiring_credentials(60 * 60 * 1000).unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].provider, "openai");
+        assert!(!expiring[0].expired);
+        assert!(store.expiring_credentials(0).unwrap().is_empty());
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_update_config_keeps_unknown_fields() {
+        let store = setup_test("unknown_fields");
+
+        let stored: ProviderConfig =
+            serde_json::from_str(r#"{"schema": "v2", "apiKey": "key", "futureSetting": "kept"}"#)
+                .unwrap();
+        store.save_config(COMMAND, "openai", &stored).unwrap();
+
+        let update = serde_json::from_str(
+            r#"{"credentials": [{"label": "default"}], "defaultModel": "gpt-4.1"}"#,
+        )
+        .unwrap();
+        store.update_config(COMMAND, "openai", update).unwrap();
+
+        let retrieved = store.get_config("openai").unwrap();
+        assert_eq!(retrieved.api_key(), Some("key"));
+        assert_eq!(retrieved.default_model.as_deref(), Some("gpt-4.1"));
+        assert_eq!(retrieved.unknown_fields["futureSetting"], "kept");
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_summary_masks_keys_and_keeps_created_at() {
+        let store = setup_test("summary");
+
+        assert_eq!(store.get_config_summary("openai").unwrap(), None);
+
+        store
+            .save_api_key(
+                COMMAND,
+                "openai",
+                "sk-proj-abcdefghijklmnop1234".to_string(),
+                None,
+            )
+            .unwrap();
+        let first = store.get_config_summary("openai").unwrap().unwrap();
+        assert_eq!(first.masked_api_key.as_deref(), Some("sk-proj-...1234"));
+        assert!(first.created_at.is_some());
+
+        store
+            .save_api_key(
+                COMMAND,
+                "openai",
+                "sk-proj-zyxwvutsrqponmlk5678".to_string(),
+                None,
+            )
+            .unwrap();
+        let second = store.get_config_summary("openai").unwrap().unwrap();
+        assert_eq!(second.masked_api_key.as_deref(), Some("sk-proj-...5678"));
+        assert_eq!(second.created_at, first.created_at);
+        assert!(second.updated_at >= first.updated_at);
+
+        assert_eq!(
+            store.reveal_api_key(COMMAND, "openai", None, None).unwrap(),
+            "sk-proj-zyxwvutsrqponmlk5678"
+        );
+        assert!(store
+            .reveal_api_key(COMMAND, "openai", Some("missing"), None)
+            .is_err());
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_reveal_requires_master_passphrase() {
+        let store = setup_test("reveal");
+
+        store
+            .save_api_key(COMMAND, "openai", "secret_key".to_string(), None)
+            .unwrap();
+        store
+            .set_master_passphrase(COMMAND, "hunter2 hunter2")
+            .unwrap();
+
+        // Unlocked for the session, but revealing still asks for the passphrase
+        assert!(matches!(
+            store.reveal_api_key(COMMAND, "openai", None, None),
+            Err(KeychainError::Locked { .. })
+        ));
+        assert!(store
+            .reveal_api_key(COMMAND, "openai", None, Some("wrong passphrase"))
+            .is_err());
+        assert_eq!(
+            store
+                .reveal_api_key(COMMAND, "openai", Some("default"), Some("hunter2 hunter2"))
+                .unwrap(),
+            "secret_key"
+        );
+
+        cleanup_test(&store);
+    }
+
+    /// Test `config`, defaulting to the stored one, and cache the result like the command
+    async fn test_and_record(
+        store: &ConfigStore,
+        provider: &str,
+        config: Option<ProviderConfig>,
+    ) -> ConnectionTestResult {
+        let config = match config {
+            Some(config) => config,
+            None => store.get_config(provider).unwrap(),
+        };
+        let result = test_config(provider, &config, None).await.unwrap();
+        store.record_test_result(provider, &config, &result);
+        result
+    }
+
+    #[tokio::test]
+    async fn test_connection_result_is_cached_until_settings_change() {
+        let store = setup_test("connection_result_cache");
+        let base_url = connection_test::tests::spawn_mock_provider(2);
+
+        store
+            .save_api_key(
+                COMMAND,
+                "openai",
+                "good-key".to_string(),
+                Some(base_url.clone()),
+            )
+            .unwrap();
+        let result = test_and_record(&store, "openai", None).await;
+        let summary = store.get_config_summary("openai").unwrap().unwrap();
+        assert_eq!(summary.last_test, Some(result));
+
+        // Unsaved settings are tested without touching the cache
+        let unsaved = ProviderConfig::from_api_key("bad-key".to_string(), Some(base_url));
+        test_and_record(&store, "openai", Some(unsaved)).await;
+        assert!(store
+            .get_config_summary("openai")
+            .unwrap()
+            .unwrap()
+            .last_test
+            .is_some());
+
+        store
+            .save_api_key(COMMAND, "openai", "rotated-key".to_string(), None)
+            .unwrap();
+        assert_eq!(
+            store
+                .get_config_summary("openai")
+                .unwrap()
+                .unwrap()
+                .last_test,
+            None
+        );
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_imports_selected_env_keys() {
+        let store = setup_test("env_import");
+
+        store
+            .save_api_key(
+                COMMAND,
+                "anthropic",
+                "old-key".to_string(),
+                Some("https://proxy.example.com/v1".to_string()),
+            )
+            .unwrap();
+        let discovered = env_import::discover(
+            [
+                (
+                    "ANTHROPIC_API_KEY".to_string(),
+                    "sk-ant-from-env".to_string(),
+                ),
+                (
+                    "OPENAI_API_KEY".to_string(),
+                    "sk-openai-from-env".to_string(),
+                ),
+            ],
+            None,
+        )
+        .unwrap();
+        let selections = [EnvImportSelection {
+            provider: "anthropic".to_string(),
+            source: env_import::EnvSource::Environment,
+        }];
+
+        let imported = store
+            .import_discovered_keys(COMMAND, discovered.clone(), &selections)
+            .unwrap();
+        assert_eq!(imported, vec!["anthropic"]);
+        let anthropic = store.get_config("anthropic").unwrap();
+        assert_eq!(anthropic.api_key(), Some("sk-ant-from-env"));
+        // A stored base URL is kept when the environment sets none
+        assert_eq!(
+            anthropic.base_url.as_deref(),
+            Some("https://proxy.example.com/v1")
+        );
+        assert!(store.get_config("openai").is_err());
+
+        let missing = [EnvImportSelection {
+            provider: "deepseek".to_string(),
+            source: env_import::EnvSource::Environment,
+        }];
+        assert!(store
+            .import_discovered_keys(COMMAND, discovered, &missing)
+            .is_err());
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_export_and_import_bundle() {
+        let store = setup_test("bundle");
+        let bundle_path = store.config_path.with_extension("bundle");
+
+        store
+            .save_api_key(COMMAND, "openai", "openai-key".to_string(), None)
+            .unwrap();
+        store
+            .save_api_key(COMMAND, "deepseek", "deepseek-key".to_string(), None)
+            .unwrap();
+        let exported = store
+            .export_configs(
+                COMMAND,
+                &bundle_path,
+                "moving day",
+                Some(&["openai".to_string()]),
+            )
+            .unwrap();
+        assert_eq!(exported, vec!["openai"]);
+
+        // A new machine that already has a different openai key
+        store.delete_config(COMMAND, "deepseek").unwrap();
+        store
+            .save_api_key(COMMAND, "openai", "other-key".to_string(), None)
+            .unwrap();
+
+        let preview = store
+            .import_configs(
+                COMMAND,
+                &bundle_path,
+                "moving day",
+                MergeStrategy::Overwrite,
+                None,
+                true,
+            )
+            .unwrap();
+        assert!(preview.dry_run);
+        assert_eq!(preview.entries[0].action, bundle::ImportAction::Overwrite);
+        assert_eq!(
+            store.get_config("openai").unwrap().api_key(),
+            Some("other-key")
+        );
+
+        let report = store
+            .import_configs(
+                COMMAND,
+                &bundle_path,
+                "moving day",
+                MergeStrategy::Overwrite,
+                None,
+                false,
+            )
+            .unwrap();
+        assert!(!report.dry_run);
+        assert_eq!(
+            store.get_config("openai").unwrap().api_key(),
+            Some("openai-key")
+        );
+        assert!(store
+            .import_configs(
+                COMMAND,
+                &bundle_path,
+                "moving day",
+                MergeStrategy::Overwrite,
+                Some(&["deepseek".to_string()]),
+                true,
+            )
+            .is_err());
+
+        let _ = fs::remove_file(&bundle_path);
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_import_validates_and_stamps_only_written_providers() {
+        let store = setup_test("bundle_skip_invalid");
+        let bundle_path = store.config_path.with_extension("bundle");
+
+        // Written before validation existed, so the store accepts it as it is
+        let mut legacy = ProviderConfig::from_api_key("legacy-key".to_string(), None);
+        legacy.credentials[0].active = false;
+        store
+            .write(|| store.active_store()?.save("legacy", &legacy))
+            .unwrap();
+        store
+            .save_api_key(COMMAND, "openai", "openai-key".to_string(), None)
+            .unwrap();
+        store
+            .export_configs(COMMAND, &bundle_path, "moving day", None)
+            .unwrap();
+        store.delete_config(COMMAND, "openai").unwrap();
+
+        let report = store
+            .import_configs(
+                COMMAND,
+                &bundle_path,
+                "moving day",
+                MergeStrategy::SkipExisting,
+                None,
+                false,
+            )
+            .unwrap();
+        let actions: Vec<_> = report
+            .entries
+            .iter()
+            .map(|entry| (entry.provider.as_str(), entry.action))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("legacy", bundle::ImportAction::Skip),
+                ("openai", bundle::ImportAction::Add)
+            ]
+        );
+        let openai = store.get_config("openai").unwrap();
+        assert_eq!(openai.api_key(), Some("openai-key"));
+        assert!(openai.updated_at.is_some());
+        assert!(openai.credentials[0].metadata.created_at.is_some());
+
+        let _ = fs::remove_file(&bundle_path);
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_master_passphrase_locks_and_unlocks_configs() {
+        let store = setup_test("master_passphrase");
+
+        let config = ProviderConfig::from_api_key("locked_key".to_string(), None);
+        store.save_config(COMMAND, "openai", &config).unwrap();
+
+        store
+            .set_master_passphrase(COMMAND, "hunter2 hunter2")
+            .unwrap();
+        assert_eq!(
+            store.get_passphrase_status().unwrap(),
+            PassphraseStatus {
+                enabled: true,
+                locked: false
+            }
+        );
+        assert_eq!(
+            store.get_config("openai").unwrap().api_key(),
+            Some("locked_key")
+        );
+
+        store.lock().unwrap();
+        assert!(store.is_locked());
+        assert!(matches!(
+            store.get_config("openai"),
+            Err(KeychainError::Locked { .. })
+        ));
+        assert!(matches!(
+            store.get_all_configs_providers(),
+            Err(KeychainError::Locked { .. })
+        ));
+        // A locked file must not be quarantined
+        assert!(store.list_quarantined_configs().unwrap().is_empty());
+
+        assert!(matches!(
+            store.unlock("wrong passphrase"),
+            Err(KeychainError::Locked { .. })
+        ));
+        store.unlock("hunter2 hunter2").unwrap();
+        assert_eq!(
+            store.get_config("openai").unwrap().api_key(),
+            Some("locked_key")
+        );
+
+        store
+            .change_master_passphrase(COMMAND, "hunter2 hunter2", "correct horse")
+            .unwrap();
+        store.lock().unwrap();
+        assert!(store.unlock("hunter2 hunter2").is_err());
+        store.unlock("correct horse").unwrap();
+
+        store
+            .remove_master_passphrase(COMMAND, "correct horse")
+            .unwrap();
+        store.lock().unwrap();
+        assert!(!store.is_locked());
+        assert_eq!(
+            store.get_config("openai").unwrap().api_key(),
+            Some("locked_key")
+        );
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_defaults_to_encrypted_file_backend() {
+        let store = setup_test("default_backend");
+
+        assert_eq!(
+            store.load_backend_preference(),
+            SecretStoreBackend::EncryptedFile
+        );
+        assert_eq!(
+            store.active_store().unwrap().backend(),
+            SecretStoreBackend::EncryptedFile
+        );
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_persists_backend_preference() {
+        let store = setup_test("backend_preference");
+
+        store
+            .set_backend(COMMAND, SecretStoreBackend::EncryptedFile)
+            .unwrap();
+        let raw = fs::read_to_string(&store.preference_path).unwrap();
+        assert!(raw.contains("\"encrypted-file\""));
+        assert_eq!(
+            store.load_backend_preference(),
+            SecretStoreBackend::EncryptedFile
+        );
+
+        cleanup_test(&store);
+    }
+
+    #[test]
+    fn test_refuses_to_switch_away_from_stored_keys() {
+        let store = setup_test("switch_backend");
+
+        store
+            .save_api_key(COMMAND, "openai", "sk-test".to_string(), None)
+            .unwrap();
+        assert!(matches!(
+            store.set_backend(COMMAND, SecretStoreBackend::SecretService),
+            Err(KeychainError::Other { message }) if message.contains("migrate_secret_store")
+        ));
+        assert_eq!(
+            store.load_backend_preference(),
+            SecretStoreBackend::EncryptedFile
+        );
+        assert_eq!(store.list_all_providers().unwrap(), vec!["openai"]);
+
+        cleanup_test(&store);
     }
 }
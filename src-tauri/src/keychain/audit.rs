@@ -0,0 +1,523 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::file_store::{create_parent_dir, owner_only_options};
+use super::KeychainError;
+
+/// `prev_hash` of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+/// Bytes read per step when looking for the last entry from the end of the log
+const TAIL_BLOCK_LEN: u64 = 4096;
+
+/// What happened to a provider config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditAction {
+    Created,
+    Updated,
+    Revealed,
+    Exported,
+    Deleted,
+    Recovered,
+    PushedToSidecar,
+    /// The master passphrase was set, changed or removed
+    PassphraseChanged,
+    BackendChanged,
+    /// The last entry could not be read, e.g. because a crash cut it off, and the chain was
+    /// continued from the last readable entry before it
+    LogRepaired,
+}
+
+/// One entry of the audit log. It never contains key material.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    /// Position in the log, starting at 1
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub action: AuditAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Tauri command, or host task, that caused the event
+    pub command: String,
+    /// `hash` of the previous entry
+    pub prev_hash: String,
+    /// SHA-256 over `prev_hash` and the other fields of this entry
+    pub hash: String,
+}
+
+impl AuditEvent {
+    fn compute_hash(&self) -> String {
+        let fields = serde_json::json!([
+            self.seq,
+            self.timestamp,
+            self.action,
+            self.provider,
+            self.command,
+        ]);
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(fields.to_string().as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// Criteria for `list_audit_events`; unset fields match everything
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditFilter {
+    pub provider: Option<String>,
+    pub actions: Option<Vec<AuditAction>>,
+    pub command: Option<String>,
+    /// Inclusive bounds in milliseconds since the Unix epoch
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    /// Keep only the most recent matches
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, event: &AuditEvent) -> bool {
+        self.provider
+            .as_ref()
+            .is_none_or(|provider| event.provider.as_ref() == Some(provider))
+            && self
+                .actions
+                .as_ref()
+                .is_none_or(|actions| actions.contains(&event.action))
+            && self
+                .command
+                .as_ref()
+                .is_none_or(|command| event.command == *command)
+            && self.since.is_none_or(|since| event.timestamp >= since)
+            && self.until.is_none_or(|until| event.timestamp <= until)
+    }
+}
+
+/// Result of checking the hash chain, returned to the frontend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditVerification {
+    pub valid: bool,
+    /// Number of entries that passed the check
+    pub verified_entries: usize,
+    /// Line number (starting at 1) of the first entry that failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broken_at_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Append-only, hash-chained log of provider config access, stored as JSON lines
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn read_lines(&self) -> Result<Vec<String>, KeychainError> {
+        match fs::read_to_string(&self.path) {
+            Ok(raw) => Ok(raw
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string)
+                .collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => {
+                Err(format!("Failed to read audit log '{}': {}", self.path.display(), e).into())
+            }
+        }
+    }
+
+    /// Append an event, chained onto the last entry
+    pub fn append(
+        &self,
+        command: &str,
+        action: AuditAction,
+        provider: Option<&str>,
+    ) -> Result<AuditEvent, KeychainError> {
+        create_parent_dir(&self.path)?;
+        let mut file = owner_only_options()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open audit log '{}': {}", self.path.display(), e))?;
+        // Each entry chains onto the last line on disk, so reading it and appending must not
+        // interleave with another thread or app instance. Released when `file` is dropped.
+        file.lock()
+            .map_err(|e| format!("Failed to lock audit log: {}", e))?;
+
+        let read_error = |e: std::io::Error| {
+            format!("Failed to read audit log '{}': {}", self.path.display(), e)
+        };
+        let mut text = String::new();
+        // A torn last line has no newline, so the next entry has to start on a line of its own
+        if !ends_with_newline(&mut file).map_err(read_error)? {
+            text.push('\n');
+        }
+
+        let last_line = read_last_line(&mut file).map_err(read_error)?;
+        let last = match last_line {
+            Some(line) => match serde_json::from_str::<AuditEvent>(&line) {
+                Ok(last) => Some(last),
+                Err(e) => {
+                    // Left in place so `verify` reports the break. Refusing to append
+                    // instead would block every reveal until the file is fixed by hand.
+                    warn!("[Audit] Skipping unreadable last audit entry: {}", e);
+                    let last = last_readable_entry(&mut file).map_err(read_error)?;
+                    let repaired = chain_event(last, command, AuditAction::LogRepaired, None);
+                    text.push_str(&encode_line(&repaired)?);
+                    Some(repaired)
+                }
+            },
+            None => None,
+        };
+
+        let event = chain_event(last, command, action, provider);
+        text.push_str(&encode_line(&event)?);
+        // The chain is only worth something if entries survive a crash
+        file.write_all(text.as_bytes())
+            .and_then(|_| file.sync_data())
+            .map_err(|e| format!("Failed to write audit log '{}': {}", self.path.display(), e))?;
+
+        Ok(event)
+    }
+
+    /// Events matching `filter`, oldest first. Unreadable entries are skipped, `verify`
+    /// reports them.
+    pub fn list(&self, filter: &AuditFilter) -> Result<Vec<AuditEvent>, KeychainError> {
+        let mut events: Vec<AuditEvent> = self
+            .read_lines()?
+            .iter()
+            .filter_map(|line| match serde_json::from_str::<AuditEvent>(line) {
+                Ok(event) => Some(event),
+                Err(e) => {
+                    warn!("[Audit] Skipping unreadable audit entry: {}", e);
+                    None
+                }
+            })
+            .collect();
+        events.retain(|event| filter.matches(event));
+
+        if let Some(limit) = filter.limit {
+            events.drain(..events.len().saturating_sub(limit));
+        }
+        Ok(events)
+    }
+
+    /// Walk the chain and report the first entry that was modified, removed or reordered.
+    ///
+    /// Entries cut off the end of the log leave a valid chain; compare `verified_entries`
+    /// with an earlier count to notice that.
+    pub fn verify(&self) -> Result<AuditVerification, KeychainError> {
+        let mut prev_hash = GENESIS_HASH.to_string();
+        let lines = self.read_lines()?;
+
+        for (index, line) in lines.iter().enumerate() {
+            let failure = match serde_json::from_str::<AuditEvent>(line) {
+                Err(e) => Some(format!("Entry is not valid JSON: {}", e)),
+                Ok(event) if event.seq != index as u64 + 1 => Some(format!(
+                    "Expected entry {} but found {}",
+                    index + 1,
+                    event.seq
+                )),
+                Ok(event) if event.prev_hash != prev_hash => {
+                    Some("Entry does not follow the previous one".to_string())
+                }
+                Ok(event) if event.hash != event.compute_hash() => {
+                    Some("Entry was modified after it was written".to_string())
+                }
+                Ok(event) => {
+                    prev_hash = event.hash;
+                    None
+                }
+            };
+            if let Some(message) = failure {
+                return Ok(AuditVerification {
+                    valid: false,
+                    verified_entries: index,
+                    broken_at_line: Some(index + 1),
+                    message: Some(message),
+                });
+            }
+        }
+
+        Ok(AuditVerification {
+            valid: true,
+            verified_entries: lines.len(),
+            broken_at_line: None,
+            message: None,
+        })
+    }
+}
+
+/// The entry following `last`, or the first entry of the log
+fn chain_event(
+    last: Option<AuditEvent>,
+    command: &str,
+    action: AuditAction,
+    provider: Option<&str>,
+) -> AuditEvent {
+    let mut event = AuditEvent {
+        seq: last.as_ref().map_or(1, |last| last.seq + 1),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+        action,
+        provider: provider.map(str::to_string),
+        command: command.to_string(),
+        prev_hash: last.map_or_else(|| GENESIS_HASH.to_string(), |last| last.hash),
+        hash: String::new(),
+    };
+    event.hash = event.compute_hash();
+    event
+}
+
+fn encode_line(event: &AuditEvent) -> Result<String, String> {
+    serde_json::to_string(event)
+        .map(|line| format!("{}\n", line))
+        .map_err(|e| format!("Failed to serialize audit event: {}", e))
+}
+
+/// Whether `file` is empty or its last byte is a newline
+fn ends_with_newline(file: &mut fs::File) -> std::io::Result<bool> {
+    if file.seek(SeekFrom::End(0))? == 0 {
+        return Ok(true);
+    }
+    let mut last_byte = [0u8; 1];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last_byte)?;
+    Ok(last_byte[0] == b'\n')
+}
+
+/// Last entry of `file` that still parses. Reads the whole log, which only happens after
+/// the last entry turned out to be unreadable.
+fn last_readable_entry(file: &mut fs::File) -> std::io::Result<Option<AuditEvent>> {
+    let mut raw = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut raw)?;
+    Ok(String::from_utf8_lossy(&raw)
+        .lines()
+        .rev()
+        .find_map(|line| serde_json::from_str::<AuditEvent>(line).ok()))
+}
+
+/// Last non-blank line of `file`, read backwards from the end in blocks
+fn read_last_line(file: &mut fs::File) -> std::io::Result<Option<String>> {
+    let mut start = file.seek(SeekFrom::End(0))?;
+    let mut tail = Vec::new();
+    while start > 0 {
+        let block_start = start.saturating_sub(TAIL_BLOCK_LEN);
+        let mut block = vec![0u8; (start - block_start) as usize];
+        file.seek(SeekFrom::Start(block_start))?;
+        file.read_exact(&mut block)?;
+        block.extend_from_slice(&tail);
+        tail = block;
+        start = block_start;
+
+        let end = tail
+            .iter()
+            .rposition(|byte| !byte.is_ascii_whitespace())
+            .map_or(0, |index| index + 1);
+        if let Some(newline) = tail[..end].iter().rposition(|&byte| byte == b'\n') {
+            return Ok(Some(
+                String::from_utf8_lossy(&tail[newline + 1..end]).into_owned(),
+            ));
+        }
+    }
+
+    let line = String::from_utf8_lossy(&tail).trim().to_string();
+    Ok((!line.is_empty()).then_some(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log(name: &str) -> AuditLog {
+        let path = std::env::temp_dir().join(format!(
+            "mind_flayer_audit_{}_{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        AuditLog::new(path)
+    }
+
+    #[test]
+    fn chains_entries_and_detects_tampering() {
+        let log = temp_log("tamper");
+        log.append("save_provider_config", AuditAction::Created, Some("openai"))
+            .unwrap();
+        log.append(
+            "reveal_provider_api_key",
+            AuditAction::Revealed,
+            Some("openai"),
+        )
+        .unwrap();
+        log.append(
+            "delete_provider_config",
+            AuditAction::Deleted,
+            Some("openai"),
+        )
+        .unwrap();
+        assert!(log.verify().unwrap().valid);
+
+        let raw = fs::read_to_string(&log.path).unwrap();
+        fs::write(&log.path, raw.replacen("revealed", "updated", 1)).unwrap();
+        let verification = log.verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at_line, Some(2));
+        assert_eq!(verification.verified_entries, 1);
+
+        // Dropping an entry breaks the link of the one after it
+        let lines: Vec<&str> = raw.lines().collect();
+        fs::write(&log.path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert_eq!(log.verify().unwrap().broken_at_line, Some(2));
+
+        let _ = fs::remove_file(&log.path);
+    }
+
+    #[test]
+    fn chains_onto_long_logs_and_trailing_blank_lines() {
+        let log = temp_log("tail");
+        for _ in 0..100 {
+            log.append("save_provider_config", AuditAction::Updated, Some("openai"))
+                .unwrap();
+        }
+        let mut file = fs::OpenOptions::new().append(true).open(&log.path).unwrap();
+        file.write_all(b"\n\n").unwrap();
+
+        let event = log
+            .append(
+                "delete_provider_config",
+                AuditAction::Deleted,
+                Some("openai"),
+            )
+            .unwrap();
+        assert_eq!(event.seq, 101);
+        assert!(log.verify().unwrap().valid);
+
+        let _ = fs::remove_file(&log.path);
+    }
+
+    #[test]
+    fn continues_after_a_torn_last_entry() {
+        let log = temp_log("torn");
+        log.append("save_provider_config", AuditAction::Created, Some("openai"))
+            .unwrap();
+        log.append("save_provider_config", AuditAction::Updated, Some("openai"))
+            .unwrap();
+        // A crash in the middle of writing the next entry
+        let raw = fs::read_to_string(&log.path).unwrap();
+        let torn = &raw.lines().last().unwrap()[..40];
+        let mut file = fs::OpenOptions::new().append(true).open(&log.path).unwrap();
+        file.write_all(torn.as_bytes()).unwrap();
+
+        let event = log
+            .append(
+                "reveal_provider_api_key",
+                AuditAction::Revealed,
+                Some("openai"),
+            )
+            .unwrap();
+        assert_eq!(event.seq, 4);
+
+        let events = log.list(&AuditFilter::default()).unwrap();
+        let actions: Vec<AuditAction> = events.iter().map(|event| event.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::Created,
+                AuditAction::Updated,
+                AuditAction::LogRepaired,
+                AuditAction::Revealed
+            ]
+        );
+        assert_eq!(events[2].prev_hash, events[1].hash);
+        assert_eq!(events[3].prev_hash, events[2].hash);
+
+        let verification = log.verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at_line, Some(3));
+        assert_eq!(verification.verified_entries, 2);
+
+        let _ = fs::remove_file(&log.path);
+    }
+
+    #[test]
+    fn concurrent_appends_keep_the_chain() {
+        let log = temp_log("concurrent");
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                // Each thread opens the file on its own, like another app instance would
+                let log = AuditLog::new(log.path.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        log.append("save_provider_config", AuditAction::Updated, None)
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let verification = log.verify().unwrap();
+        assert!(verification.valid, "{:?}", verification.message);
+        assert_eq!(verification.verified_entries, 40);
+
+        let _ = fs::remove_file(&log.path);
+    }
+
+    #[test]
+    fn filters_events() {
+        let log = temp_log("filter");
+        log.append("save_provider_config", AuditAction::Created, Some("openai"))
+            .unwrap();
+        log.append(
+            "save_provider_config",
+            AuditAction::Created,
+            Some("deepseek"),
+        )
+        .unwrap();
+        log.append(
+            "reveal_provider_api_key",
+            AuditAction::Revealed,
+            Some("openai"),
+        )
+        .unwrap();
+
+        let openai = log
+            .list(&AuditFilter {
+                provider: Some("openai".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(openai.len(), 2);
+
+        let latest = log
+            .list(&AuditFilter {
+                actions: Some(vec![AuditAction::Created]),
+                limit: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].provider.as_deref(), Some("deepseek"));
+
+        let _ = fs::remove_file(&log.path);
+    }
+}
@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::envelope::{self, KdfId, StoredPayload};
+use super::passphrase;
+use super::provider_config::mask_api_key;
+use super::{KeychainError, ProviderConfig};
+
+/// Leading bytes of an export bundle. They differ from the config file envelope, so a
+/// bundle is never mistaken for `provider_configs.dat` or the other way round.
+const BUNDLE_MAGIC: [u8; 4] = [0x89, b'M', b'F', b'X'];
+pub const BUNDLE_VERSION: u8 = 1;
+
+/// Decrypted bundle payload
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleContents {
+    version: u8,
+    /// Milliseconds since the Unix epoch
+    exported_at: u64,
+    providers: BTreeMap<String, ProviderConfig>,
+}
+
+/// How imported providers are combined with the ones already stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeStrategy {
+    /// Replace stored providers with the bundled ones
+    Overwrite,
+    /// Only add providers that are not stored yet
+    #[default]
+    SkipExisting,
+    /// Replace stored providers only if the bundled one was updated more recently
+    KeepNewer,
+}
+
+/// What importing does with one bundled provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportAction {
+    Add,
+    Overwrite,
+    Skip,
+}
+
+/// One line of the import diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportDiffEntry {
+    pub provider: String,
+    pub action: ImportAction,
+    /// Masked primary key of the bundled config
+    pub masked_api_key: Option<String>,
+}
+
+/// Result of an import, or of a dry run that only computed the diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub entries: Vec<ImportDiffEntry>,
+}
+
+/// Encrypt `providers` into a portable bundle.
+///
+/// The payload is sealed in the same envelope as the config file, keyed by Argon2id with
+/// its salt and costs in the header, so any machine with the passphrase can open it. The
+/// AES-GCM tag covers the payload and the header and serves as the integrity check.
+pub fn seal(
+    providers: BTreeMap<String, ProviderConfig>,
+    bundle_passphrase: &str,
+) -> Result<Vec<u8>, KeychainError> {
+    if bundle_passphrase.is_empty() {
+        return Err(KeychainError::Invalid {
+            field: "passphrase".to_string(),
+            message: "Export passphrase must not be empty".to_string(),
+        });
+    }
+
+    let contents = BundleContents {
+        version: BUNDLE_VERSION,
+        exported_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+        providers: providers
+            .into_iter()
+            .map(|(provider, mut config)| {
+                // Test results describe this machine's network, not the key
+                config.last_test = None;
+                (provider, config)
+            })
+            .collect(),
+    };
+    let plaintext = serde_json::to_vec(&contents)
+        .map_err(|e| format!("Failed to serialize export bundle: {}", e))?;
+
+    let kdf = passphrase::new_kdf();
+    let key = passphrase::derive_key(bundle_passphrase, &kdf)?;
+    let sealed = envelope::seal(&key, KdfId::Argon2idPassphrase(kdf), &plaintext)?;
+
+    let mut bytes = Vec::with_capacity(BUNDLE_MAGIC.len() + 1 + plaintext.len() + 128);
+    bytes.extend_from_slice(&BUNDLE_MAGIC);
+    bytes.push(BUNDLE_VERSION);
+    bytes.extend_from_slice(&sealed.to_bytes());
+    Ok(bytes)
+}
+
+/// Decrypt a bundle written by [`seal`]
+pub fn open(
+    raw: &[u8],
+    bundle_passphrase: &str,
+) -> Result<BTreeMap<String, ProviderConfig>, KeychainError> {
+    let sealed = raw
+        .strip_prefix(&BUNDLE_MAGIC)
+        .ok_or_else(|| "File is not a provider config export".to_string())?;
+    let (&version, sealed) = sealed
+        .split_first()
+        .ok_or_else(|| "Export bundle is truncated".to_string())?;
+    if version != BUNDLE_VERSION {
+        return Err(format!("Unsupported export bundle version {}", version).into());
+    }
+
+    let StoredPayload::Envelope(sealed) = envelope::parse(sealed)? else {
+        return Err("Export bundle is damaged".to_string().into());
+    };
+    let KdfId::Argon2idPassphrase(kdf) = sealed.kdf else {
+        return Err("Export bundle is not passphrase protected"
+            .to_string()
+            .into());
+    };
+
+    let key = passphrase::derive_key(bundle_passphrase, &kdf)?;
+    // A wrong passphrase and a modified file fail the same authentication check
+    let plaintext = envelope::open(&key, &sealed).map_err(|_| KeychainError::Invalid {
+        field: "passphrase".to_string(),
+        message: "Incorrect passphrase or damaged export bundle".to_string(),
+    })?;
+
+    let contents: BundleContents = serde_json::from_slice(&plaintext)
+        .map_err(|e| format!("Failed to parse export bundle: {}", e))?;
+    if contents.version != BUNDLE_VERSION {
+        return Err(format!("Unsupported export bundle version {}", contents.version).into());
+    }
+    Ok(contents.providers)
+}
+
+/// Decide per bundled provider whether importing adds, overwrites or skips it
+pub fn plan_import(
+    stored: &HashMap<String, ProviderConfig>,
+    incoming: &BTreeMap<String, ProviderConfig>,
+    strategy: MergeStrategy,
+) -> Vec<ImportDiffEntry> {
+    incoming
+        .iter()
+        .map(|(provider, config)| ImportDiffEntry {
+            provider: provider.clone(),
+            action: import_action(stored.get(provider), config, strategy),
+            masked_api_key: config.api_key().map(mask_api_key),
+        })
+        .collect()
+}
+
+/// Decide whether importing `incoming` adds, overwrites or skips the `stored` provider
+pub fn import_action(
+    stored: Option<&ProviderConfig>,
+    incoming: &ProviderConfig,
+    strategy: MergeStrategy,
+) -> ImportAction {
+    match stored {
+        None => ImportAction::Add,
+        Some(existing) if same_settings(existing, incoming) => ImportAction::Skip,
+        Some(existing) => match strategy {
+            MergeStrategy::Overwrite => ImportAction::Overwrite,
+            MergeStrategy::SkipExisting => ImportAction::Skip,
+            MergeStrategy::KeepNewer if incoming.updated_at > existing.updated_at => {
+                ImportAction::Overwrite
+            }
+            MergeStrategy::KeepNewer => ImportAction::Skip,
+        },
+    }
+}
+
+/// Equal apart from the cached connection test, which exports never carry, and the push
+/// and validation times the host records on its own
+fn same_settings(stored: &ProviderConfig, incoming: &ProviderConfig) -> bool {
+    let without_host_stamps = |config: &ProviderConfig| {
+        let mut config = config.clone();
+        config.last_test = None;
+        for credential in &mut config.credentials {
+            credential.metadata.last_pushed_at = None;
+            credential.metadata.last_validated_at = None;
+        }
+        config
+    };
+    without_host_stamps(stored) == without_host_stamps(incoming)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn providers(entries: &[(&str, &str, u64)]) -> BTreeMap<String, ProviderConfig> {
+        entries
+            .iter()
+            .map(|(provider, api_key, updated_at)| {
+                let mut config = ProviderConfig::from_api_key(api_key.to_string(), None);
+                config.updated_at = Some(*updated_at);
+                (provider.to_string(), config)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_with_passphrase() {
+        let exported = providers(&[
+            ("openai", "openai-key", 1),
+            ("anthropic", "anthropic-key", 2),
+        ]);
+
+        let bundle = seal(exported.clone(), "moving day").unwrap();
+        assert!(bundle.starts_with(&BUNDLE_MAGIC));
+        assert!(!bundle.windows(10).any(|window| window == b"openai-key"));
+
+        assert_eq!(open(&bundle, "moving day").unwrap(), exported);
+        assert!(matches!(
+            open(&bundle, "wrong passphrase"),
+            Err(KeychainError::Invalid { field, .. }) if field == "passphrase"
+        ));
+    }
+
+    #[test]
+    fn rejects_modified_or_foreign_files() {
+        let mut bundle = seal(providers(&[("openai", "openai-key", 1)]), "moving day").unwrap();
+
+        let last = bundle.len() - 1;
+        bundle[last] ^= 0x01;
+        assert!(open(&bundle, "moving day").is_err());
+
+        assert!(open(b"not a bundle", "moving day").is_err());
+        assert!(seal(BTreeMap::new(), "").is_err());
+    }
+
+    #[test]
+    fn rejects_excessive_kdf_costs_before_deriving() {
+        let mut bundle = seal(providers(&[("openai", "openai-key", 1)]), "moving day").unwrap();
+
+        // Bundle magic and version, then envelope magic, version and kdf id, then the salt
+        let memory_offset = BUNDLE_MAGIC.len() + 1 + 6 + envelope::SALT_LEN;
+        bundle[memory_offset..memory_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let error = open(&bundle, "moving day").unwrap_err().to_string();
+        assert!(error.contains("Unsupported KDF parameters"), "{}", error);
+    }
+
+    #[test]
+    fn plans_import_per_strategy() {
+        let stored: HashMap<String, ProviderConfig> =
+            providers(&[("openai", "old-key", 5), ("deepseek", "same-key", 1)])
+                .into_iter()
+                .collect();
+        let incoming = providers(&[
+            ("openai", "new-key", 3),
+            ("deepseek", "same-key", 1),
+            ("anthropic", "anthropic-key", 1),
+        ]);
+        let actions = |strategy| {
+            plan_import(&stored, &incoming, strategy)
+                .into_iter()
+                .map(|entry| (entry.provider, entry.action))
+                .collect::<Vec<_>>()
+        };
+
+        // Entries come in provider order: anthropic, deepseek, openai
+        assert_eq!(
+            actions(MergeStrategy::Overwrite),
+            vec![
+                ("anthropic".to_string(), ImportAction::Add),
+                ("deepseek".to_string(), ImportAction::Skip),
+                ("openai".to_string(), ImportAction::Overwrite),
+            ]
+        );
+        assert_eq!(
+            actions(MergeStrategy::SkipExisting)[2],
+            ("openai".to_string(), ImportAction::Skip)
+        );
+        // The stored openai entry is newer
+        assert_eq!(
+            actions(MergeStrategy::KeepNewer)[2],
+            ("openai".to_string(), ImportAction::Skip)
+        );
+    }
+}
@@ -0,0 +1,370 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error as _;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::{KeychainError, ProviderConfig};
+
+/// A models listing is quick, so a longer `request_timeout_ms` meant for completions
+/// is capped to this
+const DEFAULT_TEST_TIMEOUT_MS: u64 = 15_000;
+const DEFAULT_ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Fragments of error messages that point at certificate or handshake problems
+const TLS_ERROR_MARKERS: &[&str] = &["certificate", "tls", "ssl", "handshake"];
+
+/// Outcome of a connection test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectionTestStatus {
+    /// The key was accepted
+    Valid,
+    /// The provider rejected the key (401 or 403)
+    InvalidKey,
+    /// The endpoint could not be reached or did not answer in time
+    Unreachable,
+    /// The TLS handshake failed, e.g. an intercepting proxy with an untrusted certificate
+    TlsError,
+    /// The provider answered with a status that says nothing about the key
+    UnexpectedResponse,
+}
+
+/// Result of testing a provider config, returned to the frontend and cached with the config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionTestResult {
+    pub status: ConnectionTestStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    pub latency_ms: u64,
+    /// Model IDs listed by the provider, sorted
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Milliseconds since the Unix epoch
+    pub tested_at: u64,
+}
+
+/// How a provider authenticates and where it lists models
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ApiStyle {
+    /// `Authorization: Bearer` and `GET /models`
+    OpenAi,
+    /// `x-api-key` with `anthropic-version` and `GET /models`
+    Anthropic,
+}
+
+/// API style and default base URL of the model providers the sidecar knows.
+/// Keep the URLs in sync with `MODEL_PROVIDERS` in `sidecar/src/config/constants.ts`.
+fn provider_api(provider: &str) -> Option<(ApiStyle, Option<&'static str>)> {
+    match provider {
+        "openai" => Some((ApiStyle::OpenAi, Some("https://api.openai.com/v1"))),
+        "deepseek" => Some((ApiStyle::OpenAi, Some("https://api.deepseek.com"))),
+        "anthropic" => Some((ApiStyle::Anthropic, Some("https://api.anthropic.com/v1"))),
+        "minimax" => Some((
+            ApiStyle::Anthropic,
+            Some("https://api.minimaxi.com/anthropic/v1"),
+        )),
+        _ => None,
+    }
+}
+
+/// Call the provider's models endpoint with the config's primary key.
+///
+/// Network failures are part of the result; only a config that cannot be tested at all
+/// (no key, unknown provider, bad proxy URL) is an error.
+pub async fn test_connection(
+    provider: &str,
+    config: &ProviderConfig,
+    proxy_url: Option<&str>,
+) -> Result<ConnectionTestResult, KeychainError> {
+    let api_key = config
+        .api_key()
+        .ok_or_else(|| format!("Provider '{}' has no active API key", provider))?;
+    let (style, default_base_url) = provider_api(provider)
+        .ok_or_else(|| format!("Testing is not supported for provider '{}'", provider))?;
+    let base_url = config
+        .base_url
+        .as_deref()
+        .or(default_base_url)
+        .ok_or_else(|| format!("Provider '{}' has no base URL", provider))?;
+    let url = format!("{}/models", base_url.trim_end_matches('/'));
+
+    let timeout_ms = config
+        .request_timeout_ms
+        .map_or(DEFAULT_TEST_TIMEOUT_MS, |ms| {
+            ms.min(DEFAULT_TEST_TIMEOUT_MS)
+        });
+    let mut client = reqwest::Client::builder().timeout(Duration::from_millis(timeout_ms));
+    if let Some(proxy_url) = proxy_url {
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| format!("Invalid proxy URL '{}': {}", proxy_url, e))?;
+        client = client.proxy(proxy);
+    }
+    let client = client
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let mut request = client.get(&url);
+    for (name, value) in &config.extra_headers {
+        request = request.header(name, value);
+    }
+    request = match style {
+        ApiStyle::OpenAi => {
+            let mut request = request.bearer_auth(api_key);
+            if let Some(organization) = &config.organization {
+                request = request.header("OpenAI-Organization", organization);
+            }
+            if let Some(project) = &config.project {
+                request = request.header("OpenAI-Project", project);
+            }
+            request
+        }
+        ApiStyle::Anthropic => request.header("x-api-key", api_key).header(
+            "anthropic-version",
+            config
+                .api_version
+                .as_deref()
+                .unwrap_or(DEFAULT_ANTHROPIC_VERSION),
+        ),
+    };
+
+    let started_at = Instant::now();
+    let response = request.send().await;
+    let latency_ms = started_at.elapsed().as_millis() as u64;
+    let tested_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let result = |status, message: Option<String>, http_status| ConnectionTestResult {
+        status,
+        message,
+        http_status,
+        latency_ms,
+        models: Vec::new(),
+        tested_at,
+    };
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            let message = error_chain(&e);
+            let status = if is_tls_error(&e) {
+                ConnectionTestStatus::TlsError
+            } else {
+                ConnectionTestStatus::Unreachable
+            };
+            return Ok(result(status, Some(message), None));
+        }
+    };
+
+    let http_status = response.status();
+    if matches!(http_status.as_u16(), 401 | 403) {
+        return Ok(result(
+            ConnectionTestStatus::InvalidKey,
+            Some(format!("{} rejected the API key", provider)),
+            Some(http_status.as_u16()),
+        ));
+    }
+    if !http_status.is_success() {
+        return Ok(result(
+            ConnectionTestStatus::UnexpectedResponse,
+            Some(format!("{} answered {} for {}", provider, http_status, url)),
+            Some(http_status.as_u16()),
+        ));
+    }
+
+    let mut tested = result(
+        ConnectionTestStatus::Valid,
+        None,
+        Some(http_status.as_u16()),
+    );
+    match response.json::<ModelList>().await {
+        Ok(list) => {
+            tested.models = list.data.into_iter().map(|model| model.id).collect();
+            tested.models.sort();
+        }
+        // The key works, the listing is only a bonus
+        Err(e) => tested.message = Some(format!("Failed to read model list: {}", e)),
+    }
+    Ok(tested)
+}
+
+/// `{"data": [{"id": "..."}]}`, used by both API styles
+#[derive(Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// Whether the connection broke off during the TLS handshake.
+///
+/// Only the causes below reqwest's error are matched: its own message contains the URL,
+/// whose host or path may contain any of the markers.
+fn is_tls_error(error: &reqwest::Error) -> bool {
+    if !error.is_connect() {
+        return false;
+    }
+    let mut source = error.source();
+    while let Some(cause) = source {
+        let message = cause.to_string().to_lowercase();
+        if TLS_ERROR_MARKERS
+            .iter()
+            .any(|marker| message.contains(marker))
+        {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+/// reqwest's own message only names the URL, the cause is further down the chain
+fn error_chain(error: &reqwest::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serve `count` requests: a model list for `good-key` in either auth header, 401 otherwise
+    pub(crate) fn spawn_mock_provider(count: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut authorized = false;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    let line = line.to_ascii_lowercase();
+                    authorized |= line.starts_with("authorization: bearer good-key")
+                        || line.starts_with("x-api-key: good-key");
+                }
+
+                let (status, body) = if authorized {
+                    ("200 OK", r#"{"data":[{"id":"model-b"},{"id":"model-a"}]}"#)
+                } else {
+                    ("401 Unauthorized", r#"{"error":"invalid key"}"#)
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    fn config(api_key: &str, base_url: String) -> ProviderConfig {
+        ProviderConfig::from_api_key(api_key.to_string(), Some(base_url))
+    }
+
+    #[tokio::test]
+    async fn lists_models_for_accepted_key() {
+        let base_url = spawn_mock_provider(2);
+
+        let result = test_connection("openai", &config("good-key", base_url.clone()), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status, ConnectionTestStatus::Valid);
+        assert_eq!(result.models, vec!["model-a", "model-b"]);
+        assert_eq!(result.http_status, Some(200));
+
+        let result = test_connection("anthropic", &config("good-key", base_url), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status, ConnectionTestStatus::Valid);
+    }
+
+    #[tokio::test]
+    async fn reports_rejected_key() {
+        let base_url = spawn_mock_provider(1);
+
+        let result = test_connection("deepseek", &config("bad-key", base_url), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status, ConnectionTestStatus::InvalidKey);
+        assert_eq!(result.http_status, Some(401));
+        assert!(result.models.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_endpoint() {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let result = test_connection(
+            "openai",
+            &config("good-key", format!("http://{}", address)),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status, ConnectionTestStatus::Unreachable);
+    }
+
+    #[tokio::test]
+    async fn ignores_tls_words_in_the_url_of_other_failures() {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let base_url = format!("http://{}/ssl-tls-certificate-handshake", address);
+
+        let result = test_connection("openai", &config("good-key", base_url), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status, ConnectionTestStatus::Unreachable);
+        assert!(result.message.unwrap().contains("ssl-tls"));
+    }
+
+    #[tokio::test]
+    async fn reports_tls_failure() {
+        // A plain HTTP answer to the client hello fails the handshake
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+            }
+        });
+        let base_url = format!("https://{}", address);
+
+        let result = test_connection("openai", &config("good-key", base_url), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status, ConnectionTestStatus::TlsError);
+    }
+
+    #[tokio::test]
+    async fn rejects_untestable_configs() {
+        let config = config("good-key", "http://127.0.0.1:9".to_string());
+        assert!(test_connection("telegram", &config, None).await.is_err());
+        assert!(test_connection("openai", &config, Some("not a proxy"))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::provider_config::mask_api_key;
+use super::{KeychainError, ProviderConfig};
+
+/// Vite only exposes prefixed variables, so `.env` files often carry it
+const VITE_PREFIX: &str = "VITE_";
+
+/// Which setting of a provider an environment variable holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EnvField {
+    ApiKey,
+    BaseUrl,
+}
+
+/// Variable names in the form the provider SDKs and CLIs read them
+const KNOWN_ENV_VARS: &[(&str, &str, EnvField)] = &[
+    ("OPENAI_API_KEY", "openai", EnvField::ApiKey),
+    ("OPENAI_BASE_URL", "openai", EnvField::BaseUrl),
+    ("OPENAI_API_BASE", "openai", EnvField::BaseUrl),
+    ("ANTHROPIC_API_KEY", "anthropic", EnvField::ApiKey),
+    ("ANTHROPIC_BASE_URL", "anthropic", EnvField::BaseUrl),
+    ("DEEPSEEK_API_KEY", "deepseek", EnvField::ApiKey),
+    ("DEEPSEEK_BASE_URL", "deepseek", EnvField::BaseUrl),
+    ("MINIMAX_API_KEY", "minimax", EnvField::ApiKey),
+    ("MINIMAX_BASE_URL", "minimax", EnvField::BaseUrl),
+    ("PARALLEL_API_KEY", "parallel", EnvField::ApiKey),
+    ("TELEGRAM_BOT_TOKEN", "telegram", EnvField::ApiKey),
+];
+
+/// Where a discovered key was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EnvSource {
+    /// The environment the app was started with
+    Environment,
+    /// The `.env` file chosen by the user
+    DotEnv,
+}
+
+/// Provider key found in the environment or a `.env` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredKey {
+    pub provider: String,
+    pub source: EnvSource,
+    /// Name of the variable holding the key
+    pub variable: String,
+    pub api_key: String,
+    pub base_url: Option<String>,
+}
+
+impl DiscoveredKey {
+    /// Describe the key without revealing it, compared against the stored config
+    pub fn preview(&self, stored: Option<&ProviderConfig>) -> EnvKeyPreview {
+        EnvKeyPreview {
+            provider: self.provider.clone(),
+            source: self.source,
+            variable: self.variable.clone(),
+            masked_api_key: mask_api_key(&self.api_key),
+            base_url: self.base_url.clone(),
+            already_configured: stored.is_some(),
+            matches_stored: stored.is_some_and(|stored| {
+                stored
+                    .credentials
+                    .iter()
+                    .any(|credential| credential.api_key == self.api_key)
+            }),
+        }
+    }
+}
+
+/// Discovered key as shown to the user before importing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvKeyPreview {
+    pub provider: String,
+    pub source: EnvSource,
+    pub variable: String,
+    pub masked_api_key: String,
+    pub base_url: Option<String>,
+    /// The provider already has a stored config, which importing would update
+    pub already_configured: bool,
+    /// The key is already one of the provider's stored credentials
+    pub matches_stored: bool,
+}
+
+/// Entry the user picked from the discovery preview
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvImportSelection {
+    pub provider: String,
+    pub source: EnvSource,
+}
+
+/// Map known variables to provider keys, in the order of [`KNOWN_ENV_VARS`]
+fn discover_in(source: EnvSource, variables: &[(String, String)]) -> Vec<DiscoveredKey> {
+    let lookup = |name: &str| {
+        // Later definitions win, as they do when a shell sources the file
+        variables.iter().rfind(|(variable, value)| {
+            !value.trim().is_empty()
+                && (variable == name || variable.strip_prefix(VITE_PREFIX) == Some(name))
+        })
+    };
+
+    let mut discovered: Vec<DiscoveredKey> = Vec::new();
+    for (name, provider, field) in KNOWN_ENV_VARS {
+        let Some((variable, value)) = lookup(name) else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        let existing = discovered.iter_mut().find(|key| key.provider == *provider);
+
+        match (field, existing) {
+            (EnvField::ApiKey, None) => discovered.push(DiscoveredKey {
+                provider: provider.to_string(),
+                source,
+                variable: variable.clone(),
+                api_key: value,
+                base_url: None,
+            }),
+            (EnvField::BaseUrl, Some(key)) if key.base_url.is_none() => key.base_url = Some(value),
+            _ => {}
+        }
+    }
+    discovered
+}
+
+/// Discover provider keys in `environment` and, if given, the `.env` file at `env_file`
+pub fn discover(
+    environment: impl IntoIterator<Item = (String, String)>,
+    env_file: Option<&Path>,
+) -> Result<Vec<DiscoveredKey>, KeychainError> {
+    let environment: Vec<(String, String)> = environment.into_iter().collect();
+    let mut discovered = discover_in(EnvSource::Environment, &environment);
+
+    if let Some(env_file) = env_file {
+        let contents = fs::read_to_string(env_file)
+            .map_err(|e| format!("Failed to read env file '{}': {}", env_file.display(), e))?;
+        discovered.extend(discover_in(EnvSource::DotEnv, &parse_dotenv(&contents)));
+    }
+
+    Ok(discovered)
+}
+
+/// Parse `KEY=value` lines, accepting `export`, quotes and trailing comments
+fn parse_dotenv(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=')?;
+            let name = name.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return None;
+            }
+            Some((name.to_string(), parse_dotenv_value(value.trim())))
+        })
+        .collect()
+}
+
+fn parse_dotenv_value(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if let Some(quoted) = value.strip_prefix(quote) {
+            if let Some(end) = quoted.find(quote) {
+                return quoted[..end].to_string();
+            }
+        }
+    }
+
+    // Unquoted values end at a comment
+    match value.find(" #") {
+        Some(comment) => value[..comment].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_dotenv_syntax() {
+        let parsed = parse_dotenv(
+            r#"
+# Get your key at https://platform.openai.com
+export OPENAI_API_KEY="sk-quoted # not a comment"
+ANTHROPIC_API_KEY = sk-ant-plain # trailing comment
+DEEPSEEK_API_KEY='single-quoted'
+not a variable
+EMPTY=
+"#,
+        );
+
+        assert_eq!(
+            parsed,
+            vars(&[
+                ("OPENAI_API_KEY", "sk-quoted # not a comment"),
+                ("ANTHROPIC_API_KEY", "sk-ant-plain"),
+                ("DEEPSEEK_API_KEY", "single-quoted"),
+                ("EMPTY", ""),
+            ])
+        );
+    }
+
+    #[test]
+    fn maps_known_variables_to_providers() {
+        let discovered = discover_in(
+            EnvSource::Environment,
+            &vars(&[
+                ("OPENAI_API_KEY", "sk-openai"),
+                ("OPENAI_BASE_URL", "https://gateway.example.com/v1"),
+                ("VITE_MINIMAX_API_KEY", "minimax-key"),
+                ("DEEPSEEK_BASE_URL", "https://api.deepseek.com"),
+                ("ANTHROPIC_API_KEY", "  "),
+                ("UNRELATED_API_KEY", "ignored"),
+            ]),
+        );
+
+        assert_eq!(discovered.len(), 2);
+        assert_eq!(discovered[0].provider, "openai");
+        assert_eq!(
+            discovered[0].base_url.as_deref(),
+            Some("https://gateway.example.com/v1")
+        );
+        assert_eq!(discovered[1].provider, "minimax");
+        assert_eq!(discovered[1].variable, "VITE_MINIMAX_API_KEY");
+    }
+
+    #[test]
+    fn reads_env_file_after_environment() {
+        let path =
+            std::env::temp_dir().join(format!("mind_flayer_env_import_{}.env", std::process::id()));
+        fs::write(&path, "OPENAI_API_KEY=from-file\n").unwrap();
+
+        let discovered = discover(vars(&[("OPENAI_API_KEY", "from-env")]), Some(&path)).unwrap();
+        assert_eq!(discovered.len(), 2);
+        assert_eq!(discovered[0].source, EnvSource::Environment);
+        assert_eq!(discovered[1].source, EnvSource::DotEnv);
+        assert_eq!(discovered[1].api_key, "from-file");
+
+        let _ = fs::remove_file(&path);
+        assert!(discover(Vec::new(), Some(&path)).is_err());
+    }
+}
@@ -0,0 +1,336 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose, Engine as _};
+
+/// Leading bytes of the versioned envelope. The first byte is outside the base64
+/// alphabet, so legacy base64 text files can never be mistaken for an envelope.
+const MAGIC: [u8; 4] = [0x89, b'M', b'F', b'C'];
+pub const FORMAT_VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 2;
+pub const SALT_LEN: usize = 16;
+/// Salt followed by Argon2id memory, iteration and parallelism costs (little endian u32)
+const PASSPHRASE_PARAMS_LEN: usize = SALT_LEN + 12;
+/// Upper Argon2id costs accepted from a header (1 GiB, 10 passes, 8 lanes). Headers are
+/// read before the key is checked, so a crafted file could otherwise pin memory and CPU.
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 10;
+const MAX_PARALLELISM: u32 = 8;
+
+/// Nonce used by every write before the envelope format existed
+const LEGACY_NONCE: &[u8; NONCE_LEN] = b"mind-flayer!";
+
+/// Argon2id parameters for a passphrase-protected envelope, stored in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassphraseKdf {
+    pub salt: [u8; SALT_LEN],
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PassphraseKdf {
+    fn to_bytes(self) -> [u8; PASSPHRASE_PARAMS_LEN] {
+        let mut bytes = [0u8; PASSPHRASE_PARAMS_LEN];
+        bytes[..SALT_LEN].copy_from_slice(&self.salt);
+        bytes[SALT_LEN..SALT_LEN + 4].copy_from_slice(&self.memory_kib.to_le_bytes());
+        bytes[SALT_LEN + 4..SALT_LEN + 8].copy_from_slice(&self.iterations.to_le_bytes());
+        bytes[SALT_LEN + 8..].copy_from_slice(&self.parallelism.to_le_bytes());
+        bytes
+    }
+
+    /// Reject costs outside the range this app derives keys with
+    pub fn check_bounds(&self) -> Result<(), String> {
+        if self.memory_kib > MAX_MEMORY_KIB
+            || !(1..=MAX_ITERATIONS).contains(&self.iterations)
+            || !(1..=MAX_PARALLELISM).contains(&self.parallelism)
+        {
+            return Err(format!(
+                "Unsupported KDF parameters: {} KiB memory, {} iterations, parallelism {}",
+                self.memory_kib, self.iterations, self.parallelism
+            ));
+        }
+        Ok(())
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap_or_default())
+        };
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&bytes[..SALT_LEN]);
+        let kdf = PassphraseKdf {
+            salt,
+            memory_kib: read_u32(SALT_LEN),
+            iterations: read_u32(SALT_LEN + 4),
+            parallelism: read_u32(SALT_LEN + 8),
+        };
+        kdf.check_bounds()?;
+        Ok(kdf)
+    }
+}
+
+/// Key derivation function used to produce the file encryption key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfId {
+    /// SHA-256 over a fixed label and the machine device name
+    DeviceNameSha256,
+    /// Random key stored in an owner-only file next to the config
+    LocalKeyFile,
+    /// Argon2id over the user's master passphrase
+    Argon2idPassphrase(PassphraseKdf),
+}
+
+impl KdfId {
+    fn to_byte(self) -> u8 {
+        match self {
+            KdfId::DeviceNameSha256 => 1,
+            KdfId::LocalKeyFile => 2,
+            KdfId::Argon2idPassphrase(_) => 3,
+        }
+    }
+
+    /// Decode the kdf id and its parameters, returning how many bytes were consumed
+    fn parse(raw: &[u8]) -> Result<(Self, usize), String> {
+        match raw.first() {
+            Some(1) => Ok((KdfId::DeviceNameSha256, 1)),
+            Some(2) => Ok((KdfId::LocalKeyFile, 1)),
+            Some(3) => {
+                let params = raw
+                    .get(1..1 + PASSPHRASE_PARAMS_LEN)
+                    .ok_or_else(|| "Config envelope is truncated".to_string())?;
+                Ok((
+                    KdfId::Argon2idPassphrase(PassphraseKdf::from_bytes(params)?),
+                    1 + PASSPHRASE_PARAMS_LEN,
+                ))
+            }
+            Some(other) => Err(format!("Unsupported key derivation id {}", other)),
+            None => Err("Config envelope is truncated".to_string()),
+        }
+    }
+}
+
+/// Decoded envelope: `magic | version | kdf id | kdf params | nonce | ciphertext`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub version: u8,
+    pub kdf: KdfId,
+    pub nonce: [u8; NONCE_LEN],
+    pub ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN + PASSPHRASE_PARAMS_LEN);
+        header.extend_from_slice(&MAGIC);
+        header.push(self.version);
+        header.push(self.kdf.to_byte());
+        if let KdfId::Argon2idPassphrase(params) = self.kdf {
+            header.extend_from_slice(&params.to_bytes());
+        }
+        header
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = self.header();
+        let mut bytes = Vec::with_capacity(header.len() + NONCE_LEN + self.ciphertext.len());
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(&self.nonce);
+        bytes.extend_from_slice(&self.ciphertext);
+        bytes
+    }
+}
+
+/// Contents of a config file, in whichever format it was written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoredPayload {
+    /// Base64 text encrypted with the fixed legacy nonce
+    Legacy(Vec<u8>),
+    Envelope(Envelope),
+}
+
+/// Parse raw file contents into a legacy payload or a versioned envelope
+pub fn parse(raw: &[u8]) -> Result<StoredPayload, String> {
+    if !raw.starts_with(&MAGIC) {
+        let text = std::str::from_utf8(raw)
+            .map_err(|e| format!("Legacy config file is not valid text: {}", e))?;
+        let ciphertext = general_purpose::STANDARD
+            .decode(text.trim())
+            .map_err(|e| format!("Failed to decode base64: {}", e))?;
+        return Ok(StoredPayload::Legacy(ciphertext));
+    }
+
+    if raw.len() < HEADER_LEN + NONCE_LEN {
+        return Err("Config envelope is truncated".to_string());
+    }
+
+    let version = raw[MAGIC.len()];
+    if version != FORMAT_VERSION {
+        return Err(format!("Unsupported config format version {}", version));
+    }
+
+    let (kdf, kdf_len) = KdfId::parse(&raw[MAGIC.len() + 1..])?;
+    let nonce_start = MAGIC.len() + 1 + kdf_len;
+    let nonce: [u8; NONCE_LEN] = raw
+        .get(nonce_start..nonce_start + NONCE_LEN)
+        .and_then(|nonce| nonce.try_into().ok())
+        .ok_or_else(|| "Config envelope is truncated".to_string())?;
+
+    Ok(StoredPayload::Envelope(Envelope {
+        version,
+        kdf,
+        nonce,
+        ciphertext: raw[nonce_start + NONCE_LEN..].to_vec(),
+    }))
+}
+
+/// Encrypt `plaintext` under a fresh random nonce. The header is bound as associated data.
+pub fn seal(key: &[u8; 32], kdf: KdfId, plaintext: &[u8]) -> Result<Envelope, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let mut envelope = Envelope {
+        version: FORMAT_VERSION,
+        kdf,
+        nonce: nonce.into(),
+        ciphertext: Vec::new(),
+    };
+
+    envelope.ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: &envelope.header(),
+            },
+        )
+        .map_err(|e| format!("Failed to encrypt config: {}", e))?;
+
+    Ok(envelope)
+}
+
+/// Decrypt a versioned envelope
+pub fn open(key: &[u8; 32], envelope: &Envelope) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    cipher
+        .decrypt(
+            Nonce::from_slice(&envelope.nonce),
+            Payload {
+                msg: &envelope.ciphertext,
+                aad: &envelope.header(),
+            },
+        )
+        .map_err(|e| format!("Failed to decrypt config: {}", e))
+}
+
+/// Decrypt a payload written before the envelope format existed
+pub fn open_legacy(key: &[u8; 32], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    cipher
+        .decrypt(Nonce::from_slice(LEGACY_NONCE), ciphertext)
+        .map_err(|e| format!("Failed to decrypt legacy config: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7u8; 32];
+
+    fn legacy_file_contents(key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+        let cipher = Aes256Gcm::new(key.into());
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(LEGACY_NONCE), plaintext)
+            .unwrap();
+        general_purpose::STANDARD.encode(ciphertext).into_bytes()
+    }
+
+    #[test]
+    fn decodes_legacy_base64_file() {
+        let raw = legacy_file_contents(&KEY, br#"{"openai":{"apiKey":"k"}}"#);
+
+        let StoredPayload::Legacy(ciphertext) = parse(&raw).unwrap() else {
+            panic!("expected legacy payload");
+        };
+        assert_eq!(
+            open_legacy(&KEY, &ciphertext).unwrap(),
+            br#"{"openai":{"apiKey":"k"}}"#
+        );
+    }
+
+    #[test]
+    fn round_trips_envelope_through_bytes() {
+        let envelope = seal(&KEY, KdfId::DeviceNameSha256, b"secret configs").unwrap();
+        let raw = envelope.to_bytes();
+
+        assert!(raw.starts_with(&MAGIC));
+        let StoredPayload::Envelope(parsed) = parse(&raw).unwrap() else {
+            panic!("expected envelope payload");
+        };
+        assert_eq!(parsed, envelope);
+        assert_eq!(open(&KEY, &parsed).unwrap(), b"secret configs");
+    }
+
+    #[test]
+    fn uses_a_fresh_nonce_for_every_write() {
+        let first = seal(&KEY, KdfId::DeviceNameSha256, b"same plaintext").unwrap();
+        let second = seal(&KEY, KdfId::DeviceNameSha256, b"same plaintext").unwrap();
+
+        assert_ne!(first.nonce, second.nonce);
+        assert_ne!(first.ciphertext, second.ciphertext);
+    }
+
+    #[test]
+    fn rejects_unknown_format_version() {
+        let mut raw = seal(&KEY, KdfId::DeviceNameSha256, b"payload")
+            .unwrap()
+            .to_bytes();
+        raw[MAGIC.len()] = FORMAT_VERSION + 1;
+
+        assert!(parse(&raw)
+            .unwrap_err()
+            .contains("Unsupported config format version"));
+    }
+
+    #[test]
+    fn round_trips_passphrase_parameters_in_header() {
+        let kdf = KdfId::Argon2idPassphrase(PassphraseKdf {
+            salt: [3u8; SALT_LEN],
+            memory_kib: 19_456,
+            iterations: 2,
+            parallelism: 1,
+        });
+        let envelope = seal(&KEY, kdf, b"locked configs").unwrap();
+
+        let StoredPayload::Envelope(parsed) = parse(&envelope.to_bytes()).unwrap() else {
+            panic!("expected envelope payload");
+        };
+        assert_eq!(parsed.kdf, kdf);
+        assert_eq!(open(&KEY, &parsed).unwrap(), b"locked configs");
+    }
+
+    #[test]
+    fn rejects_tampered_passphrase_parameters() {
+        let kdf = KdfId::Argon2idPassphrase(PassphraseKdf {
+            salt: [3u8; SALT_LEN],
+            memory_kib: 19_456,
+            iterations: 2,
+            parallelism: 1,
+        });
+        let mut raw = seal(&KEY, kdf, b"locked configs").unwrap().to_bytes();
+        // Lower the memory cost recorded in the header
+        raw[HEADER_LEN + SALT_LEN] ^= 0xff;
+
+        let StoredPayload::Envelope(parsed) = parse(&raw).unwrap() else {
+            panic!("expected envelope payload");
+        };
+        assert!(open(&KEY, &parsed).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_envelope() {
+        let raw = [MAGIC.as_slice(), &[FORMAT_VERSION, 1, 0, 0]].concat();
+        assert!(parse(&raw).unwrap_err().contains("truncated"));
+    }
+}
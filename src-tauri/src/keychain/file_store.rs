@@ -0,0 +1,1101 @@
+use log::{debug, error, info, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::envelope::{self, KdfId, StoredPayload};
+use super::machine_key;
+use super::passphrase::{self, UnlockedKey};
+use super::{
+    KeychainError, PassphraseStatus, ProviderConfig, SecretStore, SecretStoreBackend, UpsertConfig,
+};
+
+const QUARANTINE_SUFFIX: &str = "quarantine";
+
+/// Makes temporary file names unique within the process
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A config file that could not be read and was moved aside for recovery
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuarantinedConfig {
+    pub file_name: String,
+    pub quarantined_at: u64,
+}
+
+/// Configs read from the file, together with the key it was sealed with
+struct StoredConfigs {
+    configs: HashMap<String, ProviderConfig>,
+    /// Master passphrase parameters and key, `None` when the file uses the local key or does
+    /// not exist yet. Writes reuse them, so a passphrase removed or changed by another
+    /// instance is never brought back from this process's memory.
+    passphrase_key: Option<(envelope::PassphraseKdf, [u8; 32])>,
+}
+
+/// Secret store backed by a single AES-GCM encrypted file in the app support directory
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    unlocked_key: Arc<UnlockedKey>,
+}
+
+impl EncryptedFileStore {
+    pub fn new(path: PathBuf, unlocked_key: Arc<UnlockedKey>) -> Self {
+        Self { path, unlocked_key }
+    }
+
+    /// Remove the backing file, used after migrating to another backend
+    pub fn clear(&self) -> Result<(), String> {
+        let _lock = self.lock_exclusive()?;
+        self.unlocked_key.forget();
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!(
+                "Failed to remove config file '{}': {}",
+                self.path.display(),
+                e
+            )),
+        }
+    }
+
+    /// List quarantined config files, newest first
+    pub fn list_quarantined(&self) -> Result<Vec<QuarantinedConfig>, KeychainError> {
+        let Some(parent_dir) = self.path.parent() else {
+            return Ok(Vec::new());
+        };
+        let prefix = format!("{}.", self.file_name());
+        let suffix = format!(".{}", QUARANTINE_SUFFIX);
+
+        let entries = match fs::read_dir(parent_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(format!(
+                    "Failed to read config directory '{}': {}",
+                    parent_dir.display(),
+                    e
+                )
+                .into())
+            }
+        };
+
+        let mut quarantined: Vec<QuarantinedConfig> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let quarantined_at = file_name
+                    .strip_prefix(&prefix)?
+                    .strip_suffix(&suffix)?
+                    .parse::<u64>()
+                    .ok()?;
+                Some(QuarantinedConfig {
+                    file_name,
+                    quarantined_at,
+                })
+            })
+            .collect();
+        quarantined.sort_by_key(|entry| std::cmp::Reverse(entry.quarantined_at));
+
+        Ok(quarantined)
+    }
+
+    /// Retry a quarantined config file and merge what it contains back into the store.
+    ///
+    /// Besides the local key and this machine's known names, `passphrase` is tried as a
+    /// previous device name and as the master passphrase. Providers saved since the file was
+    /// quarantined are kept. Returns the providers that were restored.
+    pub fn recover(
+        &self,
+        file_name: Option<&str>,
+        passphrase: Option<&str>,
+    ) -> Result<Vec<String>, KeychainError> {
+        let _lock = self.lock_exclusive()?;
+        let quarantined = self.list_quarantined()?;
+        let target = match file_name {
+            Some(file_name) => quarantined
+                .iter()
+                .find(|entry| entry.file_name == file_name)
+                .ok_or_else(|| format!("Quarantined config '{}' not found", file_name))?,
+            None => quarantined
+                .first()
+                .ok_or_else(|| "No quarantined config to recover".to_string())?,
+        };
+        let quarantine_path = self.path.with_file_name(&target.file_name);
+
+        let raw = fs::read(&quarantine_path).map_err(|e| {
+            format!(
+                "Failed to read quarantined config '{}': {}",
+                quarantine_path.display(),
+                e
+            )
+        })?;
+        let payload = envelope::parse(&raw)?;
+        let mut extra_keys: Vec<[u8; 32]> = Vec::new();
+        if let Some(passphrase) = passphrase.filter(|passphrase| !passphrase.trim().is_empty()) {
+            extra_keys.push(machine_key::device_name_key(passphrase.trim()));
+            if let StoredPayload::Envelope(sealed) = &payload {
+                if let KdfId::Argon2idPassphrase(kdf) = sealed.kdf {
+                    extra_keys.push(passphrase::derive_key(passphrase, &kdf)?);
+                }
+            }
+        }
+        let (plaintext, _) = self.decrypt_payload(&payload, &extra_keys)?;
+        let recovered = decode_configs(plaintext)?;
+
+        let mut stored = self.read_all()?;
+        let mut restored = Vec::new();
+        for (provider, config) in recovered {
+            if !stored.configs.contains_key(&provider) {
+                stored.configs.insert(provider.clone(), config);
+                restored.push(provider);
+            }
+        }
+        self.write_all(&stored)?;
+
+        if let Err(e) = fs::remove_file(&quarantine_path) {
+            warn!(
+                "[Storage] Recovered configs but failed to remove '{}': {}",
+                quarantine_path.display(),
+                e
+            );
+        }
+
+        info!(
+            "[Storage] Recovered {} providers from '{}'",
+            restored.len(),
+            target.file_name
+        );
+        Ok(restored)
+    }
+
+    /// Report whether the file is protected by a master passphrase and still locked
+    pub fn passphrase_status(&self) -> Result<PassphraseStatus, KeychainError> {
+        Ok(match self.passphrase_kdf()? {
+            Some(kdf) => PassphraseStatus {
+                enabled: true,
+                locked: self.unlocked_key.get(&kdf).is_none(),
+            },
+            None => PassphraseStatus {
+                enabled: false,
+                locked: false,
+            },
+        })
+    }
+
+    /// Derive the key from `passphrase` and keep it in memory until [`Self::lock`]
+    pub fn unlock(&self, passphrase: &str) -> Result<(), KeychainError> {
+        let kdf = self
+            .passphrase_kdf()?
+            .ok_or_else(|| "No master passphrase is set".to_string())?;
+        let key = self.verify_passphrase(passphrase, &kdf)?;
+        self.unlocked_key.remember(kdf, key);
+
+        info!("[Storage] Unlocked provider configs");
+        Ok(())
+    }
+
+    /// Check the master passphrase again without changing the lock state
+    pub fn confirm_passphrase(&self, passphrase: &str) -> Result<(), KeychainError> {
+        let kdf = self
+            .passphrase_kdf()?
+            .ok_or_else(|| "No master passphrase is set".to_string())?;
+        self.verify_passphrase(passphrase, &kdf)?;
+        Ok(())
+    }
+
+    /// Forget the passphrase-derived key
+    pub fn lock(&self) {
+        self.unlocked_key.forget();
+        info!("[Storage] Locked provider configs");
+    }
+
+    /// Protect the file with a master passphrase instead of the local key
+    pub fn set_passphrase(&self, new_passphrase: &str) -> Result<(), KeychainError> {
+        let _lock = self.lock_exclusive()?;
+        if self.passphrase_kdf()?.is_some() {
+            return Err("A master passphrase is already set".to_string().into());
+        }
+
+        let stored = self.read_all()?;
+        self.reseal_with_passphrase(&stored.configs, new_passphrase)?;
+
+        info!("[Storage] Master passphrase enabled");
+        Ok(())
+    }
+
+    /// Re-encrypt the file under a new master passphrase
+    pub fn change_passphrase(
+        &self,
+        current_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<(), KeychainError> {
+        let _lock = self.lock_exclusive()?;
+        self.unlock(current_passphrase)?;
+        let stored = self.read_all()?;
+        self.reseal_with_passphrase(&stored.configs, new_passphrase)?;
+
+        info!("[Storage] Master passphrase changed");
+        Ok(())
+    }
+
+    /// Go back to protecting the file with the local key
+    pub fn remove_passphrase(&self, current_passphrase: &str) -> Result<(), KeychainError> {
+        let _lock = self.lock_exclusive()?;
+        self.unlock(current_passphrase)?;
+        let stored = self.read_all()?;
+
+        let key = machine_key::load_or_create_local_key(&self.key_path())?;
+        self.write_sealed(&stored.configs, &key, KdfId::LocalKeyFile)?;
+        self.unlocked_key.forget();
+
+        info!("[Storage] Master passphrase removed");
+        Ok(())
+    }
+
+    /// Argon2id parameters from the file header, if the file is passphrase protected
+    fn passphrase_kdf(&self) -> Result<Option<envelope::PassphraseKdf>, String> {
+        let raw = match fs::read(&self.path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read config file: {}", e)),
+        };
+        if raw.is_empty() {
+            return Ok(None);
+        }
+
+        // A damaged header, including out-of-range KDF costs, is left for `read_all` to
+        // quarantine while it holds the store lock
+        let Ok(payload) = envelope::parse(&raw) else {
+            return Ok(None);
+        };
+        Ok(match payload {
+            StoredPayload::Envelope(sealed) => match sealed.kdf {
+                KdfId::Argon2idPassphrase(kdf) => Some(kdf),
+                _ => None,
+            },
+            StoredPayload::Legacy(_) => None,
+        })
+    }
+
+    /// Derive the key for `passphrase` and check that it opens the current file
+    fn verify_passphrase(
+        &self,
+        passphrase: &str,
+        kdf: &envelope::PassphraseKdf,
+    ) -> Result<[u8; 32], KeychainError> {
+        let key = passphrase::derive_key(passphrase, kdf)?;
+        let raw = fs::read(&self.path).map_err(|e| format!("Failed to read config file: {}", e))?;
+        let StoredPayload::Envelope(sealed) = envelope::parse(&raw)? else {
+            return Err("Config file is not passphrase protected".to_string().into());
+        };
+
+        envelope::open(&key, &sealed).map_err(|_| KeychainError::Locked {
+            message: "Incorrect master passphrase".to_string(),
+        })?;
+        Ok(key)
+    }
+
+    fn reseal_with_passphrase(
+        &self,
+        configs: &HashMap<String, ProviderConfig>,
+        new_passphrase: &str,
+    ) -> Result<(), KeychainError> {
+        if new_passphrase.is_empty() {
+            return Err("Master passphrase must not be empty".to_string().into());
+        }
+
+        let kdf = passphrase::new_kdf();
+        let key = passphrase::derive_key(new_passphrase, &kdf)?;
+        self.write_sealed(configs, &key, KdfId::Argon2idPassphrase(kdf))?;
+        self.unlocked_key.remember(kdf, key);
+        Ok(())
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Path of the random local key protecting the config file
+    fn key_path(&self) -> PathBuf {
+        self.path.with_extension("key")
+    }
+
+    /// Take the advisory lock serializing load-modify-save cycles across threads and
+    /// processes. The lock is released when the returned file is dropped.
+    ///
+    /// Locks are per open file, so callers must not take it again while holding it.
+    fn lock_exclusive(&self) -> Result<fs::File, String> {
+        let lock_path = self.path.with_extension("lock");
+        create_parent_dir(&lock_path)?;
+        let lock_file = owner_only_options()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .map_err(|e| {
+                format!(
+                    "Failed to open config lock file '{}': {}",
+                    lock_path.display(),
+                    e
+                )
+            })?;
+        lock_file
+            .lock()
+            .map_err(|e| format!("Failed to lock config file: {}", e))?;
+        Ok(lock_file)
+    }
+
+    /// Move an unreadable config file aside instead of deleting it
+    fn quarantine(&self, reason: &str) -> KeychainError {
+        let quarantined_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let quarantine_path = quarantine_path_for(&self.path, quarantined_at);
+
+        match fs::rename(&self.path, &quarantine_path) {
+            Ok(()) => {
+                warn!(
+                    "[Storage] Moved unreadable config file to '{}': {}",
+                    quarantine_path.display(),
+                    reason
+                );
+                KeychainError::Unreadable {
+                    message: format!(
+                        "Your provider keys could not be read ({}). The file was kept at '{}' so it can be recovered.",
+                        reason,
+                        quarantine_path.display()
+                    ),
+                    quarantine_path: quarantine_path.to_string_lossy().to_string(),
+                }
+            }
+            Err(e) => {
+                error!(
+                    "[Storage] Failed to quarantine unreadable config file: {}",
+                    e
+                );
+                KeychainError::Other {
+                    message: format!(
+                        "Your provider keys could not be read ({}) and the file could not be moved aside: {}",
+                        reason, e
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Decrypt a stored payload, returning the plaintext and whether it should be rewritten
+    /// with the local key.
+    ///
+    /// Payloads bound to the device name are tried against every known spelling of this
+    /// machine's name, so renaming the machine does not lose stored keys. `extra_keys` are
+    /// tried last for every payload kind.
+    fn decrypt_payload(
+        &self,
+        payload: &StoredPayload,
+        extra_keys: &[[u8; 32]],
+    ) -> Result<(Vec<u8>, bool), String> {
+        match payload {
+            StoredPayload::Envelope(sealed) if sealed.kdf == KdfId::LocalKeyFile => {
+                if let Some(key) = machine_key::load_local_key(&self.key_path())? {
+                    if let Ok(plaintext) = envelope::open(&key, sealed) {
+                        return Ok((plaintext, false));
+                    }
+                }
+                extra_keys
+                    .iter()
+                    .find_map(|key| envelope::open(key, sealed).ok())
+                    .map(|plaintext| (plaintext, true))
+                    .ok_or_else(|| {
+                        format!(
+                            "local key '{}' is missing or does not match",
+                            self.key_path().display()
+                        )
+                    })
+            }
+            StoredPayload::Envelope(sealed) => {
+                if let KdfId::Argon2idPassphrase(kdf) = &sealed.kdf {
+                    return self
+                        .unlocked_key
+                        .get(kdf)
+                        .iter()
+                        .chain(extra_keys)
+                        .find_map(|key| envelope::open(key, sealed).ok())
+                        .map(|plaintext| (plaintext, false))
+                        .ok_or_else(|| "master passphrase does not match".to_string());
+                }
+                machine_key::device_name_key_candidates()
+                    .iter()
+                    .chain(extra_keys)
+                    .find_map(|key| envelope::open(key, sealed).ok())
+                    .map(|plaintext| (plaintext, true))
+                    .ok_or_else(|| "no device name key matches".to_string())
+            }
+            StoredPayload::Legacy(ciphertext) => machine_key::device_name_key_candidates()
+                .iter()
+                .chain(extra_keys)
+                .find_map(|key| envelope::open_legacy(key, ciphertext).ok())
+                .map(|plaintext| (plaintext, true))
+                .ok_or_else(|| "no device name key matches the legacy file".to_string()),
+        }
+    }
+
+    /// Internal: Get all configs from encrypted file.
+    ///
+    /// A passphrase remembered for a file that is no longer passphrase protected, e.g.
+    /// because another instance removed it, is forgotten here.
+    fn read_all(&self) -> Result<StoredConfigs, KeychainError> {
+        let config_path = &self.path;
+        let empty = || StoredConfigs {
+            configs: HashMap::new(),
+            passphrase_key: None,
+        };
+
+        if !config_path.exists() {
+            debug!("[Storage] Config file does not exist, returning empty map");
+            self.unlocked_key.forget();
+            return Ok(empty());
+        }
+
+        let raw = fs::read(config_path).map_err(|e| {
+            error!("[Storage] Failed to read config file: {}", e);
+            e.to_string()
+        })?;
+
+        if raw.is_empty() {
+            debug!("[Storage] Config file is empty, returning empty map");
+            self.unlocked_key.forget();
+            return Ok(empty());
+        }
+
+        let payload = envelope::parse(&raw).map_err(|e| {
+            error!("[Storage] Failed to parse config file: {}", e);
+            self.quarantine(&e)
+        })?;
+
+        // Passphrase-protected files stay untouched until the user unlocks them
+        let passphrase_key = match &payload {
+            StoredPayload::Envelope(sealed) => match &sealed.kdf {
+                KdfId::Argon2idPassphrase(kdf) => {
+                    let Some(key) = self.unlocked_key.get(kdf) else {
+                        debug!("[Storage] Config file is locked by the master passphrase");
+                        return Err(KeychainError::Locked {
+                            message:
+                                "Provider keys are locked. Enter your master passphrase to unlock them."
+                                    .to_string(),
+                        });
+                    };
+                    Some((*kdf, key))
+                }
+                _ => None,
+            },
+            StoredPayload::Legacy(_) => None,
+        };
+        if passphrase_key.is_none() {
+            self.unlocked_key.forget();
+        }
+
+        // Decrypt
+        let (decrypted_data, needs_rewrite) = self.decrypt_payload(&payload, &[]).map_err(|e| {
+            error!("[Storage] Failed to decrypt config: {}", e);
+            self.quarantine(&e)
+        })?;
+
+        let configs = decode_configs(decrypted_data).map_err(|e| {
+            error!("[Storage] {}", e);
+            self.quarantine(&e)
+        })?;
+
+        let stored = StoredConfigs {
+            configs,
+            passphrase_key,
+        };
+        if needs_rewrite {
+            info!("[Storage] Re-encrypting config file with the local key");
+            if let Err(e) = self.write_all(&stored) {
+                warn!("[Storage] Failed to re-encrypt config file: {}", e);
+            }
+        }
+
+        Ok(stored)
+    }
+
+    /// Internal: Save all configs to encrypted file, sealed the way it was when read
+    fn write_all(&self, stored: &StoredConfigs) -> Result<(), String> {
+        match &stored.passphrase_key {
+            Some((kdf, key)) => {
+                self.write_sealed(&stored.configs, key, KdfId::Argon2idPassphrase(*kdf))
+            }
+            None => {
+                let key = machine_key::load_or_create_local_key(&self.key_path())?;
+                self.write_sealed(&stored.configs, &key, KdfId::LocalKeyFile)
+            }
+        }
+    }
+
+    /// Internal: Encrypt configs under `key` and a fresh nonce and write them to file
+    fn write_sealed(
+        &self,
+        configs: &HashMap<String, ProviderConfig>,
+        key: &[u8; 32],
+        kdf: KdfId,
+    ) -> Result<(), String> {
+        debug!("[Storage] Saving configs for {} providers", configs.len());
+
+        // Serialize to JSON
+        let json_str = serde_json::to_string(configs).map_err(|e| {
+            error!("[Storage] Failed to serialize configs: {}", e);
+            e.to_string()
+        })?;
+
+        // Encrypt
+        let sealed = envelope::seal(key, kdf, json_str.as_bytes()).map_err(|e| {
+            error!("[Storage] {}", e);
+            e
+        })?;
+
+        // Write to file
+        write_atomically(&self.path, &sealed.to_bytes()).map_err(|e| {
+            error!("[Storage] Failed to write config file: {}", e);
+            e.to_string()
+        })?;
+
+        info!(
+            "[Storage] Successfully saved configs for {} providers to file",
+            configs.len()
+        );
+        Ok(())
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn backend(&self) -> SecretStoreBackend {
+        SecretStoreBackend::EncryptedFile
+    }
+
+    fn load_all(&self) -> Result<HashMap<String, ProviderConfig>, KeychainError> {
+        // Reading may migrate or quarantine the file, so it takes the same lock as writers
+        let _lock = self.lock_exclusive()?;
+        Ok(self.read_all()?.configs)
+    }
+
+    fn save(&self, provider: &str, config: &ProviderConfig) -> Result<(), KeychainError> {
+        let _lock = self.lock_exclusive()?;
+        let mut stored = self.read_all()?;
+        stored.configs.insert(provider.to_string(), config.clone());
+        Ok(self.write_all(&stored)?)
+    }
+
+    fn delete(&self, provider: &str) -> Result<(), KeychainError> {
+        let _lock = self.lock_exclusive()?;
+        let mut stored = self.read_all()?;
+        stored.configs.remove(provider);
+        Ok(self.write_all(&stored)?)
+    }
+
+    fn update(
+        &self,
+        provider: &str,
+        update: &mut (dyn FnMut(&mut ProviderConfig) -> bool + Send),
+    ) -> Result<(), KeychainError> {
+        let _lock = self.lock_exclusive()?;
+        let mut stored = self.read_all()?;
+        let Some(config) = stored.configs.get_mut(provider) else {
+            return Ok(());
+        };
+        if update(config) {
+            self.write_all(&stored)?;
+        }
+        Ok(())
+    }
+
+    fn upsert(&self, provider: &str, upsert: &mut UpsertConfig<'_>) -> Result<(), KeychainError> {
+        let _lock = self.lock_exclusive()?;
+        let mut stored = self.read_all()?;
+        let Some(config) = upsert(stored.configs.get(provider))? else {
+            return Ok(());
+        };
+        stored.configs.insert(provider.to_string(), config);
+        Ok(self.write_all(&stored)?)
+    }
+}
+
+fn quarantine_path_for(config_path: &Path, quarantined_at: u64) -> PathBuf {
+    let file_name = config_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    config_path.with_file_name(format!(
+        "{}.{}.{}",
+        file_name, quarantined_at, QUARANTINE_SUFFIX
+    ))
+}
+
+/// Open options creating files readable and writable by the owner only
+pub(super) fn owner_only_options() -> fs::OpenOptions {
+    let mut options = fs::OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+/// Create the directory `path` is in, e.g. the app support directory on first use
+pub(super) fn create_parent_dir(path: &Path) -> Result<(), String> {
+    let Some(parent_dir) = path.parent() else {
+        return Ok(());
+    };
+    fs::create_dir_all(parent_dir).map_err(|e| {
+        format!(
+            "Failed to create directory '{}': {}",
+            parent_dir.display(),
+            e
+        )
+    })
+}
+
+/// Replace `path` so readers see either the old or the new contents, never a torn write.
+///
+/// The temporary file is unique to this call, so concurrent writers never share one even
+/// without holding the config lock.
+pub(super) fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let temp_path = write_temp_file(path, contents).map_err(|e| e.to_string())?;
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.to_string());
+    }
+
+    sync_parent_dir(path);
+    Ok(())
+}
+
+/// Write `contents` to a new owner-only file next to `path` and flush it to disk
+pub(super) fn write_temp_file(path: &Path, contents: &[u8]) -> std::io::Result<PathBuf> {
+    let temp_path = path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = owner_only_options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        });
+
+    match result {
+        Ok(()) => Ok(temp_path),
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Persist a rename or link into the directory `path` is in
+pub(super) fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent_dir) = path.parent() {
+        if let Err(e) = fs::File::open(parent_dir).and_then(|dir| dir.sync_all()) {
+            warn!("[Storage] Failed to sync config directory: {}", e);
+        }
+    }
+}
+
+fn decode_configs(decrypted_data: Vec<u8>) -> Result<HashMap<String, ProviderConfig>, String> {
+    let json_str = String::from_utf8(decrypted_data)
+        .map_err(|e| format!("Failed to parse decrypted data as UTF-8: {}", e))?;
+
+    serde_json::from_str(&json_str).map_err(|e| format!("Failed to deserialize configs: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes_gcm::{
+        aead::{Aead, KeyInit},
+        Aes256Gcm, Nonce,
+    };
+    use base64::{engine::general_purpose, Engine as _};
+
+    use crate::keychain::provider_config::ProviderCredential;
+
+    /// Config file path in the temp directory. The file and everything stored next to it
+    /// are removed when the test ends, also when it fails.
+    struct TempConfigPath(PathBuf);
+
+    impl std::ops::Deref for TempConfigPath {
+        type Target = PathBuf;
+
+        fn deref(&self) -> &PathBuf {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempConfigPath {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempConfigPath {
+        fn drop(&mut self) {
+            let store = EncryptedFileStore::new(self.0.clone(), Arc::default());
+            for entry in store.list_quarantined().unwrap_or_default() {
+                let _ = fs::remove_file(self.0.with_file_name(entry.file_name));
+            }
+            let _ = fs::remove_file(self.0.with_extension("key"));
+            let _ = fs::remove_file(self.0.with_extension("lock"));
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn temp_config_path(name: &str) -> TempConfigPath {
+        let path = TempConfigPath(std::env::temp_dir().join(format!(
+            "mind_flayer_file_store_{}_{}.dat",
+            name,
+            std::process::id()
+        )));
+        // Left behind by a run that was killed
+        let _ = fs::remove_file(&*path);
+        path
+    }
+
+    #[test]
+    fn migrates_legacy_file_on_first_read() {
+        let path = temp_config_path("legacy");
+        let cipher = Aes256Gcm::new(&machine_key::device_name_key(&whoami::devicename()).into());
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(b"mind-flayer!"),
+                br#"{"openai":{"apiKey":"legacy-key"}}"#.as_ref(),
+            )
+            .unwrap();
+        fs::write(&path, general_purpose::STANDARD.encode(ciphertext)).unwrap();
+
+        let store = EncryptedFileStore::new(path.clone(), Arc::default());
+        assert_eq!(
+            store.load_all().unwrap()["openai"].api_key(),
+            Some("legacy-key")
+        );
+
+        let migrated = fs::read(&path).unwrap();
+        assert!(matches!(
+            envelope::parse(&migrated).unwrap(),
+            StoredPayload::Envelope(_)
+        ));
+        assert_eq!(
+            store.load_all().unwrap()["openai"].api_key(),
+            Some("legacy-key")
+        );
+    }
+
+    #[test]
+    fn moves_device_name_envelope_to_local_key() {
+        let path = temp_config_path("device_name");
+        let sealed = envelope::seal(
+            &machine_key::device_name_key(&whoami::devicename()),
+            KdfId::DeviceNameSha256,
+            br#"{"anthropic":{"apiKey":"device-key"}}"#,
+        )
+        .unwrap();
+        fs::write(&path, sealed.to_bytes()).unwrap();
+
+        let store = EncryptedFileStore::new(path.clone(), Arc::default());
+        assert_eq!(
+            store.load_all().unwrap()["anthropic"].api_key(),
+            Some("device-key")
+        );
+
+        let StoredPayload::Envelope(rewritten) =
+            envelope::parse(&fs::read(&path).unwrap()).unwrap()
+        else {
+            panic!("expected envelope payload");
+        };
+        assert_eq!(rewritten.kdf, KdfId::LocalKeyFile);
+        assert!(path.with_extension("key").exists());
+    }
+
+    #[test]
+    fn rewrites_file_with_new_nonce_on_every_save() {
+        let path = temp_config_path("nonce");
+        let store = EncryptedFileStore::new(path.clone(), Arc::default());
+        let config = ProviderConfig::from_api_key("key".to_string(), None);
+
+        store.save("openai", &config).unwrap();
+        let first = fs::read(&path).unwrap();
+        store.save("openai", &config).unwrap();
+        let second = fs::read(&path).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(store.load_all().unwrap()["openai"].api_key(), Some("key"));
+    }
+
+    #[test]
+    fn quarantines_unreadable_file_and_recovers_with_old_device_name() {
+        let path = temp_config_path("quarantine");
+        let sealed = envelope::seal(
+            &machine_key::device_name_key("old-laptop"),
+            KdfId::DeviceNameSha256,
+            br#"{"openai":{"apiKey":"old-key"}}"#,
+        )
+        .unwrap();
+        fs::write(&path, sealed.to_bytes()).unwrap();
+
+        let store = EncryptedFileStore::new(path.clone(), Arc::default());
+        let err = store.load_all().unwrap_err();
+        let KeychainError::Unreadable {
+            quarantine_path, ..
+        } = err
+        else {
+            panic!("expected unreadable error, got {:?}", err);
+        };
+        assert!(!path.exists());
+        assert!(PathBuf::from(&quarantine_path).exists());
+        assert_eq!(store.list_quarantined().unwrap().len(), 1);
+
+        // New keys saved after the failure must survive recovery
+        let config = ProviderConfig::from_api_key("new-key".to_string(), None);
+        store.save("anthropic", &config).unwrap();
+
+        assert!(store.recover(None, Some("wrong-name")).is_err());
+        let restored = store.recover(None, Some("old-laptop")).unwrap();
+        assert_eq!(restored, vec!["openai".to_string()]);
+        assert_eq!(
+            store.load_all().unwrap()["openai"].api_key(),
+            Some("old-key")
+        );
+        assert_eq!(
+            store.load_all().unwrap()["anthropic"].api_key(),
+            Some("new-key")
+        );
+        assert!(store.list_quarantined().unwrap().is_empty());
+    }
+
+    #[test]
+    fn quarantines_file_with_out_of_range_kdf_costs() {
+        let path = temp_config_path("kdf-costs");
+        let kdf = envelope::PassphraseKdf {
+            memory_kib: u32::MAX,
+            ..passphrase::new_kdf()
+        };
+        let sealed = envelope::seal(
+            &[0u8; 32],
+            KdfId::Argon2idPassphrase(kdf),
+            br#"{"openai":{"apiKey":"k"}}"#,
+        )
+        .unwrap();
+        fs::write(&path, sealed.to_bytes()).unwrap();
+
+        let store = EncryptedFileStore::new(path.clone(), Arc::default());
+        assert!(!store.passphrase_status().unwrap().enabled);
+        assert!(matches!(
+            store.load_all(),
+            Err(KeychainError::Unreadable { .. })
+        ));
+        assert!(!path.exists());
+        assert_eq!(store.list_quarantined().unwrap().len(), 1);
+        assert!(store.recover(None, None).is_err());
+    }
+
+    #[test]
+    fn concurrent_writers_do_not_lose_updates() {
+        let path = temp_config_path("concurrent");
+
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let store = EncryptedFileStore::new(path, Arc::default());
+                    for round in 0..5 {
+                        let config =
+                            ProviderConfig::from_api_key(format!("key-{}-{}", writer, round), None);
+                        store
+                            .save(&format!("provider_{}", writer), &config)
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let configs = EncryptedFileStore::new(path.clone(), Arc::default())
+            .load_all()
+            .unwrap();
+        assert_eq!(configs.len(), 8);
+        for writer in 0..8 {
+            assert_eq!(
+                configs[&format!("provider_{}", writer)].api_key(),
+                Some(format!("key-{}-4", writer).as_str())
+            );
+        }
+    }
+
+    /// Set for the child processes started by `concurrent_processes_do_not_lose_updates`
+    const CHILD_CONFIG_PATH_ENV_KEY: &str = "MIND_FLAYER_TEST_CHILD_CONFIG_PATH";
+    const CHILD_WRITER_ENV_KEY: &str = "MIND_FLAYER_TEST_CHILD_WRITER";
+
+    #[test]
+    #[ignore = "run in child processes by concurrent_processes_do_not_lose_updates"]
+    fn write_from_child_process() {
+        let (Ok(path), Ok(writer)) = (
+            std::env::var(CHILD_CONFIG_PATH_ENV_KEY),
+            std::env::var(CHILD_WRITER_ENV_KEY),
+        ) else {
+            return;
+        };
+        let store = EncryptedFileStore::new(PathBuf::from(path), Arc::default());
+        for round in 0..5 {
+            let config = ProviderConfig::from_api_key(format!("key-{}-{}", writer, round), None);
+            store
+                .save(&format!("provider_{}", writer), &config)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn concurrent_processes_do_not_lose_updates() {
+        let path = temp_config_path("processes");
+        // Creates the local key before the writers race for the config file
+        EncryptedFileStore::new(path.clone(), Arc::default())
+            .save(
+                "shared",
+                &ProviderConfig::from_api_key("key-shared".to_string(), None),
+            )
+            .unwrap();
+
+        // Test names leave out the crate name
+        let module = module_path!()
+            .split_once("::")
+            .map_or(module_path!(), |(_, module)| module);
+        let children: Vec<_> = (0..4)
+            .map(|writer| {
+                std::process::Command::new(std::env::current_exe().unwrap())
+                    .args([
+                        "--exact",
+                        &format!("{}::write_from_child_process", module),
+                        "--ignored",
+                        "--test-threads=1",
+                    ])
+                    .env(CHILD_CONFIG_PATH_ENV_KEY, path.as_os_str())
+                    .env(CHILD_WRITER_ENV_KEY, writer.to_string())
+                    .stdout(std::process::Stdio::null())
+                    .spawn()
+                    .unwrap()
+            })
+            .collect();
+        for mut child in children {
+            assert!(child.wait().unwrap().success());
+        }
+
+        let configs = EncryptedFileStore::new(path.clone(), Arc::default())
+            .load_all()
+            .unwrap();
+        assert_eq!(configs.len(), 5);
+        for writer in 0..4 {
+            assert_eq!(
+                configs[&format!("provider_{}", writer)].api_key(),
+                Some(format!("key-{}-4", writer).as_str())
+            );
+        }
+    }
+
+    #[test]
+    fn concurrent_updates_see_each_other() {
+        let path = temp_config_path("concurrent-update");
+        EncryptedFileStore::new(path.clone(), Arc::default())
+            .save(
+                "openai",
+                &ProviderConfig::from_api_key("sk-primary".to_string(), None),
+            )
+            .unwrap();
+
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let store = EncryptedFileStore::new(path, Arc::default());
+                    for round in 0..5 {
+                        store
+                            .update("openai", &mut |config| {
+                                config.credentials.push(ProviderCredential {
+                                    label: format!("backup-{}-{}", writer, round),
+                                    api_key: format!("sk-{}-{}", writer, round),
+                                    active: false,
+                                    metadata: Default::default(),
+                                });
+                                true
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let configs = EncryptedFileStore::new(path.clone(), Arc::default())
+            .load_all()
+            .unwrap();
+        assert_eq!(configs["openai"].credentials.len(), 41);
+        assert_eq!(configs["openai"].api_key(), Some("sk-primary"));
+    }
+
+    #[test]
+    fn keeps_the_seal_another_instance_left_on_disk() {
+        let path = temp_config_path("stale-passphrase");
+        let store = EncryptedFileStore::new(path.clone(), Arc::default());
+        let config = ProviderConfig::from_api_key("key".to_string(), None);
+        store.save("openai", &config).unwrap();
+        store.set_passphrase("hunter2 hunter2").unwrap();
+
+        // Another instance removes the passphrase; this process still remembers it
+        let local_key = machine_key::load_or_create_local_key(&path.with_extension("key")).unwrap();
+        let configs = store.load_all().unwrap();
+        store
+            .write_sealed(&configs, &local_key, KdfId::LocalKeyFile)
+            .unwrap();
+
+        store.save("anthropic", &config).unwrap();
+        let StoredPayload::Envelope(sealed) = envelope::parse(&fs::read(&path).unwrap()).unwrap()
+        else {
+            panic!("expected envelope payload");
+        };
+        assert_eq!(sealed.kdf, KdfId::LocalKeyFile);
+        assert!(!store.passphrase_status().unwrap().enabled);
+        assert_eq!(store.load_all().unwrap().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restricts_config_file_to_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_config_path("permissions");
+        // Files written by older versions were world readable
+        fs::write(&path, b"").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let store = EncryptedFileStore::new(path.clone(), Arc::default());
+        let config = ProviderConfig::from_api_key("key".to_string(), None);
+        store.save("openai", &config).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
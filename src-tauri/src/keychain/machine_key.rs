@@ -0,0 +1,203 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use super::file_store;
+
+const LOCAL_KEY_LEN: usize = 32;
+const DEVICE_NAME_KEY_LABEL: &[u8] = b"mind-flayer-v1";
+
+/// Load the random local key, or `None` if it has not been generated yet. A key file of the
+/// wrong length cannot have sealed anything and counts as missing.
+pub fn load_local_key(key_path: &Path) -> Result<Option<[u8; 32]>, String> {
+    let raw = match fs::read(key_path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(format!(
+                "Failed to read local key file '{}': {}",
+                key_path.display(),
+                e
+            ))
+        }
+    };
+
+    match <[u8; LOCAL_KEY_LEN]>::try_from(raw.as_slice()) {
+        Ok(key) => Ok(Some(key)),
+        Err(_) => {
+            warn!(
+                "[Storage] Ignoring local key file '{}' with unexpected length {}",
+                key_path.display(),
+                raw.len()
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Load the random local key, generating it with owner-only permissions on first use.
+///
+/// Unlike the device name, this key survives hostname changes because it only depends on
+/// the file itself.
+pub fn load_or_create_local_key(key_path: &Path) -> Result<[u8; 32], String> {
+    if let Some(key) = load_local_key(key_path)? {
+        return Ok(key);
+    }
+
+    let mut key = [0u8; LOCAL_KEY_LEN];
+    OsRng.fill_bytes(&mut key);
+
+    // Linked into place only once it was written in full, so a crash never leaves a short
+    // key file behind, and linking never replaces a key another writer created first
+    let temp_path = file_store::write_temp_file(key_path, &key).map_err(|e| {
+        format!(
+            "Failed to write local key file '{}': {}",
+            key_path.display(),
+            e
+        )
+    })?;
+    let existing = match fs::hard_link(&temp_path, key_path) {
+        Ok(()) => Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            match load_local_key(key_path) {
+                // Another writer created the key first, use theirs
+                Ok(Some(theirs)) => Ok(Some(theirs)),
+                // A torn key file, e.g. from an older version, sealed nothing and is replaced
+                Ok(None) => fs::rename(&temp_path, key_path).map(|_| None).map_err(|e| {
+                    format!(
+                        "Failed to replace local key file '{}': {}",
+                        key_path.display(),
+                        e
+                    )
+                }),
+                Err(e) => Err(e),
+            }
+        }
+        Err(e) => Err(format!(
+            "Failed to create local key file '{}': {}",
+            key_path.display(),
+            e
+        )),
+    };
+    let _ = fs::remove_file(&temp_path);
+    if let Some(theirs) = existing? {
+        return Ok(theirs);
+    }
+    file_store::sync_parent_dir(key_path);
+
+    info!(
+        "[Storage] Generated local config key '{}'",
+        key_path.display()
+    );
+    Ok(key)
+}
+
+/// Key derived from a device name, as used before the local key file existed
+pub fn device_name_key(device_name: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(DEVICE_NAME_KEY_LABEL);
+    hasher.update(device_name.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Keys for every name this machine may have been known by when a legacy file was written.
+///
+/// Renames often change only one of the device name and host name, so both are tried,
+/// together with common host name spellings (`.local` suffix, short name, lowercase).
+pub fn device_name_key_candidates() -> Vec<[u8; 32]> {
+    let mut names = vec![whoami::devicename()];
+    if let Ok(hostname) = whoami::fallible::hostname() {
+        names.extend(host_name_variants(&hostname));
+    }
+    for env_key in ["HOSTNAME", "COMPUTERNAME"] {
+        if let Ok(value) = std::env::var(env_key) {
+            names.extend(host_name_variants(&value));
+        }
+    }
+
+    let mut unique_names: Vec<String> = Vec::new();
+    for name in names {
+        if !name.is_empty() && !unique_names.contains(&name) {
+            unique_names.push(name);
+        }
+    }
+
+    unique_names
+        .iter()
+        .map(|name| device_name_key(name))
+        .collect()
+}
+
+fn host_name_variants(hostname: &str) -> Vec<String> {
+    let trimmed = hostname.trim();
+    let short = trimmed.split('.').next().unwrap_or(trimmed);
+    let mut variants = vec![
+        trimmed.to_string(),
+        short.to_string(),
+        format!("{}.local", short),
+    ];
+    variants.extend(
+        variants
+            .clone()
+            .into_iter()
+            .map(|variant| variant.to_lowercase()),
+    );
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_local_key_once_with_owner_only_permissions() {
+        let key_path =
+            std::env::temp_dir().join(format!("mind_flayer_local_key_{}.key", std::process::id()));
+        let _ = fs::remove_file(&key_path);
+
+        let first = load_or_create_local_key(&key_path).unwrap();
+        let second = load_or_create_local_key(&key_path).unwrap();
+        assert_eq!(first, second);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&key_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let _ = fs::remove_file(key_path);
+    }
+
+    #[test]
+    fn replaces_a_torn_local_key_file() {
+        let key_path = std::env::temp_dir().join(format!(
+            "mind_flayer_torn_local_key_{}.key",
+            std::process::id()
+        ));
+        fs::write(&key_path, [7u8; 5]).unwrap();
+
+        assert_eq!(load_local_key(&key_path).unwrap(), None);
+        let key = load_or_create_local_key(&key_path).unwrap();
+        assert_eq!(fs::read(&key_path).unwrap(), key);
+        assert_eq!(load_local_key(&key_path).unwrap(), Some(key));
+
+        let _ = fs::remove_file(key_path);
+    }
+
+    #[test]
+    fn includes_current_device_name_in_candidates() {
+        let current = device_name_key(&whoami::devicename());
+        assert!(device_name_key_candidates().contains(&current));
+    }
+
+    #[test]
+    fn expands_host_name_spellings() {
+        let variants = host_name_variants("Johns-MacBook-Pro.local");
+        assert!(variants.contains(&"Johns-MacBook-Pro.local".to_string()));
+        assert!(variants.contains(&"Johns-MacBook-Pro".to_string()));
+        assert!(variants.contains(&"johns-macbook-pro".to_string()));
+    }
+}
@@ -0,0 +1,105 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::sync::Mutex;
+
+use super::envelope::{PassphraseKdf, SALT_LEN};
+
+/// Argon2id costs for newly set passphrases (OWASP minimum: 19 MiB, 2 passes)
+const MEMORY_KIB: u32 = 19_456;
+const ITERATIONS: u32 = 2;
+const PARALLELISM: u32 = 1;
+
+/// Passphrase-derived key of the config file while it is unlocked. Owned by the config
+/// store and shared with every file store it opens; the key is zeroed when it is replaced,
+/// forgotten or dropped.
+#[derive(Default)]
+pub struct UnlockedKey {
+    unlocked: Mutex<Option<(PassphraseKdf, [u8; 32])>>,
+}
+
+/// Fresh Argon2id parameters with a random salt
+pub fn new_kdf() -> PassphraseKdf {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    PassphraseKdf {
+        salt,
+        memory_kib: MEMORY_KIB,
+        iterations: ITERATIONS,
+        parallelism: PARALLELISM,
+    }
+}
+
+/// Derive the file encryption key from a passphrase
+pub fn derive_key(passphrase: &str, kdf: &PassphraseKdf) -> Result<[u8; 32], String> {
+    kdf.check_bounds()?;
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| format!("Invalid passphrase key parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &kdf.salt, &mut key)
+        .map_err(|e| format!("Failed to derive key from passphrase: {}", e))?;
+    Ok(key)
+}
+
+impl UnlockedKey {
+    /// Remember the key until the file is locked again
+    pub fn remember(&self, kdf: PassphraseKdf, key: [u8; 32]) {
+        let mut unlocked = self.unlocked.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, previous)) = unlocked.as_mut() {
+            previous.fill(0);
+        }
+        *unlocked = Some((kdf, key));
+    }
+
+    /// The unlocked key, if it was derived with the same parameters
+    pub fn get(&self, kdf: &PassphraseKdf) -> Option<[u8; 32]> {
+        let unlocked = self.unlocked.lock().unwrap_or_else(|e| e.into_inner());
+        unlocked
+            .as_ref()
+            .filter(|(unlocked_kdf, _)| unlocked_kdf == kdf)
+            .map(|(_, key)| *key)
+    }
+
+    /// Drop the unlocked key
+    pub fn forget(&self) {
+        let mut unlocked = self.unlocked.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, mut key)) = unlocked.take() {
+            key.fill(0);
+        }
+    }
+}
+
+impl Drop for UnlockedKey {
+    fn drop(&mut self) {
+        self.forget();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_the_same_key_only_for_the_same_passphrase_and_salt() {
+        let kdf = new_kdf();
+        let key = derive_key("correct horse", &kdf).unwrap();
+
+        assert_eq!(derive_key("correct horse", &kdf).unwrap(), key);
+        assert_ne!(derive_key("battery staple", &kdf).unwrap(), key);
+        assert_ne!(derive_key("correct horse", &new_kdf()).unwrap(), key);
+    }
+
+    #[test]
+    fn keeps_only_the_latest_unlocked_key() {
+        let (first_kdf, second_kdf) = (new_kdf(), new_kdf());
+        let unlocked = UnlockedKey::default();
+
+        unlocked.remember(first_kdf, [1; 32]);
+        unlocked.remember(second_kdf, [2; 32]);
+        assert_eq!(unlocked.get(&first_kdf), None);
+        assert_eq!(unlocked.get(&second_kdf), Some([2; 32]));
+
+        unlocked.forget();
+        assert_eq!(unlocked.get(&second_kdf), None);
+    }
+}
@@ -0,0 +1,896 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+use super::connection_test::ConnectionTestResult;
+use super::KeychainError;
+
+/// Label given to the key of entries saved before multiple credentials existed
+const DEFAULT_CREDENTIAL_LABEL: &str = "default";
+/// Upper bound for `request_timeout_ms`, long enough for slow reasoning models
+const MAX_REQUEST_TIMEOUT_MS: u64 = 30 * 60 * 1000;
+/// Headers derived from the credentials, which extra headers must not override
+const RESERVED_HEADERS: &[&str] = &["authorization", "x-api-key", "host", "content-length"];
+/// How far into a key a vendor prefix separator (`-` or `_`) is looked for
+const MASK_PREFIX_SEARCH_LEN: usize = 8;
+/// Fewest characters a masked key must hide
+const MASK_MIN_HIDDEN_LEN: usize = 8;
+/// Longest accepted credential note, in characters
+const MAX_NOTES_LEN: usize = 2000;
+
+/// One labelled API key of a provider
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderCredential {
+    pub label: String,
+    pub api_key: String,
+    /// Inactive credentials are kept but never used, e.g. a key that is being rotated out
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(flatten)]
+    pub metadata: CredentialMetadata,
+}
+
+/// Bookkeeping stored with each credential, in milliseconds since the Unix epoch.
+///
+/// Only `expires_at` and `notes` are set by the user, the timestamps are maintained by the
+/// host and carried over from the stored credential with the same label.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<u64>,
+    /// Last time the key was handed to the sidecar
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_pushed_at: Option<u64>,
+    /// Last connection test that accepted the key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_validated_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+/// A credential as sent by the frontend when saving.
+///
+/// `api_key` may be left out to keep the stored key with the same label, so labels, order,
+/// notes and expiry can be edited without handling plaintext keys.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialUpdate {
+    pub label: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(flatten)]
+    pub metadata: CredentialMetadata,
+}
+
+/// Provider settings as sent by the frontend to replace a stored configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderConfigUpdate {
+    pub credentials: Vec<CredentialUpdate>,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub extra_headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub organization: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub request_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub default_model: Option<String>,
+    #[serde(default)]
+    pub api_version: Option<String>,
+    #[serde(flatten)]
+    pub unknown_fields: Map<String, Value>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+/// Stored configuration of one provider
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredProviderConfig", into = "StoredProviderConfig")]
+pub struct ProviderConfig {
+    /// Credentials in failover order: the sidecar uses the first active one and moves on
+    /// to the next when a request is rejected
+    pub credentials: Vec<ProviderCredential>,
+    pub base_url: Option<String>,
+    /// Additional HTTP headers sent with every request, e.g. for API gateways
+    pub extra_headers: BTreeMap<String, String>,
+    /// OpenAI organization ID
+    pub organization: Option<String>,
+    /// OpenAI project ID
+    pub project: Option<String>,
+    pub request_timeout_ms: Option<u64>,
+    /// Model used when a request does not name one
+    pub default_model: Option<String>,
+    /// API version for providers that version their API per request (Anthropic)
+    pub api_version: Option<String>,
+    /// Milliseconds since the Unix epoch, set when the entry is first saved
+    pub created_at: Option<u64>,
+    /// Milliseconds since the Unix epoch, set on every save
+    pub updated_at: Option<u64>,
+    /// Result of the last connection test of the stored settings
+    pub last_test: Option<CachedConnectionTest>,
+    /// Fields this version does not know about, written back unchanged
+    pub unknown_fields: Map<String, Value>,
+}
+
+/// Connection test result together with the settings it was run against
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedConnectionTest {
+    /// [`ProviderConfig::connection_fingerprint`] of the tested settings
+    pub fingerprint: String,
+    #[serde(flatten)]
+    pub result: ConnectionTestResult,
+}
+
+/// Serialized form, tagged with the schema version.
+///
+/// Entries written before the schema was versioned carry no tag and are read as
+/// [`ProviderConfigV1`]. Entries from a newer schema fall back to the same best-effort
+/// read, keeping everything they contain in `unknown_fields`. A `v2` entry that does not
+/// parse is an error, so its settings are never silently dropped.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum StoredProviderConfig {
+    Versioned(Box<VersionedProviderConfig>),
+    Unversioned(ProviderConfigV1),
+}
+
+impl<'de> Deserialize<'de> for StoredProviderConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let result = match value.get("schema").and_then(Value::as_str) {
+            Some("v2") => serde_json::from_value(value)
+                .map(|versioned| StoredProviderConfig::Versioned(Box::new(versioned))),
+            _ => serde_json::from_value(value).map(StoredProviderConfig::Unversioned),
+        };
+        result.map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "schema")]
+enum VersionedProviderConfig {
+    #[serde(rename = "v2")]
+    V2(ProviderConfigV2),
+}
+
+/// `apiKey` with optional `credentials` and `baseUrl`
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProviderConfigV1 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    credentials: Vec<ProviderCredential>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_url: Option<String>,
+    #[serde(flatten)]
+    unknown_fields: Map<String, Value>,
+}
+
+/// `apiKey` is still written as the primary key so single-key readers (the settings page,
+/// older app versions) keep working.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProviderConfigV2 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_key: Option<String>,
+    #[serde(default)]
+    credentials: Vec<ProviderCredential>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_url: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    extra_headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    organization: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_timeout_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_test: Option<CachedConnectionTest>,
+    #[serde(flatten)]
+    unknown_fields: Map<String, Value>,
+}
+
+/// Entries that only have `apiKey` become a single credential
+fn migrate_credentials(
+    api_key: Option<String>,
+    credentials: Vec<ProviderCredential>,
+) -> Vec<ProviderCredential> {
+    match api_key {
+        Some(api_key) if credentials.is_empty() => {
+            ProviderConfig::from_api_key(api_key, None).credentials
+        }
+        _ => credentials,
+    }
+}
+
+impl From<StoredProviderConfig> for ProviderConfig {
+    fn from(stored: StoredProviderConfig) -> Self {
+        match stored {
+            StoredProviderConfig::Versioned(versioned) => {
+                let VersionedProviderConfig::V2(v2) = *versioned;
+                ProviderConfig {
+                    credentials: migrate_credentials(v2.api_key, v2.credentials),
+                    base_url: v2.base_url,
+                    extra_headers: v2.extra_headers,
+                    organization: v2.organization,
+                    project: v2.project,
+                    request_timeout_ms: v2.request_timeout_ms,
+                    default_model: v2.default_model,
+                    api_version: v2.api_version,
+                    created_at: v2.created_at,
+                    updated_at: v2.updated_at,
+                    last_test: v2.last_test,
+                    unknown_fields: v2.unknown_fields,
+                }
+            }
+            StoredProviderConfig::Unversioned(mut v1) => {
+                // A tag from a newer schema is replaced by ours when written back
+                v1.unknown_fields.remove("schema");
+                ProviderConfig {
+                    credentials: migrate_credentials(v1.api_key, v1.credentials),
+                    base_url: v1.base_url,
+                    unknown_fields: v1.unknown_fields,
+                    ..Default::default()
+                }
+            }
+        }
+    }
+}
+
+impl From<ProviderConfig> for StoredProviderConfig {
+    fn from(config: ProviderConfig) -> Self {
+        StoredProviderConfig::Versioned(Box::new(VersionedProviderConfig::V2(ProviderConfigV2 {
+            api_key: config.api_key().map(str::to_string),
+            credentials: config.credentials,
+            base_url: config.base_url,
+            extra_headers: config.extra_headers,
+            organization: config.organization,
+            project: config.project,
+            request_timeout_ms: config.request_timeout_ms,
+            default_model: config.default_model,
+            api_version: config.api_version,
+            created_at: config.created_at,
+            updated_at: config.updated_at,
+            last_test: config.last_test,
+            unknown_fields: config.unknown_fields,
+        })))
+    }
+}
+
+impl ProviderConfig {
+    /// Configuration with a single API key
+    pub fn from_api_key(api_key: String, base_url: Option<String>) -> Self {
+        ProviderConfig {
+            credentials: vec![ProviderCredential {
+                label: DEFAULT_CREDENTIAL_LABEL.to_string(),
+                api_key,
+                active: true,
+                metadata: CredentialMetadata::default(),
+            }],
+            base_url,
+            ..Default::default()
+        }
+    }
+
+    /// Key of the first active credential
+    pub fn api_key(&self) -> Option<&str> {
+        self.credentials
+            .iter()
+            .find(|credential| credential.active)
+            .map(|credential| credential.api_key.as_str())
+    }
+
+    /// Replace the key of the first active credential, keeping the others as fallbacks
+    pub fn set_primary_api_key(&mut self, api_key: String) {
+        match self
+            .credentials
+            .iter_mut()
+            .find(|credential| credential.active)
+        {
+            Some(credential) => {
+                if credential.api_key != api_key {
+                    // The old key's expiry does not apply to its replacement
+                    credential.metadata.expires_at = None;
+                }
+                credential.api_key = api_key;
+            }
+            None => self.credentials.insert(
+                0,
+                ProviderCredential {
+                    label: self.unused_label(),
+                    api_key,
+                    active: true,
+                    metadata: CredentialMetadata::default(),
+                },
+            ),
+        }
+    }
+
+    /// Replace the credentials, keeping the stored key of every update without one.
+    ///
+    /// A new label without a key is kept with an empty key, which [`Self::validate`]
+    /// rejects.
+    pub fn apply_credential_updates(&mut self, updates: Vec<CredentialUpdate>) {
+        let credentials = updates
+            .into_iter()
+            .map(|update| {
+                let api_key = update
+                    .api_key
+                    .filter(|api_key| !api_key.trim().is_empty())
+                    .or_else(|| {
+                        self.credentials
+                            .iter()
+                            .find(|stored| stored.label == update.label)
+                            .map(|stored| stored.api_key.clone())
+                    })
+                    .unwrap_or_default();
+                ProviderCredential {
+                    label: update.label,
+                    api_key,
+                    active: update.active,
+                    metadata: update.metadata,
+                }
+            })
+            .collect();
+        self.credentials = credentials;
+    }
+
+    /// Check every field before it is saved and handed to the sidecar
+    pub fn validate(&self) -> Result<(), KeychainError> {
+        if !self.credentials.iter().any(|credential| credential.active) {
+            return Err(invalid(
+                "credentials",
+                "At least one API key must be active",
+            ));
+        }
+        for (index, credential) in self.credentials.iter().enumerate() {
+            if credential.label.trim().is_empty() {
+                return Err(invalid("credentials", "API key labels must not be empty"));
+            }
+            if credential.api_key.trim().is_empty() {
+                return Err(invalid(
+                    "credentials",
+                    format!("API key '{}' is empty", credential.label),
+                ));
+            }
+            if self.credentials[..index]
+                .iter()
+                .any(|other| other.label == credential.label)
+            {
+                return Err(invalid(
+                    "credentials",
+                    format!("Duplicate API key label '{}'", credential.label),
+                ));
+            }
+        }
+
+        if let Some(credential) = self.credentials.iter().find(|credential| {
+            credential
+                .metadata
+                .notes
+                .as_ref()
+                .is_some_and(|notes| notes.chars().count() > MAX_NOTES_LEN)
+        }) {
+            return Err(invalid(
+                "credentials",
+                format!(
+                    "Notes of API key '{}' are longer than {} characters",
+                    credential.label, MAX_NOTES_LEN
+                ),
+            ));
+        }
+
+        if let Some(base_url) = &self.base_url {
+            match reqwest::Url::parse(base_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => {
+                    return Err(invalid(
+                        "baseUrl",
+                        format!("'{}' is not an http(s) URL", base_url),
+                    ))
+                }
+            }
+        }
+
+        for (name, value) in &self.extra_headers {
+            if name.is_empty() || !name.bytes().all(is_header_name_byte) {
+                return Err(invalid(
+                    "extraHeaders",
+                    format!("'{}' is not a valid header name", name),
+                ));
+            }
+            if RESERVED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                return Err(invalid(
+                    "extraHeaders",
+                    format!(
+                        "Header '{}' is set from the API key and cannot be overridden",
+                        name
+                    ),
+                ));
+            }
+            if value.chars().any(|c| c.is_control() && c != '\t') {
+                return Err(invalid(
+                    "extraHeaders",
+                    format!("Header '{}' contains control characters", name),
+                ));
+            }
+        }
+
+        for (field, value) in [
+            ("organization", &self.organization),
+            ("project", &self.project),
+            ("defaultModel", &self.default_model),
+            ("apiVersion", &self.api_version),
+        ] {
+            if let Some(value) = value {
+                if value.trim().is_empty() || value.chars().any(char::is_control) {
+                    return Err(invalid(
+                        field,
+                        "Value must not be empty or contain control characters",
+                    ));
+                }
+            }
+        }
+
+        if let Some(timeout_ms) = self.request_timeout_ms {
+            if timeout_ms == 0 || timeout_ms > MAX_REQUEST_TIMEOUT_MS {
+                return Err(invalid(
+                    "requestTimeoutMs",
+                    format!(
+                        "Timeout must be between 1 and {} ms",
+                        MAX_REQUEST_TIMEOUT_MS
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Carry credential bookkeeping over from the stored entry and stamp what changed.
+    ///
+    /// Credentials are matched by label; one whose key changed is treated as new.
+    pub fn stamp_credentials(&mut self, stored: Option<&ProviderConfig>, now: u64) {
+        for credential in &mut self.credentials {
+            let previous = stored
+                .and_then(|stored| {
+                    stored
+                        .credentials
+                        .iter()
+                        .find(|previous| previous.label == credential.label)
+                })
+                .filter(|previous| previous.api_key == credential.api_key);
+            let metadata = &mut credential.metadata;
+
+            match previous {
+                Some(previous) => {
+                    let changed = previous.active != credential.active
+                        || previous.metadata.expires_at != metadata.expires_at
+                        || previous.metadata.notes != metadata.notes;
+                    metadata.created_at = previous.metadata.created_at.or(Some(now));
+                    metadata.updated_at = if changed {
+                        Some(now)
+                    } else {
+                        previous.metadata.updated_at.or(Some(now))
+                    };
+                    metadata.last_pushed_at = previous.metadata.last_pushed_at;
+                    metadata.last_validated_at = previous.metadata.last_validated_at;
+                }
+                None => {
+                    metadata.created_at = Some(now);
+                    metadata.updated_at = Some(now);
+                    metadata.last_pushed_at = None;
+                    metadata.last_validated_at = None;
+                }
+            }
+        }
+    }
+
+    fn unused_label(&self) -> String {
+        let is_used = |label: &str| {
+            self.credentials
+                .iter()
+                .any(|credential| credential.label == label)
+        };
+        if !is_used(DEFAULT_CREDENTIAL_LABEL) {
+            return DEFAULT_CREDENTIAL_LABEL.to_string();
+        }
+        (2..)
+            .map(|suffix| format!("{}-{}", DEFAULT_CREDENTIAL_LABEL, suffix))
+            .find(|label| !is_used(label))
+            .unwrap_or_default()
+    }
+
+    /// Hash of every setting that decides whether a connection test still applies
+    pub fn connection_fingerprint(&self) -> String {
+        let settings = serde_json::json!([
+            self.api_key(),
+            self.base_url,
+            self.extra_headers,
+            self.organization,
+            self.project,
+            self.api_version,
+        ]);
+        format!("{:x}", Sha256::digest(settings.to_string().as_bytes()))
+    }
+
+    /// Hash of the credentials handed to the sidecar, which tells a new push from a repeat
+    pub fn push_fingerprint(&self) -> String {
+        let credentials: Vec<_> = self
+            .credentials
+            .iter()
+            .map(|credential| (&credential.label, &credential.api_key, credential.active))
+            .collect();
+        let credentials = serde_json::json!(credentials);
+        format!("{:x}", Sha256::digest(credentials.to_string().as_bytes()))
+    }
+
+    /// Last connection test result, unless the settings changed since it was run
+    pub fn last_test(&self) -> Option<&ConnectionTestResult> {
+        self.last_test
+            .as_ref()
+            .filter(|cached| cached.fingerprint == self.connection_fingerprint())
+            .map(|cached| &cached.result)
+    }
+
+    /// Describe the entry without any secret, for display in the frontend
+    pub fn summary(&self, provider: &str) -> ProviderConfigSummary {
+        ProviderConfigSummary {
+            provider: provider.to_string(),
+            masked_api_key: self.api_key().map(mask_api_key),
+            credentials: self
+                .credentials
+                .iter()
+                .map(|credential| CredentialSummary {
+                    label: credential.label.clone(),
+                    masked_api_key: mask_api_key(&credential.api_key),
+                    active: credential.active,
+                    metadata: credential.metadata.clone(),
+                })
+                .collect(),
+            base_url: self.base_url.clone(),
+            // Header values may carry gateway tokens, so only the names are listed
+            extra_header_names: self.extra_headers.keys().cloned().collect(),
+            organization: self.organization.clone(),
+            project: self.project.clone(),
+            request_timeout_ms: self.request_timeout_ms,
+            default_model: self.default_model.clone(),
+            api_version: self.api_version.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            validation_error: self.validate().err(),
+            last_test: self.last_test().cloned(),
+        }
+    }
+}
+
+/// Provider configuration with every API key masked, returned to the frontend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderConfigSummary {
+    pub provider: String,
+    /// Masked key of the first active credential
+    pub masked_api_key: Option<String>,
+    pub credentials: Vec<CredentialSummary>,
+    pub base_url: Option<String>,
+    pub extra_header_names: Vec<String>,
+    pub organization: Option<String>,
+    pub project: Option<String>,
+    pub request_timeout_ms: Option<u64>,
+    pub default_model: Option<String>,
+    pub api_version: Option<String>,
+    pub created_at: Option<u64>,
+    pub updated_at: Option<u64>,
+    /// Why the stored entry would be rejected by [`ProviderConfig::validate`], if it would
+    pub validation_error: Option<KeychainError>,
+    pub last_test: Option<ConnectionTestResult>,
+}
+
+/// One credential of a [`ProviderConfigSummary`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialSummary {
+    pub label: String,
+    pub masked_api_key: String,
+    pub active: bool,
+    #[serde(flatten)]
+    pub metadata: CredentialMetadata,
+}
+
+/// Keep the vendor prefix (`sk-proj-`, `tvly-`, ...) and the last four characters.
+///
+/// Keys too short to hide at least eight characters are masked completely.
+pub fn mask_api_key(api_key: &str) -> String {
+    let chars: Vec<char> = api_key.chars().collect();
+    let prefix_len = chars
+        .iter()
+        .take(MASK_PREFIX_SEARCH_LEN)
+        .rposition(|c| matches!(c, '-' | '_'))
+        .map_or(3, |index| index + 1);
+
+    if chars.len() < prefix_len + MASK_MIN_HIDDEN_LEN + 4 {
+        return "****".to_string();
+    }
+
+    let prefix: String = chars[..prefix_len].iter().collect();
+    let suffix: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}...{}", prefix, suffix)
+}
+
+fn invalid(field: &str, message: impl Into<String>) -> KeychainError {
+    KeychainError::Invalid {
+        field: field.to_string(),
+        message: message.into(),
+    }
+}
+
+/// RFC 9110 token characters
+fn is_header_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credential(label: &str, api_key: &str, active: bool) -> ProviderCredential {
+        ProviderCredential {
+            label: label.to_string(),
+            api_key: api_key.to_string(),
+            active,
+            metadata: CredentialMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn migrates_single_key_entries() {
+        let config: ProviderConfig =
+            serde_json::from_str(r#"{"apiKey":"legacy","baseUrl":"https://api.test.com"}"#)
+                .unwrap();
+
+        assert_eq!(
+            config.credentials,
+            vec![credential("default", "legacy", true)]
+        );
+        assert_eq!(config.base_url.as_deref(), Some("https://api.test.com"));
+    }
+
+    #[test]
+    fn writes_primary_key_alongside_credentials() {
+        let config = ProviderConfig {
+            credentials: vec![
+                credential("personal", "personal-key", false),
+                credential("org", "org-key", true),
+            ],
+            ..Default::default()
+        };
+
+        let value = serde_json::to_value(&config).unwrap();
+        assert_eq!(value["apiKey"], "org-key");
+        assert_eq!(value["credentials"][0]["label"], "personal");
+        assert_eq!(
+            serde_json::from_value::<ProviderConfig>(value).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn replaces_only_the_primary_key() {
+        let mut config = ProviderConfig {
+            credentials: vec![
+                credential("org", "org-key", true),
+                credential("backup", "backup-key", true),
+            ],
+            ..Default::default()
+        };
+
+        config.set_primary_api_key("rotated-key".to_string());
+        assert_eq!(config.api_key(), Some("rotated-key"));
+        assert_eq!(config.credentials[1].api_key, "backup-key");
+    }
+
+    #[test]
+    fn rejects_unusable_credentials() {
+        let mut config = ProviderConfig::from_api_key("key".to_string(), None);
+        assert!(config.validate().is_ok());
+
+        config
+            .credentials
+            .push(credential("default", "other", true));
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("Duplicate"));
+
+        config.credentials = vec![credential("off", "key", false)];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn writes_versioned_schema_and_preserves_unknown_fields() {
+        let raw = r#"{
+            "schema": "v2",
+            "credentials": [{"label": "org", "apiKey": "org-key", "active": true}],
+            "extraHeaders": {"X-Gateway": "team-a"},
+            "organization": "org-123",
+            "requestTimeoutMs": 120000,
+            "futureSetting": {"enabled": true}
+        }"#;
+        let config: ProviderConfig = serde_json::from_str(raw).unwrap();
+        assert_eq!(config.extra_headers["X-Gateway"], "team-a");
+        assert_eq!(config.organization.as_deref(), Some("org-123"));
+        assert_eq!(config.request_timeout_ms, Some(120_000));
+
+        let value = serde_json::to_value(&config).unwrap();
+        assert_eq!(value["schema"], "v2");
+        assert_eq!(value["apiKey"], "org-key");
+        assert_eq!(value["futureSetting"]["enabled"], true);
+    }
+
+    #[test]
+    fn reads_newer_schema_best_effort() {
+        let config: ProviderConfig = serde_json::from_str(
+            r#"{"schema": "v9", "apiKey": "key", "baseUrl": "https://api.test.com", "newField": 1}"#,
+        )
+        .unwrap();
+        assert_eq!(config.api_key(), Some("key"));
+        assert_eq!(config.unknown_fields["newField"], 1);
+
+        let value = serde_json::to_value(&config).unwrap();
+        assert_eq!(value["schema"], "v2");
+        assert_eq!(value["newField"], 1);
+    }
+
+    #[test]
+    fn reports_v2_entries_that_do_not_parse() {
+        let error = serde_json::from_str::<ProviderConfig>(
+            r#"{"schema": "v2", "apiKey": "key", "requestTimeoutMs": "two minutes"}"#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("invalid type"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_invalid_optional_fields() {
+        let valid = ProviderConfig::from_api_key("key".to_string(), None);
+        let field_of = |config: ProviderConfig| match config.validate() {
+            Err(KeychainError::Invalid { field, .. }) => field,
+            other => panic!("expected invalid field, got {:?}", other),
+        };
+
+        let mut config = valid.clone();
+        config.base_url = Some("ftp://example.com".to_string());
+        assert_eq!(field_of(config), "baseUrl");
+
+        let mut config = valid.clone();
+        config
+            .extra_headers
+            .insert("Authorization".to_string(), "Bearer other".to_string());
+        assert_eq!(field_of(config), "extraHeaders");
+
+        let mut config = valid.clone();
+        config
+            .extra_headers
+            .insert("X-Bad".to_string(), "line\r\nbreak".to_string());
+        assert_eq!(field_of(config), "extraHeaders");
+
+        let mut config = valid.clone();
+        config.request_timeout_ms = Some(0);
+        assert_eq!(field_of(config), "requestTimeoutMs");
+
+        let mut config = valid.clone();
+        config.organization = Some("  ".to_string());
+        assert_eq!(field_of(config), "organization");
+
+        let mut config = valid;
+        config
+            .extra_headers
+            .insert("X-Gateway".to_string(), "team-a".to_string());
+        config.request_timeout_ms = Some(60_000);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn stamps_credential_metadata_by_label() {
+        let mut stored = ProviderConfig {
+            credentials: vec![
+                credential("main", "key-1", true),
+                credential("backup", "key-2", true),
+            ],
+            ..Default::default()
+        };
+        stored.stamp_credentials(None, 100);
+        stored.credentials[0].metadata.last_pushed_at = Some(150);
+        assert_eq!(stored.credentials[1].metadata.created_at, Some(100));
+
+        // The frontend sends credentials without the host-managed timestamps
+        let mut update = ProviderConfig {
+            credentials: vec![
+                credential("main", "key-1", true),
+                credential("backup", "key-3", true),
+            ],
+            ..Default::default()
+        };
+        update.credentials[0].metadata.notes = Some("billing account".to_string());
+        update.stamp_credentials(Some(&stored), 200);
+
+        let main = &update.credentials[0].metadata;
+        assert_eq!(main.created_at, Some(100));
+        assert_eq!(main.updated_at, Some(200));
+        assert_eq!(main.last_pushed_at, Some(150));
+        // A new key under an old label starts over
+        let backup = &update.credentials[1].metadata;
+        assert_eq!(backup.created_at, Some(200));
+        assert_eq!(backup.last_pushed_at, None);
+
+        update.credentials[0].metadata.notes = Some("x".repeat(MAX_NOTES_LEN + 1));
+        assert!(update.validate().is_err());
+    }
+
+    #[test]
+    fn masks_keys_keeping_prefix_and_last_four() {
+        assert_eq!(
+            mask_api_key("sk-proj-abcdefghijklmnop1234"),
+            "sk-proj-...1234"
+        );
+        assert_eq!(mask_api_key("tvly-abcdefghijkl5678"), "tvly-...5678");
+        assert_eq!(mask_api_key("AIzaSyabcdefghij9012"), "AIz...9012");
+        assert_eq!(mask_api_key("sk-short1234"), "****");
+        assert_eq!(mask_api_key(""), "****");
+    }
+
+    #[test]
+    fn summary_contains_no_secrets() {
+        let mut config = ProviderConfig::from_api_key(
+            "sk-proj-abcdefghijklmnop1234".to_string(),
+            Some("https://api.test.com".to_string()),
+        );
+        config
+            .extra_headers
+            .insert("X-Gateway-Token".to_string(), "gateway-secret".to_string());
+        config.created_at = Some(1_700_000_000_000);
+
+        let summary = config.summary("openai");
+        assert_eq!(summary.masked_api_key.as_deref(), Some("sk-proj-...1234"));
+        assert_eq!(summary.extra_header_names, vec!["X-Gateway-Token"]);
+        assert_eq!(summary.created_at, Some(1_700_000_000_000));
+        assert!(summary.validation_error.is_none());
+
+        let serialized = serde_json::to_string(&summary).unwrap();
+        assert!(!serialized.contains("abcdefghijklmnop"));
+        assert!(!serialized.contains("gateway-secret"));
+
+        config.base_url = Some("not a url".to_string());
+        assert!(matches!(
+            config.summary("openai").validation_error,
+            Some(KeychainError::Invalid { field, .. }) if field == "baseUrl"
+        ));
+    }
+}
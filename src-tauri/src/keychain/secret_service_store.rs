@@ -0,0 +1,278 @@
+use log::{debug, info, warn};
+use secret_service::blocking::{Collection, Item, SecretService};
+use secret_service::EncryptionType;
+use std::collections::HashMap;
+
+use super::{KeychainError, ProviderConfig, SecretStore, SecretStoreBackend, UpsertConfig};
+
+const ATTRIBUTE_APPLICATION: &str = "application";
+const ATTRIBUTE_PROVIDER: &str = "provider";
+const APPLICATION_ID: &str = "mind-flayer";
+const SECRET_CONTENT_TYPE: &str = "application/json";
+
+/// Secret store backed by the freedesktop Secret Service (gnome-keyring, KWallet, KeePassXC, ...)
+///
+/// Each provider is stored as one item in the default collection, tagged with
+/// `application=mind-flayer` and `provider=<name>` attributes.
+pub struct SecretServiceStore;
+
+impl SecretServiceStore {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Check whether a Secret Service provider is reachable on the session bus
+    pub fn is_available() -> bool {
+        run_isolated(|| connect().map(|_| ())).is_ok()
+    }
+
+    /// Remove every item owned by this application, used after migrating to another backend
+    pub fn clear(&self) -> Result<(), String> {
+        run_isolated(|| {
+            let service = connect()?;
+            let collection = open_collection(&service)?;
+            let items = find_items(&collection, None)?;
+
+            for item in items {
+                item.delete()
+                    .map_err(|e| format!("Failed to delete secret item: {}", e))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl SecretStore for SecretServiceStore {
+    fn backend(&self) -> SecretStoreBackend {
+        SecretStoreBackend::SecretService
+    }
+
+    fn load_all(&self) -> Result<HashMap<String, ProviderConfig>, KeychainError> {
+        run_isolated(|| {
+            let service = connect()?;
+            let collection = open_collection(&service)?;
+            let items = find_items(&collection, None)?;
+
+            let mut configs = HashMap::new();
+            for item in items {
+                let attributes = item
+                    .get_attributes()
+                    .map_err(|e| format!("Failed to read secret item attributes: {}", e))?;
+                let Some(provider) = attributes.get(ATTRIBUTE_PROVIDER).cloned() else {
+                    continue;
+                };
+
+                let secret = item
+                    .get_secret()
+                    .map_err(|e| format!("Failed to read secret for '{}': {}", provider, e))?;
+                match serde_json::from_slice::<ProviderConfig>(&secret) {
+                    Ok(config) => {
+                        configs.insert(provider, config);
+                    }
+                    Err(e) => warn!(
+                        "[SecretService] Skipping unreadable secret for '{}': {}",
+                        provider, e
+                    ),
+                }
+            }
+
+            debug!("[SecretService] Loaded {} provider secrets", configs.len());
+            Ok(configs)
+        })
+    }
+
+    fn save(&self, provider: &str, config: &ProviderConfig) -> Result<(), KeychainError> {
+        let secret = serde_json::to_vec(config)
+            .map_err(|e| format!("Failed to serialize provider config: {}", e))?;
+
+        run_isolated(|| {
+            let service = connect()?;
+            let collection = open_collection(&service)?;
+            collection
+                .create_item(
+                    &format!("Mind Flayer provider: {}", provider),
+                    HashMap::from([
+                        (ATTRIBUTE_APPLICATION, APPLICATION_ID),
+                        (ATTRIBUTE_PROVIDER, provider),
+                    ]),
+                    &secret,
+                    true,
+                    SECRET_CONTENT_TYPE,
+                )
+                .map_err(|e| format!("Failed to store secret for '{}': {}", provider, e))?;
+
+            info!("[SecretService] Stored secret for provider: {}", provider);
+            Ok(())
+        })
+    }
+
+    fn delete(&self, provider: &str) -> Result<(), KeychainError> {
+        run_isolated(|| {
+            let service = connect()?;
+            let collection = open_collection(&service)?;
+            let items = find_items(&collection, Some(provider))?;
+
+            for item in items {
+                item.delete()
+                    .map_err(|e| format!("Failed to delete secret for '{}': {}", provider, e))?;
+            }
+
+            info!("[SecretService] Deleted secret for provider: {}", provider);
+            Ok(())
+        })
+    }
+
+    /// Read and rewrite only the item of `provider`, so a change to another provider made
+    /// in between is never overwritten
+    fn update(
+        &self,
+        provider: &str,
+        update: &mut (dyn FnMut(&mut ProviderConfig) -> bool + Send),
+    ) -> Result<(), KeychainError> {
+        run_isolated(|| {
+            let service = connect()?;
+            let collection = open_collection(&service)?;
+            let items = find_items(&collection, Some(provider))?;
+            let Some(item) = items.first() else {
+                return Ok(());
+            };
+
+            let secret = item
+                .get_secret()
+                .map_err(|e| format!("Failed to read secret for '{}': {}", provider, e))?;
+            let mut config = serde_json::from_slice::<ProviderConfig>(&secret)
+                .map_err(|e| format!("Failed to parse secret for '{}': {}", provider, e))?;
+            if !update(&mut config) {
+                return Ok(());
+            }
+
+            let secret = serde_json::to_vec(&config)
+                .map_err(|e| format!("Failed to serialize provider config: {}", e))?;
+            item.set_secret(&secret, SECRET_CONTENT_TYPE)
+                .map_err(|e| format!("Failed to store secret for '{}': {}", provider, e))?;
+
+            debug!("[SecretService] Updated secret for provider: {}", provider);
+            Ok(())
+        })
+    }
+
+    /// Like [`Self::update`], rewrite only the item of `provider`, creating it if needed
+    fn upsert(&self, provider: &str, upsert: &mut UpsertConfig<'_>) -> Result<(), KeychainError> {
+        run_isolated(|| {
+            let service = connect()?;
+            let collection = open_collection(&service)?;
+            let items = find_items(&collection, Some(provider))?;
+
+            let stored = match items.first() {
+                Some(item) => {
+                    let secret = item
+                        .get_secret()
+                        .map_err(|e| format!("Failed to read secret for '{}': {}", provider, e))?;
+                    let config = serde_json::from_slice::<ProviderConfig>(&secret)
+                        .map_err(|e| format!("Failed to parse secret for '{}': {}", provider, e))?;
+                    Some(config)
+                }
+                None => None,
+            };
+            let Some(config) = upsert(stored.as_ref())? else {
+                return Ok(());
+            };
+            let secret = serde_json::to_vec(&config)
+                .map_err(|e| format!("Failed to serialize provider config: {}", e))?;
+
+            match items.first() {
+                Some(item) => item.set_secret(&secret, SECRET_CONTENT_TYPE),
+                None => collection
+                    .create_item(
+                        &format!("Mind Flayer provider: {}", provider),
+                        HashMap::from([
+                            (ATTRIBUTE_APPLICATION, APPLICATION_ID),
+                            (ATTRIBUTE_PROVIDER, provider),
+                        ]),
+                        &secret,
+                        true,
+                        SECRET_CONTENT_TYPE,
+                    )
+                    .map(|_| ()),
+            }
+            .map_err(|e| format!("Failed to store secret for '{}': {}", provider, e))?;
+
+            info!("[SecretService] Stored secret for provider: {}", provider);
+            Ok(())
+        })
+    }
+}
+
+fn connect<'a>() -> Result<SecretService<'a>, String> {
+    SecretService::connect(EncryptionType::Dh)
+        .map_err(|e| format!("Failed to connect to Secret Service: {}", e))
+}
+
+/// Open the default collection, failing while it is locked
+fn open_collection<'a>(service: &'a SecretService<'a>) -> Result<Collection<'a>, String> {
+    let collection = service
+        .get_default_collection()
+        .map_err(|e| format!("Failed to open default secret collection: {}", e))?;
+    collection
+        .ensure_unlocked()
+        .map_err(|e| format!("Failed to unlock secret collection: {}", e))?;
+    Ok(collection)
+}
+
+/// Find the items of this application, or only the one of `provider`
+fn find_items<'a>(
+    collection: &'a Collection<'a>,
+    provider: Option<&str>,
+) -> Result<Vec<Item<'a>>, String> {
+    let mut attributes = HashMap::from([(ATTRIBUTE_APPLICATION, APPLICATION_ID)]);
+    if let Some(provider) = provider {
+        attributes.insert(ATTRIBUTE_PROVIDER, provider);
+    }
+    collection
+        .search_items(attributes)
+        .map_err(|e| format!("Failed to search secret items: {}", e))
+}
+
+/// Run a blocking Secret Service call on its own thread.
+///
+/// The blocking zbus API drives its own tokio runtime, which panics when entered from a
+/// thread that is already inside the Tauri async runtime.
+fn run_isolated<T, E, F>(operation: F) -> Result<T, E>
+where
+    T: Send,
+    E: Send + From<String>,
+    F: FnOnce() -> Result<T, E> + Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(operation)
+            .join()
+            .map_err(|_| E::from("Secret Service worker thread panicked".to_string()))?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Requires a running Secret Service provider, e.g.:
+    // `dbus-run-session -- sh -c 'echo -n test | gnome-keyring-daemon --unlock && cargo test -- --ignored'`
+    #[test]
+    #[ignore]
+    fn round_trips_provider_config_through_secret_service() {
+        let store = SecretServiceStore::new();
+        let provider = format!("test_provider_{}", std::process::id());
+        let config = ProviderConfig::from_api_key(
+            "secret_service_key".to_string(),
+            Some("https://api.test.com".to_string()),
+        );
+
+        store.save(&provider, &config).unwrap();
+        let retrieved = store.load_all().unwrap().remove(&provider).unwrap();
+        assert_eq!(retrieved.api_key(), config.api_key());
+        assert_eq!(retrieved.base_url, config.base_url);
+
+        store.delete(&provider).unwrap();
+        assert!(!store.load_all().unwrap().contains_key(&provider));
+    }
+}
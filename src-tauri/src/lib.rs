@@ -1,5 +1,6 @@
 mod app_support;
 mod keychain;
+mod redact;
 mod setup;
 mod shortcuts;
 
@@ -9,72 +10,577 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
-/// Save provider configuration to system keychain
+/// Run a `ConfigStore` call on the blocking thread pool. Every call can wait on the config
+/// file lock held by another instance, decrypt the file, or block on a Secret Service unlock
+/// prompt, and calls that check a passphrase derive an Argon2id key. On the main thread or an
+/// async worker that would freeze the UI or stall other tasks.
+pub(crate) async fn with_store_blocking<T, F>(
+    app: &tauri::AppHandle,
+    f: F,
+) -> Result<T, keychain::KeychainError>
+where
+    T: Send + 'static,
+    F: FnOnce(&keychain::ConfigStore) -> Result<T, keychain::KeychainError> + Send + 'static,
+{
+    use tauri::Manager;
+
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || f(&app.state::<keychain::ConfigStore>()))
+        .await
+        .map_err(|e| keychain::KeychainError::from(format!("Keychain task failed: {}", e)))?
+}
+
+/// Save provider configuration to system keychain.
+///
+/// Without an API key only the base URL of the stored entry is updated, so the frontend
+/// never needs the plaintext key just to save other settings.
 #[tauri::command]
-fn save_provider_config(
+async fn save_provider_config(
     app: tauri::AppHandle,
     provider: String,
-    api_key: String,
+    api_key: Option<String>,
     base_url: Option<String>,
-) -> Result<(), String> {
+) -> Result<(), keychain::KeychainError> {
     log::info!(
         "[Command] save_provider_config called for provider: {}",
         provider
     );
     log::debug!(
-        "[Command] API key length: {}, base_url: {:?}",
-        api_key.len(),
+        "[Command] API key length: {:?}, base_url: {:?}",
+        api_key.as_ref().map(String::len),
         base_url
     );
 
-    let config = keychain::ProviderConfig { api_key, base_url };
-
-    log::info!("[Command] Calling keychain::save_config...");
-    keychain::save_config(&provider, &config)?;
+    with_store_blocking(&app, move |store| {
+        match api_key.filter(|api_key| !api_key.is_empty()) {
+            Some(api_key) => {
+                log::info!("[Command] Calling keychain::save_api_key...");
+                store.save_api_key("save_provider_config", &provider, api_key, base_url)
+            }
+            None => {
+                log::info!("[Command] Calling keychain::save_base_url...");
+                store.save_base_url("save_provider_config", &provider, base_url)
+            }
+        }
+    })
+    .await?;
     log::info!("[Command] Config saved to keychain successfully");
 
     // Push updated configuration to sidecar via stdin
     log::info!("[Command] Pushing config to sidecar...");
-    setup::push_config_to_sidecar(&app)?;
+    setup::push_config_to_sidecar(&app, "save_provider_config").await?;
     log::info!("[Command] Config pushed to sidecar successfully");
 
     Ok(())
 }
 
+/// Replace all labelled API keys of a provider, in failover order; omitted keys are kept
+#[tauri::command]
+async fn save_provider_credentials(
+    app: tauri::AppHandle,
+    provider: String,
+    credentials: Vec<keychain::CredentialUpdate>,
+    base_url: Option<String>,
+) -> Result<(), keychain::KeychainError> {
+    log::info!(
+        "[Command] save_provider_credentials called for provider: {} ({} keys)",
+        provider,
+        credentials.len()
+    );
+
+    with_store_blocking(&app, move |store| {
+        store.save_credentials(
+            "save_provider_credentials",
+            &provider,
+            credentials,
+            base_url,
+        )
+    })
+    .await?;
+
+    setup::push_config_to_sidecar(&app, "save_provider_credentials").await?;
+
+    Ok(())
+}
+
+/// Replace the full configuration of a provider, including headers, IDs and timeouts
+#[tauri::command]
+async fn update_provider_config(
+    app: tauri::AppHandle,
+    provider: String,
+    config: keychain::ProviderConfigUpdate,
+) -> Result<(), keychain::KeychainError> {
+    log::info!(
+        "[Command] update_provider_config called for provider: {}",
+        provider
+    );
+    with_store_blocking(&app, move |store| {
+        store.update_config("update_provider_config", &provider, config)
+    })
+    .await?;
+
+    setup::push_config_to_sidecar(&app, "update_provider_config").await?;
+
+    Ok(())
+}
+
 /// Delete provider configuration from system keychain
 #[tauri::command]
-fn delete_provider_config(app: tauri::AppHandle, provider: String) -> Result<(), String> {
-    keychain::delete_config(&provider)?;
+async fn delete_provider_config(
+    app: tauri::AppHandle,
+    provider: String,
+) -> Result<(), keychain::KeychainError> {
+    with_store_blocking(&app, move |store| {
+        store.delete_config("delete_provider_config", &provider)
+    })
+    .await?;
 
     // Push updated configuration to sidecar via stdin
-    setup::push_config_to_sidecar(&app)?;
+    setup::push_config_to_sidecar(&app, "delete_provider_config").await?;
 
     Ok(())
 }
 
-/// Get provider configuration from system keychain
+/// Get provider configuration with masked API keys, or `None` if it is not configured
 #[tauri::command]
-fn get_provider_config(provider: String) -> Result<keychain::ProviderConfig, String> {
+async fn get_provider_config_summary(
+    app: tauri::AppHandle,
+    provider: String,
+) -> Result<Option<keychain::ProviderConfigSummary>, keychain::KeychainError> {
     log::info!(
-        "[Command] get_provider_config called for provider: {}",
+        "[Command] get_provider_config_summary called for provider: {}",
         provider
     );
-    keychain::get_config(&provider)
+    with_store_blocking(&app, move |store| store.get_config_summary(&provider)).await
+}
+
+/// Return one plaintext API key, re-confirming the master passphrase if one is set
+#[tauri::command]
+async fn reveal_provider_api_key(
+    app: tauri::AppHandle,
+    provider: String,
+    label: Option<String>,
+    passphrase: Option<String>,
+) -> Result<String, keychain::KeychainError> {
+    log::info!(
+        "[Command] reveal_provider_api_key called for provider: {}",
+        provider
+    );
+    with_store_blocking(&app, move |store| {
+        store.reveal_api_key(
+            "reveal_provider_api_key",
+            &provider,
+            label.as_deref(),
+            passphrase.as_deref(),
+        )
+    })
+    .await
+}
+
+/// Check a provider config against the provider's API, defaulting to the stored config
+#[tauri::command]
+async fn test_provider_config(
+    app: tauri::AppHandle,
+    provider: String,
+    config: Option<keychain::ProviderConfig>,
+) -> Result<keychain::ConnectionTestResult, keychain::KeychainError> {
+    log::info!(
+        "[Command] test_provider_config called for provider: {}",
+        provider
+    );
+    let config = match config {
+        Some(config) => config,
+        None => {
+            let provider = provider.clone();
+            with_store_blocking(&app, move |store| store.get_config(&provider)).await?
+        }
+    };
+    let proxy_url = setup::load_sidecar_proxy_url(&app);
+    let result = keychain::test_config(&provider, &config, proxy_url.as_deref()).await?;
+
+    with_store_blocking(&app, move |store| {
+        store.record_test_result(&provider, &config, &result);
+        Ok(result)
+    })
+    .await
+}
+
+/// Find provider keys in the environment and an optional `.env` file, with masked values
+#[tauri::command]
+async fn discover_env_provider_keys(
+    app: tauri::AppHandle,
+    env_file: Option<String>,
+) -> Result<Vec<keychain::EnvKeyPreview>, keychain::KeychainError> {
+    log::info!("[Command] discover_env_provider_keys called");
+    with_store_blocking(&app, move |store| {
+        store.discover_env_keys(env_file.as_deref().map(std::path::Path::new))
+    })
+    .await
+}
+
+/// Import the selected discovered keys into the keychain store
+#[tauri::command]
+async fn import_env_provider_keys(
+    app: tauri::AppHandle,
+    env_file: Option<String>,
+    selections: Vec<keychain::EnvImportSelection>,
+) -> Result<Vec<String>, keychain::KeychainError> {
+    log::info!(
+        "[Command] import_env_provider_keys called for {} providers",
+        selections.len()
+    );
+    let imported = with_store_blocking(&app, move |store| {
+        store.import_env_keys(
+            "import_env_provider_keys",
+            env_file.as_deref().map(std::path::Path::new),
+            &selections,
+        )
+    })
+    .await?;
+
+    // Push updated configuration to sidecar via stdin
+    setup::push_config_to_sidecar(&app, "import_env_provider_keys").await?;
+
+    Ok(imported)
+}
+
+/// Export provider configs to a passphrase-encrypted bundle that works on any machine
+#[tauri::command]
+async fn export_provider_configs(
+    app: tauri::AppHandle,
+    passphrase: String,
+    path: String,
+    providers: Option<Vec<String>>,
+) -> Result<Vec<String>, keychain::KeychainError> {
+    log::info!("[Command] export_provider_configs called");
+    with_store_blocking(&app, move |store| {
+        store.export_configs(
+            "export_provider_configs",
+            std::path::Path::new(&path),
+            &passphrase,
+            providers.as_deref(),
+        )
+    })
+    .await
+}
+
+/// Import provider configs from an export bundle, or preview the import with `dry_run`
+#[tauri::command]
+async fn import_provider_configs(
+    app: tauri::AppHandle,
+    passphrase: String,
+    path: String,
+    merge_strategy: keychain::MergeStrategy,
+    providers: Option<Vec<String>>,
+    dry_run: Option<bool>,
+) -> Result<keychain::ImportReport, keychain::KeychainError> {
+    log::info!(
+        "[Command] import_provider_configs called ({:?}, dry run: {:?})",
+        merge_strategy,
+        dry_run
+    );
+    let report = with_store_blocking(&app, move |store| {
+        store.import_configs(
+            "import_provider_configs",
+            std::path::Path::new(&path),
+            &passphrase,
+            merge_strategy,
+            providers.as_deref(),
+            dry_run.unwrap_or(false),
+        )
+    })
+    .await?;
+
+    if !report.dry_run {
+        // Push updated configuration to sidecar via stdin
+        setup::push_config_to_sidecar(&app, "import_provider_configs").await?;
+    }
+
+    Ok(report)
 }
 
 /// List all configured providers
 #[tauri::command]
-fn list_all_providers() -> Vec<String> {
-    keychain::list_all_providers()
+async fn list_all_providers(app: tauri::AppHandle) -> Result<Vec<String>, keychain::KeychainError> {
+    with_store_blocking(&app, |store| store.list_all_providers()).await
+}
+
+/// List every stored provider with its masked credentials and their metadata
+#[tauri::command]
+async fn list_provider_config_summaries(
+    app: tauri::AppHandle,
+) -> Result<Vec<keychain::ProviderConfigSummary>, keychain::KeychainError> {
+    with_store_blocking(&app, |store| store.list_config_summaries()).await
+}
+
+/// Credentials expiring within a week, or already expired, soonest first. Changes are also
+/// sent as `provider-credentials-expiring`
+#[tauri::command]
+async fn get_expiring_credentials(
+    app: tauri::AppHandle,
+) -> Result<Vec<keychain::ExpiringCredential>, keychain::KeychainError> {
+    with_store_blocking(&app, setup::expiring_credentials).await
+}
+
+/// List provider config files that were moved aside because they could not be read
+#[tauri::command]
+async fn list_quarantined_provider_configs(
+    app: tauri::AppHandle,
+) -> Result<Vec<keychain::QuarantinedConfig>, keychain::KeychainError> {
+    with_store_blocking(&app, |store| store.list_quarantined_configs()).await
+}
+
+/// Retry a quarantined provider config file, optionally with the machine's previous name
+#[tauri::command]
+async fn recover_provider_configs(
+    app: tauri::AppHandle,
+    file_name: Option<String>,
+    passphrase: Option<String>,
+) -> Result<Vec<String>, keychain::KeychainError> {
+    log::info!(
+        "[Command] recover_provider_configs called for file: {:?}",
+        file_name
+    );
+    let restored = with_store_blocking(&app, move |store| {
+        store.recover_configs(
+            "recover_provider_configs",
+            file_name.as_deref(),
+            passphrase.as_deref(),
+        )
+    })
+    .await?;
+
+    setup::push_config_to_sidecar(&app, "recover_provider_configs").await?;
+
+    Ok(restored)
+}
+
+/// List audit log entries matching the filter, oldest first
+#[tauri::command]
+async fn list_audit_events(
+    app: tauri::AppHandle,
+    filter: Option<keychain::AuditFilter>,
+) -> Result<Vec<keychain::AuditEvent>, keychain::KeychainError> {
+    with_store_blocking(&app, move |store| {
+        store.list_audit_events(&filter.unwrap_or_default())
+    })
+    .await
+}
+
+/// Check the audit log's hash chain for modified, removed or reordered entries
+#[tauri::command]
+async fn verify_audit_log(
+    app: tauri::AppHandle,
+) -> Result<keychain::AuditVerification, keychain::KeychainError> {
+    log::info!("[Command] verify_audit_log called");
+    with_store_blocking(&app, |store| store.verify_audit_log()).await
+}
+
+/// Report whether stored provider keys are protected by a master passphrase and locked
+#[tauri::command]
+async fn get_master_passphrase_status(
+    app: tauri::AppHandle,
+) -> Result<keychain::PassphraseStatus, keychain::KeychainError> {
+    with_store_blocking(&app, |store| store.get_passphrase_status()).await
+}
+
+/// Protect stored provider keys with a master passphrase
+#[tauri::command]
+async fn set_master_passphrase(
+    app: tauri::AppHandle,
+    passphrase: String,
+) -> Result<(), keychain::KeychainError> {
+    log::info!("[Command] set_master_passphrase called");
+    with_store_blocking(&app, move |store| {
+        store.set_master_passphrase("set_master_passphrase", &passphrase)
+    })
+    .await?;
+
+    setup::push_config_to_sidecar(&app, "set_master_passphrase").await?;
+
+    Ok(())
+}
+
+/// Re-encrypt stored provider keys under a new master passphrase
+#[tauri::command]
+async fn change_master_passphrase(
+    app: tauri::AppHandle,
+    current_passphrase: String,
+    new_passphrase: String,
+) -> Result<(), keychain::KeychainError> {
+    log::info!("[Command] change_master_passphrase called");
+    with_store_blocking(&app, move |store| {
+        store.change_master_passphrase(
+            "change_master_passphrase",
+            &current_passphrase,
+            &new_passphrase,
+        )
+    })
+    .await?;
+
+    setup::push_config_to_sidecar(&app, "change_master_passphrase").await?;
+
+    Ok(())
+}
+
+/// Remove the master passphrase and protect provider keys with the local key again
+#[tauri::command]
+async fn remove_master_passphrase(
+    app: tauri::AppHandle,
+    passphrase: String,
+) -> Result<(), keychain::KeychainError> {
+    log::info!("[Command] remove_master_passphrase called");
+    with_store_blocking(&app, move |store| {
+        store.remove_master_passphrase("remove_master_passphrase", &passphrase)
+    })
+    .await?;
+
+    setup::push_config_to_sidecar(&app, "remove_master_passphrase").await?;
+
+    Ok(())
 }
 
-/// Wait for sidecar startup and return the resolved runtime port
+/// Unlock stored provider keys and hand them to the sidecar
 #[tauri::command]
-async fn wait_for_sidecar_port(
+async fn unlock_provider_configs(
+    app: tauri::AppHandle,
+    passphrase: String,
+) -> Result<(), keychain::KeychainError> {
+    log::info!("[Command] unlock_provider_configs called");
+    with_store_blocking(&app, move |store| store.unlock(&passphrase)).await?;
+
+    setup::push_config_to_sidecar(&app, "unlock_provider_configs").await?;
+
+    Ok(())
+}
+
+/// Lock stored provider keys and withdraw them from the sidecar
+#[tauri::command]
+async fn lock_provider_configs(app: tauri::AppHandle) -> Result<(), keychain::KeychainError> {
+    log::info!("[Command] lock_provider_configs called");
+    with_store_blocking(&app, |store| store.lock()).await?;
+
+    setup::push_config_to_sidecar(&app, "lock_provider_configs").await?;
+
+    Ok(())
+}
+
+/// Report the active secret store backend and the backends available on this machine
+#[tauri::command]
+async fn get_secret_store_backend(
+    app: tauri::AppHandle,
+) -> Result<keychain::SecretStoreStatus, keychain::KeychainError> {
+    // Checking for Secret Service opens a D-Bus connection
+    with_store_blocking(&app, |store| Ok(store.get_backend_status())).await
+}
+
+/// Switch the secret store backend, refused while the active one still holds provider configs
+#[tauri::command]
+async fn set_secret_store_backend(
+    app: tauri::AppHandle,
+    backend: keychain::SecretStoreBackend,
+) -> Result<(), keychain::KeychainError> {
+    log::info!(
+        "[Command] set_secret_store_backend called with backend: {:?}",
+        backend
+    );
+    with_store_blocking(&app, move |store| {
+        store.set_backend("set_secret_store_backend", backend)
+    })
+    .await?;
+
+    // The new backend may already hold providers from before
+    setup::push_config_to_sidecar(&app, "set_secret_store_backend").await?;
+
+    Ok(())
+}
+
+/// Move all provider configs into another secret store backend and make it active
+#[tauri::command]
+async fn migrate_secret_store(
+    app: tauri::AppHandle,
+    backend: keychain::SecretStoreBackend,
+) -> Result<usize, keychain::KeychainError> {
+    log::info!(
+        "[Command] migrate_secret_store called with target backend: {:?}",
+        backend
+    );
+    let migrated = with_store_blocking(&app, move |store| {
+        store.migrate_backend("migrate_secret_store", backend)
+    })
+    .await?;
+
+    setup::push_config_to_sidecar(&app, "migrate_secret_store").await?;
+
+    Ok(migrated)
+}
+
+/// Wait for sidecar startup and return the origin of its API, `http://localhost:<port>` or
+/// `sidecar://localhost` when it listens on a Unix socket. Fails with the startup error as
+/// soon as the sidecar crashes or is stopped
+#[tauri::command]
+async fn wait_for_sidecar_origin(
     app: tauri::AppHandle,
     timeout_ms: Option<u64>,
-) -> Result<u16, String> {
-    setup::wait_for_sidecar_port(app, timeout_ms).await
+) -> Result<String, String> {
+    setup::wait_for_sidecar_origin(app, timeout_ms).await
+}
+
+/// Start the sidecar if it is not running and return the origin of its API
+#[tauri::command]
+async fn start_sidecar(app: tauri::AppHandle) -> Result<String, String> {
+    log::info!("[Command] start_sidecar called");
+    setup::start_sidecar(app).await
+}
+
+/// Stop the sidecar until it is started again, cancelling in-flight chat streams
+#[tauri::command]
+async fn stop_sidecar(app: tauri::AppHandle) -> Result<(), String> {
+    log::info!("[Command] stop_sidecar called");
+    setup::stop_sidecar(app).await
+}
+
+/// Restart the sidecar, e.g. to apply a changed proxy URL or transport, and return the origin
+/// of its API
+#[tauri::command]
+async fn restart_sidecar(app: tauri::AppHandle) -> Result<String, String> {
+    log::info!("[Command] restart_sidecar called");
+    setup::restart_sidecar(app).await
+}
+
+/// Current sidecar lifecycle state; transitions are also sent as `sidecar-status-changed`
+#[tauri::command]
+fn get_sidecar_status(app: tauri::AppHandle) -> setup::SidecarStatus {
+    setup::get_sidecar_status(&app)
+}
+
+/// Bearer token the webview sends with every sidecar request except the health check
+#[tauri::command]
+fn get_sidecar_api_token(app: tauri::AppHandle) -> String {
+    setup::get_sidecar_api_token(&app)
+}
+
+/// Send a webview `fetch()` to a sidecar listening on a Unix socket, streaming the response
+/// over `on_event`, and return the request ID used to abort it
+#[tauri::command]
+fn start_sidecar_socket_request(
+    app: tauri::AppHandle,
+    request: setup::SidecarSocketRequest,
+    on_event: tauri::ipc::Channel<tauri::ipc::InvokeResponseBody>,
+) -> Result<u64, String> {
+    setup::start_sidecar_socket_request(&app, request, on_event)
+}
+
+/// Cancel a socket request, closing its connection so the sidecar stops working on it
+#[tauri::command]
+fn abort_sidecar_socket_request(app: tauri::AppHandle, request_id: u64) {
+    setup::abort_sidecar_socket_request(&app, request_id)
+}
+
+/// Round trip time in milliseconds of a ping over the sidecar stdin and stdout
+#[tauri::command]
+async fn ping_sidecar(app: tauri::AppHandle) -> Result<u64, String> {
+    setup::ping_sidecar(&app).await
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -82,7 +588,10 @@ pub fn run() {
     // Initialize logger
     // In development: RUST_LOG=debug pnpm dev
     // In production: logs are suppressed unless RUST_LOG is set
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    // Every record goes through redaction, so keys never reach the log even at debug level
+    redact::init_logger(
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).build(),
+    );
 
     let builder = tauri::Builder::default()
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
@@ -90,6 +599,16 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_os::init())
         .setup(setup::init)
+        // Resources the webview loads by URL from a sidecar listening on a Unix socket
+        .register_asynchronous_uri_scheme_protocol(
+            setup::SIDECAR_URI_SCHEME,
+            |ctx, request, responder| {
+                let app = ctx.app_handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    responder.respond(setup::proxy_sidecar_request(&app, request).await);
+                });
+            },
+        )
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(
@@ -137,10 +656,41 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             greet,
             save_provider_config,
-            get_provider_config,
+            save_provider_credentials,
+            update_provider_config,
+            get_provider_config_summary,
+            reveal_provider_api_key,
+            test_provider_config,
+            discover_env_provider_keys,
+            import_env_provider_keys,
+            export_provider_configs,
+            import_provider_configs,
             delete_provider_config,
             list_all_providers,
-            wait_for_sidecar_port
+            list_provider_config_summaries,
+            get_expiring_credentials,
+            list_quarantined_provider_configs,
+            recover_provider_configs,
+            list_audit_events,
+            verify_audit_log,
+            get_secret_store_backend,
+            set_secret_store_backend,
+            migrate_secret_store,
+            get_master_passphrase_status,
+            set_master_passphrase,
+            change_master_passphrase,
+            remove_master_passphrase,
+            unlock_provider_configs,
+            lock_provider_configs,
+            wait_for_sidecar_origin,
+            get_sidecar_status,
+            get_sidecar_api_token,
+            ping_sidecar,
+            start_sidecar,
+            stop_sidecar,
+            restart_sidecar,
+            start_sidecar_socket_request,
+            abort_sidecar_socket_request
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
@@ -0,0 +1,206 @@
+use log::{Log, Metadata, Record};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+const REDACTED: &str = "[REDACTED]";
+/// Shorter secrets are not registered, replacing them would mangle ordinary words
+const MIN_SECRET_LEN: usize = 8;
+
+/// Configured keys seen in this session. Keys are never unregistered, so a deleted or
+/// rotated key stays hidden if the sidecar still echoes it.
+static KNOWN_SECRETS: RwLock<BTreeSet<String>> = RwLock::new(BTreeSet::new());
+
+/// `"apiKey": "..."`, `api_key=...`, `Authorization: Bearer ...` and similar fields
+static SECRET_FIELD_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)("?(?:api[_-]?key|x-api-key|authorization|access[_-]?token|startup[_-]?token|token|secret|password|passphrase)"?\s*[:=]\s*)("(?:[^"\\]|\\.)*"|(?:bearer\s+)?[^\s,;&}\]]+)"#,
+    )
+    .expect("secret field pattern is valid")
+});
+
+/// Bearer tokens and keys with a well-known vendor prefix
+static KEY_LIKE_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]{8,}|\b(?:(?:sk|pk|rk|xai|gsk|pplx|tvly|ghp|github_pat)[-_]|AIza)[A-Za-z0-9_-]{16,}",
+    )
+    .expect("key pattern is valid")
+});
+
+/// Remember secret values so they are redacted wherever they show up later
+pub fn register_secrets<'a>(secrets: impl IntoIterator<Item = &'a str>) {
+    let mut known = KNOWN_SECRETS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    for secret in secrets {
+        let secret = secret.trim();
+        if secret.len() >= MIN_SECRET_LEN {
+            known.insert(secret.to_string());
+        }
+    }
+}
+
+/// Replace registered secrets, secret-named fields and key-like tokens with `[REDACTED]`
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let mut redacted = Cow::Borrowed(text);
+    {
+        let known = KNOWN_SECRETS.read().unwrap_or_else(PoisonError::into_inner);
+        // Longer secrets first, in case one key contains another
+        let mut secrets: Vec<&String> = known
+            .iter()
+            .filter(|secret| redacted.contains(secret.as_str()))
+            .collect();
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        for secret in secrets {
+            redacted = Cow::Owned(redacted.replace(secret.as_str(), REDACTED));
+        }
+    }
+
+    let redacted = replace_all(redacted, &SECRET_FIELD_PATTERN, "${1}[REDACTED]");
+    replace_all(redacted, &KEY_LIKE_PATTERN, REDACTED)
+}
+
+fn replace_all<'a>(text: Cow<'a, str>, pattern: &Regex, replacement: &str) -> Cow<'a, str> {
+    let replaced = match pattern.replace_all(&text, replacement) {
+        Cow::Owned(replaced) => Some(replaced),
+        Cow::Borrowed(_) => None,
+    };
+    replaced.map_or(text, Cow::Owned)
+}
+
+/// Logger that redacts every record before handing it to the wrapped logger
+pub struct RedactingLogger<L> {
+    inner: L,
+}
+
+impl<L> RedactingLogger<L> {
+    pub fn new(inner: L) -> Self {
+        RedactingLogger { inner }
+    }
+}
+
+impl<L: Log> Log for RedactingLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = record.args().to_string();
+        self.inner.log(
+            &Record::builder()
+                .metadata(record.metadata().clone())
+                .args(format_args!("{}", redact(&message)))
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+/// Install `logger` as the global logger, behind redaction
+pub fn init_logger(logger: env_logger::Logger) {
+    let max_level = logger.filter();
+    match log::set_boxed_logger(Box::new(RedactingLogger::new(logger))) {
+        Ok(()) => log::set_max_level(max_level),
+        Err(e) => eprintln!("Failed to install logger: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct CapturingLogger {
+        lines: Mutex<Vec<String>>,
+    }
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.lines.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn redacts_registered_secrets_anywhere() {
+        register_secrets(["gateway-token-1f2e3d4c", "short"]);
+
+        assert_eq!(
+            redact("X-Gateway: gateway-token-1f2e3d4c sent"),
+            "X-Gateway: [REDACTED] sent"
+        );
+        // Too short to register
+        assert_eq!(redact("a short message"), "a short message");
+    }
+
+    #[test]
+    fn redacts_secret_fields() {
+        let redacted = redact(
+            r#"{"configs":{"openai":{"apiKey":"plain-value","baseUrl":"https://api.test.com"}}}"#,
+        );
+        assert!(!redacted.contains("plain-value"));
+        assert!(redacted.contains(r#""apiKey":[REDACTED]"#));
+        assert!(redacted.contains("https://api.test.com"));
+
+        assert_eq!(
+            redact("Authorization: Bearer abc.def.ghi"),
+            "Authorization: [REDACTED]"
+        );
+        assert_eq!(
+            redact("SIDECAR_STARTUP_TOKEN=1234abcd"),
+            "SIDECAR_STARTUP_TOKEN=[REDACTED]"
+        );
+        assert_eq!(redact("maxTokens: 1024"), "maxTokens: 1024");
+    }
+
+    #[test]
+    fn redacts_key_like_tokens() {
+        assert_eq!(
+            redact("request failed for sk-proj-abcdefghijklmnop1234, retrying"),
+            "request failed for [REDACTED], retrying"
+        );
+        assert_eq!(
+            redact("tavily key tvly-abcdefghijklmnopqrst"),
+            "tavily key [REDACTED]"
+        );
+        assert!(matches!(redact("no secrets here"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn logger_redacts_debug_records() {
+        register_secrets(["custom-provider-secret"]);
+        let logger = RedactingLogger::new(CapturingLogger::default());
+
+        logger.log(
+            &Record::builder()
+                .level(Level::Debug)
+                .args(format_args!(
+                    "Pushing config message: {}",
+                    r#"{"credentials":[{"label":"a","apiKey":"custom-provider-secret"}]}"#
+                ))
+                .build(),
+        );
+
+        let lines = logger.inner.lines.lock().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(!lines[0].contains("custom-provider-secret"));
+    }
+}
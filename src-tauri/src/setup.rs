@@ -1,9 +1,16 @@
-use log::{error, info};
-use tauri::{App, Manager};
+use log::{error, info, warn};
+use tauri::{App, Emitter, Manager};
+
+use crate::keychain::{ConfigStore, ExpiringCredential, KeychainError};
 
 mod sidecar;
 
-pub use sidecar::{cleanup_sidecar, push_config_to_sidecar, wait_for_sidecar_port};
+pub use sidecar::{
+    abort_sidecar_socket_request, cleanup_sidecar, get_sidecar_api_token, get_sidecar_status,
+    load_sidecar_proxy_url, ping_sidecar, proxy_sidecar_request, push_config_to_sidecar,
+    restart_sidecar, start_sidecar, start_sidecar_socket_request, stop_sidecar,
+    wait_for_sidecar_origin, SidecarSocketRequest, SidecarStatus, SIDECAR_URI_SCHEME,
+};
 
 #[cfg(target_os = "macos")]
 use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial};
@@ -11,6 +18,13 @@ use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial};
 #[cfg(target_os = "windows")]
 use window_vibrancy::apply_blur;
 
+/// Credentials expiring within this window are reported to the frontend
+const CREDENTIAL_EXPIRY_WARNING: std::time::Duration =
+    std::time::Duration::from_secs(7 * 24 * 60 * 60);
+/// How often stored credentials are checked for expiry
+const CREDENTIAL_EXPIRY_CHECK_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(60 * 60);
+
 /// setup window vibrancy effects and start sidecar
 pub fn init(app: &mut App) -> std::result::Result<(), Box<dyn std::error::Error>> {
     let window = app.get_webview_window("main").unwrap();
@@ -24,6 +38,7 @@ pub fn init(app: &mut App) -> std::result::Result<(), Box<dyn std::error::Error>
         .expect("Unsupported platform! 'apply_blur' is only supported on Windows");
 
     app.manage(sidecar::create_sidecar_state());
+    app.manage(ConfigStore::from_app_support_dir()?);
 
     // Register global shortcuts
     if let Err(e) = crate::shortcuts::register_global_shortcuts(&app.handle()) {
@@ -43,28 +58,50 @@ pub fn init(app: &mut App) -> std::result::Result<(), Box<dyn std::error::Error>
         }
     });
 
-    // Start sidecar service
-    let app_handle = app.handle().clone();
-    let app_handle_for_push = app.handle().clone();
+    // Start sidecar service and keep it running
+    sidecar::spawn_sidecar_supervisor(app.handle().clone());
+
+    // Warn about credentials close to expiry
+    let app_handle_for_expiry = app.handle().clone();
     tauri::async_runtime::spawn(async move {
-        match sidecar::start_sidecar(app_handle).await {
-            Ok(port) => {
-                info!("Sidecar started successfully on port {}", port);
-
-                // Push initial API keys configuration to sidecar via stdin
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                if let Err(e) = push_config_to_sidecar(&app_handle_for_push) {
-                    error!("Failed to push initial config to sidecar: {}", e);
-                }
-            }
-            Err(e) if sidecar::is_sidecar_shutdown_error(&e) => info!("{}", e),
-            Err(e) => error!("Failed to start sidecar: {}", e),
-        }
+        watch_credential_expiry(app_handle_for_expiry).await;
     });
 
     Ok(())
 }
 
+/// Credentials that expire within the warning window, soonest first. A locked store has
+/// nothing to report until it is unlocked.
+pub fn expiring_credentials(store: &ConfigStore) -> Result<Vec<ExpiringCredential>, KeychainError> {
+    if store.is_locked() {
+        return Ok(Vec::new());
+    }
+    store.expiring_credentials(CREDENTIAL_EXPIRY_WARNING.as_millis() as u64)
+}
+
+/// Emit `provider-credentials-expiring` whenever the set of expiring credentials changes.
+/// The first check runs before the webview listens, so the UI also loads the current set
+/// with `get_expiring_credentials`.
+async fn watch_credential_expiry(app: tauri::AppHandle) {
+    let mut reported: Vec<ExpiringCredential> = Vec::new();
+    loop {
+        match crate::with_store_blocking(&app, expiring_credentials).await {
+            Ok(expiring) if expiring != reported => {
+                if !expiring.is_empty() {
+                    info!("{} provider credential(s) expiring soon", expiring.len());
+                }
+                if let Err(e) = app.emit("provider-credentials-expiring", &expiring) {
+                    error!("Failed to emit credential expiry event: {}", e);
+                }
+                reported = expiring;
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to check provider credential expiry: {}", e),
+        }
+        tokio::time::sleep(CREDENTIAL_EXPIRY_CHECK_INTERVAL).await;
+    }
+}
+
 pub fn show_main_window(app: &tauri::AppHandle) -> Result<(), String> {
     let window = app
         .get_webview_window("main")
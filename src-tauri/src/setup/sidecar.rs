@@ -1,34 +1,73 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use base64::{engine::general_purpose, Engine as _};
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs,
     io::Write,
     net::TcpListener,
     path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, Mutex,
     },
     time::{SystemTime, UNIX_EPOCH},
 };
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 use tauri_plugin_shell::process::{CommandChild, CommandEvent, TerminatedPayload};
 use tauri_plugin_shell::ShellExt;
 
+mod protocol;
+#[cfg(unix)]
+mod unix_socket;
+
+use protocol::{HostMessage, SidecarMessage};
+
 /// Sidecar server port
 const PREFERRED_SIDECAR_PORT: u16 = 3737;
 const SIDECAR_START_MAX_ATTEMPTS: u8 = 3;
 const SIDECAR_HEALTH_CHECK_TIMEOUT_MS: u64 = 10_000;
-const SIDECAR_HEALTH_CHECK_INTERVAL_MS: u64 = 200;
-const SIDECAR_PORT_WAIT_TIMEOUT_MS: u64 = 15_000;
-const SIDECAR_PORT_WAIT_INTERVAL_MS: u64 = 100;
+const SIDECAR_ORIGIN_WAIT_TIMEOUT_MS: u64 = 15_000;
 const SIDECAR_STDERR_BUFFER_MAX_BYTES: usize = 4 * 1024;
 const SIDECAR_RETRY_DELAY_MS: u64 = 200;
+/// Delay before the first restart after a crash, doubled for each further restart in a row
+const SIDECAR_RESTART_BASE_DELAY_MS: u64 = 500;
+const SIDECAR_RESTART_MAX_DELAY_MS: u64 = 30_000;
+/// Restarts in a row after which the sidecar is considered to be crash looping
+const SIDECAR_MAX_CONSECUTIVE_RESTARTS: u32 = 5;
+/// A sidecar that kept answering health checks this long resets the restart count
+const SIDECAR_STABLE_UPTIME_MS: u64 = 60_000;
+/// How long the sidecar gets to acknowledge a config update before it is sent again
+const SIDECAR_CONFIG_ACK_TIMEOUT_MS: u64 = 3_000;
+const SIDECAR_CONFIG_PUSH_MAX_ATTEMPTS: u8 = 3;
+/// How long a freshly started sidecar gets to answer the protocol `hello`
+const SIDECAR_PROTOCOL_HANDSHAKE_TIMEOUT_MS: u64 = 3_000;
+const SIDECAR_PING_TIMEOUT_MS: u64 = 5_000;
+const DEFAULT_SIDECAR_WATCHDOG_INTERVAL_MS: u64 = 5_000;
+const SIDECAR_WATCHDOG_MIN_INTERVAL_MS: u64 = 1_000;
+const SIDECAR_WATCHDOG_MAX_INTERVAL_MS: u64 = 300_000;
+const SIDECAR_WATCHDOG_REQUEST_TIMEOUT_MS: u64 = 5_000;
+/// Failed liveness checks in a row after which the sidecar is reported as degraded
+const SIDECAR_WATCHDOG_DEGRADED_AFTER_FAILURES: u32 = 3;
+/// Failed liveness checks in a row after which the sidecar is killed and restarted
+const SIDECAR_WATCHDOG_RESTART_AFTER_FAILURES: u32 = 12;
+/// How long a stopping sidecar gets to finish its requests before it is killed. The sidecar
+/// exits on its own 5 seconds after it was asked to shut down.
+const SIDECAR_GRACEFUL_STOP_TIMEOUT_MS: u64 = 6_000;
 const SIDECAR_SERVICE_NAME: &str = "mind-flayer-sidecar";
 const SIDECAR_STARTUP_TOKEN_ENV_KEY: &str = "SIDECAR_STARTUP_TOKEN";
 const MINDFLAYER_APP_SUPPORT_DIR_ENV_KEY: &str = "MINDFLAYER_APP_SUPPORT_DIR";
 const MINDFLAYER_PROXY_URL_ENV_KEY: &str = "MINDFLAYER_PROXY_URL";
+const SIDECAR_API_TOKEN_ENV_KEY: &str = "MINDFLAYER_SIDECAR_API_TOKEN";
+const SIDECAR_API_TOKEN_BYTES: usize = 32;
+const SIDECAR_SOCKET_PATH_ENV_KEY: &str = "MINDFLAYER_SIDECAR_SOCKET_PATH";
+const SIDECAR_SOCKET_FILE_NAME: &str = "sidecar.sock";
+/// Longest socket path that fits `sockaddr_un` on every supported platform (104 bytes on
+/// macOS, including the terminating NUL)
+const SIDECAR_SOCKET_PATH_MAX_BYTES: usize = 103;
+/// Scheme the webview uses to reach a sidecar listening on a Unix socket
+pub const SIDECAR_URI_SCHEME: &str = "sidecar";
 const SETTINGS_STORE_FILE_NAME: &str = "settings.json";
 const GLOBAL_SKILLS_DIR_NAME: &str = "skills";
 const BUNDLED_SKILLS_DIR_NAME: &str = "builtin";
@@ -40,6 +79,13 @@ const WORKSPACE_BOOTSTRAP_FILE_NAME: &str = "BOOTSTRAP.md";
 const LOGS_DIR_NAME: &str = "logs";
 const HOST_LOG_FILE_NAME: &str = "host.log";
 const WORKSPACE_STATE_VERSION: u32 = 1;
+const SIDECAR_STATUS_CHANGED_EVENT: &str = "sidecar-status-changed";
+const SIDECAR_FAILED_EVENT: &str = "sidecar-failed";
+/// Reasons sent with the shutdown request, shown to chat streams the sidecar cancels
+const SIDECAR_STOPPED_REASON: &str = "The sidecar was stopped";
+const SIDECAR_RESTARTING_REASON: &str = "The sidecar is restarting";
+const SIDECAR_APP_EXIT_REASON: &str = "The app is quitting";
+const SIDECAR_NOT_RUNNING_MESSAGE: &str = "Sidecar is stopped";
 const SIDECAR_SHUTDOWN_MESSAGE: &str =
     "Sidecar startup skipped because application is shutting down";
 
@@ -79,20 +125,260 @@ impl Default for WorkspaceState {
     }
 }
 
+/// Where the sidecar is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SidecarLifecycle {
+    /// Copying bundled skills and workspace files before the first spawn attempt
+    InstallingAssets,
+    Spawning,
+    WaitingForHealth,
+    Healthy,
+    /// Running, but not fully usable; `SidecarStatus::degraded_reason` says why
+    Degraded,
+    /// Waiting to start again after a crash
+    Restarting,
+    /// Exited unexpectedly or failed to start, and will not be restarted
+    Crashed,
+    Stopped,
+}
+
+/// Snapshot of the sidecar lifecycle, returned by `get_sidecar_status` and sent with every
+/// `sidecar-status-changed` event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarStatus {
+    pub state: SidecarLifecycle,
+    /// Spawn attempt of the current start, starting at 1
+    pub attempt: u8,
+    /// Restarts in a row since the sidecar last stayed up
+    pub restarts: u32,
+    pub port: Option<u16>,
+    pub last_error: Option<String>,
+    /// Milliseconds since the Unix epoch at which `state` was entered
+    pub changed_at: u64,
+    /// Milliseconds since the Unix epoch at which the sidecar last became healthy
+    pub healthy_since: Option<u64>,
+    pub watchdog: SidecarWatchdogMetrics,
+    /// Sequence number of the last config update the sidecar acknowledged
+    pub last_applied_config_seq: Option<u64>,
+    /// Protocol version agreed on with the running sidecar
+    pub protocol_version: Option<u32>,
+    /// Why the sidecar is `Degraded`, `None` in every other state
+    pub degraded_reason: Option<SidecarDegradedReason>,
+}
+
+/// Cause of the `Degraded` lifecycle state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SidecarDegradedReason {
+    /// The provider config could not be pushed; cleared once an update is acknowledged
+    ConfigNotApplied,
+    /// Health checks keep failing; cleared once one succeeds again
+    Unresponsive,
+}
+
+/// Payload of `sidecar-failed`, sent when the sidecar crashed and will not be restarted, or
+/// could not be started at all
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarFailure {
+    pub error: String,
+}
+
+/// Results of the liveness checks against the running sidecar
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarWatchdogMetrics {
+    /// Response time of the last successful check
+    pub last_latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    /// Milliseconds since the Unix epoch
+    pub last_checked_at: Option<u64>,
+}
+
+/// How the sidecar HTTP server is reached, from `sidecarTransport` in the settings store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SidecarTransport {
+    /// Loopback TCP port, reachable by every local user
+    Tcp,
+    /// Unix domain socket in the app support directory, only accessible to the current user.
+    /// Not available on Windows.
+    Unix,
+}
+
+/// Address the running sidecar listens on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SidecarEndpoint {
+    Tcp(u16),
+    Unix(PathBuf),
+}
+
+impl SidecarEndpoint {
+    fn port(&self) -> Option<u16> {
+        match self {
+            Self::Tcp(port) => Some(*port),
+            Self::Unix(_) => None,
+        }
+    }
+
+    /// Base URL of the sidecar API for the webview. Requests to a Unix socket go through the
+    /// `sidecar://` URI scheme, which the host forwards to the socket.
+    pub fn origin(&self) -> String {
+        match self {
+            Self::Tcp(port) => format!("http://localhost:{}", port),
+            Self::Unix(_) => format!("{}://localhost", SIDECAR_URI_SCHEME),
+        }
+    }
+}
+
+impl std::fmt::Display for SidecarEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(port) => write!(f, "port {}", port),
+            Self::Unix(socket_path) => write!(f, "socket {}", socket_path.display()),
+        }
+    }
+}
+
 /// State to hold the sidecar process handle
 pub struct SidecarState {
     pub child: Arc<Mutex<Option<CommandChild>>>,
-    pub port: Arc<Mutex<Option<u16>>>,
+    /// Published once the sidecar passed its health check, `None` while it does not serve
+    /// requests
+    pub endpoint: tokio::sync::watch::Sender<Option<SidecarEndpoint>>,
     pub startup_lock: Arc<tauri::async_runtime::Mutex<()>>,
     pub shutting_down: Arc<AtomicBool>,
+    pub status: tokio::sync::watch::Sender<SidecarStatus>,
+    /// Fires when the current sidecar process exits
+    terminated_rx: Arc<Mutex<Option<TerminationReceiver>>>,
+    /// Task restarting the sidecar after crashes, aborted when it is stopped on request
+    supervisor: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    /// Keeps config updates in sequence order, one awaiting its acknowledgement at a time
+    config_push_lock: Arc<tauri::async_runtime::Mutex<()>>,
+    /// Sequence number of the last config update sent to the sidecar
+    config_seq: Arc<AtomicU64>,
+    /// ID of the last request written to the sidecar stdin
+    message_id: Arc<AtomicU64>,
+    pending_replies: PendingReplies,
+    /// ID of the last webview request streamed from the sidecar socket
+    socket_request_id: Arc<AtomicU64>,
+    /// Tasks streaming socket responses to the webview, aborted when it cancels the request
+    socket_requests: Arc<Mutex<HashMap<u64, tauri::async_runtime::JoinHandle<()>>>>,
+    /// Bearer token required by the sidecar HTTP API, kept for the whole app launch so
+    /// restarts do not invalidate the token cached by the webview
+    api_token: String,
 }
 
+/// Requests waiting for the sidecar reply with their ID
+type PendingReplies = Arc<Mutex<HashMap<u64, tokio::sync::oneshot::Sender<SidecarMessage>>>>;
+
 pub fn create_sidecar_state() -> SidecarState {
     SidecarState {
         child: Arc::new(Mutex::new(None)),
-        port: Arc::new(Mutex::new(None)),
+        endpoint: tokio::sync::watch::Sender::new(None),
         startup_lock: Arc::new(tauri::async_runtime::Mutex::new(())),
         shutting_down: Arc::new(AtomicBool::new(false)),
+        status: tokio::sync::watch::Sender::new(SidecarStatus {
+            state: SidecarLifecycle::Stopped,
+            attempt: 0,
+            restarts: 0,
+            port: None,
+            last_error: None,
+            changed_at: current_timestamp_millis(),
+            healthy_since: None,
+            watchdog: SidecarWatchdogMetrics::default(),
+            last_applied_config_seq: None,
+            protocol_version: None,
+            degraded_reason: None,
+        }),
+        terminated_rx: Arc::new(Mutex::new(None)),
+        supervisor: Arc::new(Mutex::new(None)),
+        config_push_lock: Arc::new(tauri::async_runtime::Mutex::new(())),
+        config_seq: Arc::new(AtomicU64::new(0)),
+        message_id: Arc::new(AtomicU64::new(0)),
+        pending_replies: Arc::new(Mutex::new(HashMap::new())),
+        socket_request_id: Arc::new(AtomicU64::new(0)),
+        socket_requests: Arc::new(Mutex::new(HashMap::new())),
+        api_token: generate_sidecar_api_token(),
+    }
+}
+
+/// Token the webview sends as `Authorization: Bearer ...` to the sidecar
+pub fn get_sidecar_api_token(app: &tauri::AppHandle) -> String {
+    app.state::<SidecarState>().api_token.clone()
+}
+
+/// Current sidecar lifecycle state
+pub fn get_sidecar_status(app: &tauri::AppHandle) -> SidecarStatus {
+    app.state::<SidecarState>().status.borrow().clone()
+}
+
+/// Move the sidecar to `lifecycle`, letting `update` adjust the other fields, and notify
+/// waiters and the frontend
+fn set_sidecar_status(
+    app: &tauri::AppHandle,
+    lifecycle: SidecarLifecycle,
+    update: impl FnOnce(&mut SidecarStatus),
+) {
+    let status = {
+        let state = app.state::<SidecarState>();
+        state.status.send_modify(|status| {
+            status.state = lifecycle;
+            status.changed_at = current_timestamp_millis();
+            if lifecycle != SidecarLifecycle::Degraded {
+                status.degraded_reason = None;
+            }
+            update(status);
+        });
+        let status = state.status.borrow().clone();
+        status
+    };
+
+    debug!("Sidecar status changed to {:?}", status.state);
+    emit_sidecar_status(app, &status);
+    if status.state == SidecarLifecycle::Crashed {
+        let failure = SidecarFailure {
+            error: status
+                .last_error
+                .unwrap_or_else(|| "Sidecar stopped unexpectedly".to_string()),
+        };
+        if let Err(e) = app.emit(SIDECAR_FAILED_EVENT, failure) {
+            error!("Failed to emit sidecar failure event: {}", e);
+        }
+    }
+}
+
+/// Store the result of a liveness check, notifying the frontend only when the failure count
+/// changes so every successful check does not produce an event
+fn record_sidecar_watchdog_check(
+    app: &tauri::AppHandle,
+    last_latency_ms: Option<u64>,
+    consecutive_failures: u32,
+) {
+    let changed_status = {
+        let state = app.state::<SidecarState>();
+        let failures_changed = state.status.send_if_modified(|status| {
+            let failures_changed = status.watchdog.consecutive_failures != consecutive_failures;
+            status.watchdog = SidecarWatchdogMetrics {
+                last_latency_ms: last_latency_ms.or(status.watchdog.last_latency_ms),
+                consecutive_failures,
+                last_checked_at: Some(current_timestamp_millis()),
+            };
+            failures_changed
+        });
+        let changed_status = failures_changed.then(|| state.status.borrow().clone());
+        changed_status
+    };
+
+    if let Some(status) = changed_status {
+        emit_sidecar_status(app, &status);
+    }
+}
+
+fn emit_sidecar_status(app: &tauri::AppHandle, status: &SidecarStatus) {
+    if let Err(e) = app.emit(SIDECAR_STATUS_CHANGED_EVENT, status) {
+        error!("Failed to emit sidecar status event: {}", e);
     }
 }
 
@@ -108,10 +394,21 @@ pub fn is_sidecar_shutdown_error(error: &str) -> bool {
     error == SIDECAR_SHUTDOWN_MESSAGE
 }
 
+fn ensure_not_shutting_down(app: &tauri::AppHandle) -> Result<(), String> {
+    if is_shutting_down(app.state::<SidecarState>().shutting_down.as_ref()) {
+        return Err(sidecar_shutdown_error());
+    }
+    Ok(())
+}
+
 #[derive(Debug, Deserialize)]
 struct PersistedSidecarSettings {
     #[serde(rename = "proxyUrl", default)]
     proxy_url: String,
+    #[serde(rename = "sidecarHealthCheckIntervalMs", default)]
+    health_check_interval_ms: Option<u64>,
+    #[serde(rename = "sidecarTransport", default)]
+    transport: Option<String>,
 }
 
 fn resolve_settings_store_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
@@ -137,7 +434,92 @@ fn parse_sidecar_proxy_url_from_settings_json(
     Ok(Some(trimmed_proxy_url.to_string()))
 }
 
-fn load_sidecar_proxy_url(app: &tauri::AppHandle) -> Option<String> {
+fn parse_sidecar_watchdog_interval_from_settings_json(
+    settings_json: &str,
+) -> Result<tokio::time::Duration, String> {
+    let settings: PersistedSidecarSettings = serde_json::from_str(settings_json)
+        .map_err(|e| format!("Failed to parse settings store: {}", e))?;
+    let interval_ms = settings
+        .health_check_interval_ms
+        .unwrap_or(DEFAULT_SIDECAR_WATCHDOG_INTERVAL_MS)
+        .clamp(
+            SIDECAR_WATCHDOG_MIN_INTERVAL_MS,
+            SIDECAR_WATCHDOG_MAX_INTERVAL_MS,
+        );
+    Ok(tokio::time::Duration::from_millis(interval_ms))
+}
+
+/// Interval of the sidecar liveness checks from the app settings
+fn load_sidecar_watchdog_interval(app: &tauri::AppHandle) -> tokio::time::Duration {
+    let default_interval = tokio::time::Duration::from_millis(DEFAULT_SIDECAR_WATCHDOG_INTERVAL_MS);
+    let Some((settings_path, settings_json)) = read_settings_store(app) else {
+        return default_interval;
+    };
+
+    match parse_sidecar_watchdog_interval_from_settings_json(&settings_json) {
+        Ok(interval) => interval,
+        Err(error) => {
+            warn!("{} at '{}'", error, settings_path.display());
+            default_interval
+        }
+    }
+}
+
+fn parse_sidecar_transport_from_settings_json(
+    settings_json: &str,
+) -> Result<SidecarTransport, String> {
+    let settings: PersistedSidecarSettings = serde_json::from_str(settings_json)
+        .map_err(|e| format!("Failed to parse settings store: {}", e))?;
+
+    match settings.transport.as_deref().map(str::trim) {
+        None | Some("") | Some("tcp") => Ok(SidecarTransport::Tcp),
+        Some("unix") => Ok(SidecarTransport::Unix),
+        Some(other) => Err(format!("Unknown sidecar transport '{}'", other)),
+    }
+}
+
+/// Sidecar transport from the app settings, TCP unless the Unix socket was selected
+fn load_sidecar_transport(app: &tauri::AppHandle) -> SidecarTransport {
+    let Some((settings_path, settings_json)) = read_settings_store(app) else {
+        return SidecarTransport::Tcp;
+    };
+
+    match parse_sidecar_transport_from_settings_json(&settings_json) {
+        Ok(transport) => transport,
+        Err(error) => {
+            warn!("{} at '{}'", error, settings_path.display());
+            SidecarTransport::Tcp
+        }
+    }
+}
+
+/// Socket the sidecar should listen on, or `None` when it has to use a TCP port
+fn resolve_sidecar_socket_path(
+    transport: SidecarTransport,
+    app_support_dir: &str,
+) -> Option<PathBuf> {
+    if transport != SidecarTransport::Unix {
+        return None;
+    }
+    if cfg!(not(unix)) {
+        warn!("Unix socket sidecar transport is not supported on this platform, using TCP");
+        return None;
+    }
+
+    let socket_path = Path::new(app_support_dir).join(SIDECAR_SOCKET_FILE_NAME);
+    if socket_path.as_os_str().len() > SIDECAR_SOCKET_PATH_MAX_BYTES {
+        warn!(
+            "Sidecar socket path '{}' is longer than {} bytes, using TCP",
+            socket_path.display(),
+            SIDECAR_SOCKET_PATH_MAX_BYTES
+        );
+        return None;
+    }
+    Some(socket_path)
+}
+
+/// Contents of the settings store, `None` if it does not exist or cannot be read
+fn read_settings_store(app: &tauri::AppHandle) -> Option<(PathBuf, String)> {
     let settings_path = match resolve_settings_store_path(app) {
         Ok(path) => path,
         Err(error) => {
@@ -146,18 +528,23 @@ fn load_sidecar_proxy_url(app: &tauri::AppHandle) -> Option<String> {
         }
     };
 
-    let settings_json = match fs::read_to_string(&settings_path) {
-        Ok(contents) => contents,
-        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return None,
+    match fs::read_to_string(&settings_path) {
+        Ok(contents) => Some((settings_path, contents)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
         Err(error) => {
             warn!(
                 "Failed to read settings store '{}': {}",
                 settings_path.display(),
                 error
             );
-            return None;
+            None
         }
-    };
+    }
+}
+
+/// Proxy URL from the app settings, shared by the sidecar and host-side provider requests
+pub fn load_sidecar_proxy_url(app: &tauri::AppHandle) -> Option<String> {
+    let (settings_path, settings_json) = read_settings_store(app)?;
 
     match parse_sidecar_proxy_url_from_settings_json(&settings_json) {
         Ok(proxy_url) => proxy_url,
@@ -168,67 +555,282 @@ fn load_sidecar_proxy_url(app: &tauri::AppHandle) -> Option<String> {
     }
 }
 
-/// Push API keys configuration to sidecar via stdin
-pub fn push_config_to_sidecar(app: &tauri::AppHandle) -> Result<(), String> {
-    let configs = crate::keychain::get_all_configs_providers();
+/// Push API keys configuration to sidecar via stdin, recording `command` in the audit log.
+/// Succeeds once the sidecar acknowledged the update, which is sent again when it does not
+/// answer in time.
+pub async fn push_config_to_sidecar(app: &tauri::AppHandle, command: &str) -> Result<(), String> {
+    let (push_lock, config_seq) = {
+        let state = app.state::<SidecarState>();
+        (
+            Arc::clone(&state.config_push_lock),
+            Arc::clone(&state.config_seq),
+        )
+    };
+    // Read the configs under the lock so a later sequence number never carries older configs
+    let _push_guard = push_lock.lock().await;
+
+    // While the master passphrase has not been entered the sidecar gets no keys at all.
+    // Configs that cannot be read fail the push, so the sidecar keeps the keys it has
+    // instead of being told there are none.
+    let (locked, configs) = crate::with_store_blocking(app, |store| {
+        let locked = store.is_locked();
+        let configs = if locked {
+            info!("Provider configs are locked, pushing empty config");
+            HashMap::new()
+        } else {
+            store.get_all_configs_providers()?
+        };
+        Ok((locked, configs))
+    })
+    .await
+    .map_err(|e| format!("Failed to read provider configs: {}", e))?;
 
     info!("Retrieved {} configs from keychain", configs.len());
-    for (provider, _) in &configs {
+    for (provider, config) in &configs {
         info!("  - {}", provider);
+        crate::redact::register_secrets(
+            config
+                .credentials
+                .iter()
+                .map(|credential| credential.api_key.as_str())
+                .chain(config.extra_headers.values().map(String::as_str)),
+        );
     }
 
     // Convert to JSON format for sidecar
     let mut json_configs = serde_json::Map::new();
-    for (provider, config) in configs {
+    for (provider, config) in &configs {
+        let Some(api_key) = config.api_key() else {
+            warn!("Skipping provider without an active API key: {}", provider);
+            continue;
+        };
+        // Notes and bookkeeping stay on the host
+        let credentials: Vec<_> = config
+            .credentials
+            .iter()
+            .map(|credential| {
+                serde_json::json!({
+                    "label": credential.label,
+                    "apiKey": credential.api_key,
+                    "active": credential.active,
+                    "expiresAt": credential.metadata.expires_at,
+                })
+            })
+            .collect();
         json_configs.insert(
-            provider,
+            provider.clone(),
             serde_json::json!({
-                "apiKey": config.api_key,
+                "apiKey": api_key,
                 "baseUrl": config.base_url,
+                "credentials": credentials,
+                "extraHeaders": config.extra_headers,
+                "organization": config.organization,
+                "project": config.project,
+                "requestTimeoutMs": config.request_timeout_ms,
+                "defaultModel": config.default_model,
+                "apiVersion": config.api_version,
             }),
         );
     }
 
-    let message = serde_json::json!({
-        "type": "config_update",
-        "configs": json_configs
-    });
+    let seq = config_seq.fetch_add(1, Ordering::SeqCst) + 1;
+    let mut attempt: u8 = 1;
+    loop {
+        let reply = request_sidecar(
+            app,
+            |id| HostMessage::ConfigUpdate {
+                id,
+                seq,
+                configs: json_configs.clone(),
+                locked,
+            },
+            tokio::time::Duration::from_millis(SIDECAR_CONFIG_ACK_TIMEOUT_MS),
+        )
+        .await?;
+        match reply {
+            Some(SidecarMessage::ConfigAck { error: None, .. }) => break,
+            Some(SidecarMessage::ConfigAck {
+                error: Some(message),
+                ..
+            })
+            | Some(SidecarMessage::Error { message, .. }) => {
+                let err = format!("Sidecar rejected config update {}: {}", seq, message);
+                error!("{}", err);
+                return Err(err);
+            }
+            Some(other) => {
+                return Err(format!(
+                    "Unexpected sidecar reply to config update {}: {:?}",
+                    seq, other
+                ));
+            }
+            None if attempt < SIDECAR_CONFIG_PUSH_MAX_ATTEMPTS => {
+                warn!(
+                    "Sidecar did not acknowledge config update {} within {}ms (attempt {}/{}), \
+                     sending it again",
+                    seq, SIDECAR_CONFIG_ACK_TIMEOUT_MS, attempt, SIDECAR_CONFIG_PUSH_MAX_ATTEMPTS
+                );
+                attempt += 1;
+            }
+            None => {
+                let err = format!(
+                    "Sidecar did not acknowledge config update {} after {} attempts",
+                    seq, SIDECAR_CONFIG_PUSH_MAX_ATTEMPTS
+                );
+                error!("{}", err);
+                return Err(err);
+            }
+        }
+    }
 
-    let message_str = format!("{}\n", message);
-    debug!(
-        "Pushing config message: {}",
-        &message_str[0..message_str.len().min(200)]
+    info!(
+        "Sidecar applied config update {}: {} providers",
+        seq,
+        json_configs.len()
     );
-    let message_bytes = message_str.as_bytes();
+    let pushed: Vec<_> = configs
+        .into_iter()
+        .filter(|(provider, _)| json_configs.contains_key(provider))
+        .collect();
+    let command = command.to_string();
+    if let Err(e) = crate::with_store_blocking(app, move |store| {
+        store.record_push(
+            &command,
+            pushed.iter().map(|(provider, config)| (provider, config)),
+        )
+    })
+    .await
+    {
+        warn!("Failed to record config push {}: {}", seq, e);
+    }
+    let mut restore_healthy = false;
+    app.state::<SidecarState>().status.send_modify(|status| {
+        status.last_applied_config_seq = Some(seq);
+        restore_healthy = status.degraded_reason == Some(SidecarDegradedReason::ConfigNotApplied);
+    });
+    if restore_healthy {
+        set_sidecar_status(app, SidecarLifecycle::Healthy, |status| {
+            status.last_error = None;
+        });
+    }
+    Ok(())
+}
 
-    let state = app.state::<SidecarState>();
-    let mut guard = state
-        .child
+/// Write the message built by `build` with a new request ID to the sidecar stdin and wait
+/// for the reply carrying that ID, or `None` when none arrives within `timeout`
+async fn request_sidecar(
+    app: &tauri::AppHandle,
+    build: impl FnOnce(u64) -> HostMessage,
+    timeout: tokio::time::Duration,
+) -> Result<Option<SidecarMessage>, String> {
+    let (id, pending_replies) = {
+        let state = app.state::<SidecarState>();
+        (
+            state.message_id.fetch_add(1, Ordering::SeqCst) + 1,
+            Arc::clone(&state.pending_replies),
+        )
+    };
+    let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+    pending_replies
+        .lock()
+        .map_err(|e| format!("Failed to acquire sidecar replies lock: {}", e))?
+        .insert(id, reply_tx);
+
+    let written = write_sidecar_message(&app.state::<SidecarState>().child, &build(id));
+    let reply = match written {
+        // The sender is dropped when the sidecar exits, which counts as no reply
+        Ok(()) => tokio::time::timeout(timeout, reply_rx)
+            .await
+            .ok()
+            .and_then(Result::ok),
+        Err(_) => None,
+    };
+    if let Ok(mut guard) = pending_replies.lock() {
+        guard.remove(&id);
+    }
+    written?;
+    Ok(reply)
+}
+
+/// Write a protocol message to the sidecar stdin
+fn write_sidecar_message(
+    child_ref: &Arc<Mutex<Option<CommandChild>>>,
+    message: &HostMessage,
+) -> Result<(), String> {
+    let line = protocol::encode_host_message(message)?;
+    debug!("Writing sidecar message: {}", message.log_summary());
+    let mut guard = child_ref
         .lock()
         .map_err(|e| format!("Failed to acquire sidecar lock: {}", e))?;
+    let Some(child) = guard.as_mut() else {
+        return Err("Sidecar process not running".to_string());
+    };
 
-    if let Some(child) = guard.as_mut() {
-        child.write(message_bytes).map_err(|e| {
-            let err = format!("Failed to write to sidecar stdin: {}", e);
-            error!("{}", err);
-            err
-        })?;
-        info!(
-            "Pushed config update to sidecar: {} providers",
-            json_configs.len()
-        );
-        Ok(())
-    } else {
-        Err("Sidecar process not running".to_string())
+    child.write(line.as_bytes()).map_err(|e| {
+        let err = format!("Failed to write to sidecar stdin: {}", e);
+        error!("{}", err);
+        err
+    })
+}
+
+/// Agree on the protocol version with a sidecar that just passed its health check
+async fn negotiate_sidecar_protocol(app: &tauri::AppHandle) -> Result<u32, String> {
+    let reply = request_sidecar(
+        app,
+        |id| HostMessage::Hello {
+            id,
+            protocol_version: protocol::PROTOCOL_VERSION,
+            min_protocol_version: protocol::MIN_PROTOCOL_VERSION,
+        },
+        tokio::time::Duration::from_millis(SIDECAR_PROTOCOL_HANDSHAKE_TIMEOUT_MS),
+    )
+    .await?;
+
+    match reply {
+        Some(SidecarMessage::Hello {
+            protocol_version, ..
+        }) => protocol::check_protocol_version(protocol_version),
+        Some(SidecarMessage::Error { message, .. }) => {
+            Err(protocol::incompatible_protocol_error(&message))
+        }
+        Some(other) => Err(protocol::incompatible_protocol_error(&format!(
+            "unexpected reply to hello: {:?}",
+            other
+        ))),
+        // Sidecars from before the protocol ignore messages they do not know
+        None => Err(protocol::incompatible_protocol_error(&format!(
+            "the sidecar did not answer hello within {}ms",
+            SIDECAR_PROTOCOL_HANDSHAKE_TIMEOUT_MS
+        ))),
+    }
+}
+
+/// Round trip time of a ping over the sidecar stdin and stdout, in milliseconds
+pub async fn ping_sidecar(app: &tauri::AppHandle) -> Result<u64, String> {
+    let started_at = tokio::time::Instant::now();
+    let reply = request_sidecar(
+        app,
+        |id| HostMessage::Ping { id },
+        tokio::time::Duration::from_millis(SIDECAR_PING_TIMEOUT_MS),
+    )
+    .await?;
+
+    match reply {
+        Some(SidecarMessage::Pong { .. }) => Ok(started_at.elapsed().as_millis() as u64),
+        Some(other) => Err(format!("Unexpected sidecar reply to ping: {:?}", other)),
+        None => Err(format!(
+            "Sidecar did not answer ping within {}ms",
+            SIDECAR_PING_TIMEOUT_MS
+        )),
     }
 }
 
-pub async fn start_sidecar(app: tauri::AppHandle) -> Result<u16, String> {
-    let (child_ref, port_ref, startup_lock, shutting_down) = {
+async fn start_sidecar_with_lock(app: tauri::AppHandle) -> Result<RunningSidecar, String> {
+    let (child_ref, endpoint_ref, startup_lock, shutting_down) = {
         let state = app.state::<SidecarState>();
         (
             Arc::clone(&state.child),
-            Arc::clone(&state.port),
+            state.endpoint.clone(),
             Arc::clone(&state.startup_lock),
             Arc::clone(&state.shutting_down),
         )
@@ -240,11 +842,529 @@ pub async fn start_sidecar(app: tauri::AppHandle) -> Result<u16, String> {
 
     let _startup_guard = startup_lock.lock().await;
 
-    if is_shutting_down(shutting_down.as_ref()) {
-        return Err(sidecar_shutdown_error());
+    if is_shutting_down(shutting_down.as_ref()) {
+        return Err(sidecar_shutdown_error());
+    }
+
+    start_sidecar_internal(app, child_ref, endpoint_ref, shutting_down).await
+}
+
+/// Start the sidecar if it is not running and return the origin of its API
+pub async fn start_sidecar(app: tauri::AppHandle) -> Result<String, String> {
+    let startup_lock = Arc::clone(&app.state::<SidecarState>().startup_lock);
+    ensure_not_shutting_down(&app)?;
+    let _startup_guard = startup_lock.lock().await;
+    ensure_not_shutting_down(&app)?;
+
+    let running_endpoint = app.state::<SidecarState>().endpoint.borrow().clone();
+    if let Some(endpoint) = running_endpoint {
+        return Ok(endpoint.origin());
+    }
+
+    info!("Starting sidecar on request");
+    // Cancels a pending restart and cleans up what is left of the last process
+    stop_sidecar_process(&app, SIDECAR_STOPPED_REASON).await;
+    start_supervised_sidecar(app, "start_sidecar").await
+}
+
+/// Shut the sidecar down gracefully and keep it stopped until it is started again
+pub async fn stop_sidecar(app: tauri::AppHandle) -> Result<(), String> {
+    let startup_lock = Arc::clone(&app.state::<SidecarState>().startup_lock);
+    ensure_not_shutting_down(&app)?;
+    let _startup_guard = startup_lock.lock().await;
+    ensure_not_shutting_down(&app)?;
+
+    info!("Stopping sidecar on request");
+    stop_sidecar_process(&app, SIDECAR_STOPPED_REASON).await;
+    set_sidecar_status(&app, SidecarLifecycle::Stopped, |status| {
+        status.port = None;
+        status.healthy_since = None;
+        status.restarts = 0;
+    });
+    Ok(())
+}
+
+/// Shut the sidecar down gracefully, start it again and return the origin of its API
+pub async fn restart_sidecar(app: tauri::AppHandle) -> Result<String, String> {
+    let startup_lock = Arc::clone(&app.state::<SidecarState>().startup_lock);
+    ensure_not_shutting_down(&app)?;
+    let _startup_guard = startup_lock.lock().await;
+    ensure_not_shutting_down(&app)?;
+
+    info!("Restarting sidecar on request");
+    set_sidecar_status(&app, SidecarLifecycle::Restarting, |status| {
+        status.restarts = 0;
+    });
+    stop_sidecar_process(&app, SIDECAR_RESTARTING_REASON).await;
+    start_supervised_sidecar(app, "restart_sidecar").await
+}
+
+/// Start the sidecar and hand it to a new supervisor. Call with `startup_lock` held.
+async fn start_supervised_sidecar(
+    app: tauri::AppHandle,
+    command: &'static str,
+) -> Result<String, String> {
+    let (child_ref, endpoint_ref, shutting_down) = {
+        let state = app.state::<SidecarState>();
+        (
+            Arc::clone(&state.child),
+            state.endpoint.clone(),
+            Arc::clone(&state.shutting_down),
+        )
+    };
+
+    match start_sidecar_internal(app.clone(), child_ref, endpoint_ref, shutting_down).await {
+        Ok(running) => {
+            let origin = running.endpoint.origin();
+            spawn_supervisor(app, Some(running), command);
+            Ok(origin)
+        }
+        Err(e) => {
+            if !is_sidecar_shutdown_error(&e) {
+                set_sidecar_status(&app, SidecarLifecycle::Crashed, |status| {
+                    status.port = None;
+                    status.last_error = Some(e.clone());
+                });
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Ask the sidecar over stdin to shut down, reporting `reason` to the requests it cancels
+fn send_sidecar_shutdown_message(state: &SidecarState, reason: &str) -> bool {
+    let message = HostMessage::Shutdown {
+        id: state.message_id.fetch_add(1, Ordering::SeqCst) + 1,
+        reason: reason.to_string(),
+    };
+
+    match write_sidecar_message(&state.child, &message) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Failed to send shutdown request to sidecar: {}", e);
+            false
+        }
+    }
+}
+
+/// End supervision and shut the sidecar process down, giving it the chance to finish
+/// in-flight chat streams with `reason` before it is killed. Call with `startup_lock` held.
+async fn stop_sidecar_process(app: &tauri::AppHandle, reason: &str) {
+    let (child_ref, endpoint_ref, terminated_rx) = {
+        let state = app.state::<SidecarState>();
+        match state.supervisor.lock() {
+            Ok(mut guard) => {
+                if let Some(supervisor) = guard.take() {
+                    supervisor.abort();
+                }
+            }
+            Err(e) => error!("Failed to acquire sidecar supervisor lock: {}", e),
+        }
+        let terminated_rx = match state.terminated_rx.lock() {
+            Ok(mut guard) => guard.take(),
+            Err(e) => {
+                error!("Failed to acquire sidecar termination lock: {}", e);
+                None
+            }
+        };
+        (
+            Arc::clone(&state.child),
+            state.endpoint.clone(),
+            terminated_rx,
+        )
+    };
+
+    let exited = match terminated_rx {
+        Some(mut terminated_rx)
+            if send_sidecar_shutdown_message(&app.state::<SidecarState>(), reason) =>
+        {
+            tokio::time::timeout(
+                tokio::time::Duration::from_millis(SIDECAR_GRACEFUL_STOP_TIMEOUT_MS),
+                wait_for_termination(&mut terminated_rx),
+            )
+            .await
+            .is_ok()
+        }
+        _ => false,
+    };
+
+    if exited {
+        info!("Sidecar shut down gracefully");
+        match child_ref.lock() {
+            Ok(mut guard) => drop(guard.take()),
+            Err(e) => error!("Failed to acquire sidecar child lock: {}", e),
+        }
+    } else {
+        kill_sidecar_process(&child_ref);
+    }
+    clear_sidecar_endpoint(&endpoint_ref);
+}
+
+/// Delay before the `restart`th restart in a row, starting at 1
+fn sidecar_restart_delay(restart: u32) -> tokio::time::Duration {
+    let delay_ms = SIDECAR_RESTART_BASE_DELAY_MS
+        .saturating_mul(1u64 << restart.saturating_sub(1).min(16))
+        .min(SIDECAR_RESTART_MAX_DELAY_MS);
+    tokio::time::Duration::from_millis(delay_ms)
+}
+
+/// Push provider config to the sidecar, reporting it as degraded if that fails
+async fn push_config_or_degrade(app: &tauri::AppHandle, command: &str) {
+    if let Err(e) = push_config_to_sidecar(app, command).await {
+        error!("Failed to push config to sidecar: {}", e);
+        set_sidecar_status(app, SidecarLifecycle::Degraded, |status| {
+            status.last_error = Some(format!("Failed to push config: {}", e));
+            status.degraded_reason = Some(SidecarDegradedReason::ConfigNotApplied);
+        });
+    }
+}
+
+fn is_config_not_applied(app: &tauri::AppHandle) -> bool {
+    app.state::<SidecarState>().status.borrow().degraded_reason
+        == Some(SidecarDegradedReason::ConfigNotApplied)
+}
+
+/// Start the sidecar in the background and keep it running
+pub fn spawn_sidecar_supervisor(app: tauri::AppHandle) {
+    // Waiters arriving before the supervisor runs must not take the initial state for a
+    // stopped sidecar
+    set_sidecar_status(&app, SidecarLifecycle::InstallingAssets, |_| {});
+    spawn_supervisor(app, None, "startup");
+}
+
+/// Run [`supervise_sidecar`] and remember its task, so stopping the sidecar can end it
+fn spawn_supervisor(app: tauri::AppHandle, running: Option<RunningSidecar>, command: &'static str) {
+    let supervisor_ref = Arc::clone(&app.state::<SidecarState>().supervisor);
+    let handle = tauri::async_runtime::spawn(supervise_sidecar(app, running, command));
+    match supervisor_ref.lock() {
+        Ok(mut guard) => {
+            if let Some(previous) = guard.replace(handle) {
+                previous.abort();
+            }
+        }
+        Err(e) => error!("Failed to acquire sidecar supervisor lock: {}", e),
+    };
+}
+
+/// Start the sidecar, unless `running` was already started, and restart it whenever it exits
+/// on its own, until the app shuts down or the sidecar keeps crashing. Provider config is
+/// pushed again after every start, recording `command` in the audit log for the first one.
+async fn supervise_sidecar(
+    app: tauri::AppHandle,
+    mut running: Option<RunningSidecar>,
+    mut command: &'static str,
+) {
+    let (child_ref, endpoint_ref, shutting_down) = {
+        let state = app.state::<SidecarState>();
+        (
+            Arc::clone(&state.child),
+            state.endpoint.clone(),
+            Arc::clone(&state.shutting_down),
+        )
+    };
+    let mut consecutive_restarts: u32 = 0;
+
+    loop {
+        let started = match running.take() {
+            Some(running) => Ok(running),
+            None => start_sidecar_with_lock(app.clone()).await,
+        };
+        let (failure, uptime) = match started {
+            Ok(running) => {
+                info!("Sidecar started successfully on {}", running.endpoint);
+                let started_at = tokio::time::Instant::now();
+                let mut uptime = SidecarUptime::started(started_at);
+
+                // Push API keys configuration to sidecar via stdin
+                if !is_shutting_down(shutting_down.as_ref()) {
+                    push_config_or_degrade(&app, command).await;
+                }
+
+                let mut terminated_rx = running.terminated_rx;
+                let exit_reason = tokio::select! {
+                    termination = wait_for_termination(&mut terminated_rx) => {
+                        if is_shutting_down(shutting_down.as_ref()) {
+                            return;
+                        }
+                        warn!(
+                            "Sidecar exited after {}s (code: {:?}, signal: {:?}, reason: {})",
+                            started_at.elapsed().as_secs(),
+                            termination.code,
+                            termination.signal,
+                            termination.reason
+                        );
+                        // The process is gone, so drop its handle instead of killing a reused pid
+                        match child_ref.lock() {
+                            Ok(mut guard) => drop(guard.take()),
+                            Err(e) => error!("Failed to acquire sidecar child lock: {}", e),
+                        }
+                        format!(
+                            "Sidecar exited unexpectedly (code: {:?}, signal: {:?})",
+                            termination.code, termination.signal
+                        )
+                    }
+                    reason = watch_sidecar_liveness(
+                        &app,
+                        &running.endpoint,
+                        &running.startup_token,
+                        &mut uptime,
+                    ) => {
+                        if is_shutting_down(shutting_down.as_ref()) {
+                            return;
+                        }
+                        error!("{}, killing it", reason);
+                        kill_sidecar_process(&child_ref);
+                        reason
+                    }
+                };
+
+                clear_sidecar_endpoint(&endpoint_ref);
+                uptime.record_exit(tokio::time::Instant::now());
+                (exit_reason, Some(uptime))
+            }
+            Err(e) if is_sidecar_shutdown_error(&e) => {
+                info!("{}", e);
+                return;
+            }
+            Err(e) if protocol::is_incompatible_protocol_error(&e) => {
+                error!("Not restarting sidecar: {}", e);
+                set_sidecar_status(&app, SidecarLifecycle::Crashed, |status| {
+                    status.port = None;
+                    status.last_error = Some(e);
+                });
+                return;
+            }
+            Err(e) => {
+                error!("Failed to start sidecar: {}", e);
+                // Startup already retried, only crashes are restarted
+                if command == "startup" {
+                    set_sidecar_status(&app, SidecarLifecycle::Crashed, |status| {
+                        status.port = None;
+                        status.last_error = Some(e);
+                    });
+                    return;
+                }
+                (e, None)
+            }
+        };
+
+        // Crashed is only entered once no restart follows, so waiters can give up on it
+        let Some(restart) = next_sidecar_restart(consecutive_restarts, uptime.as_ref()) else {
+            error!(
+                "Sidecar failed {} times in a row, not restarting it again",
+                SIDECAR_MAX_CONSECUTIVE_RESTARTS
+            );
+            set_sidecar_status(&app, SidecarLifecycle::Crashed, |status| {
+                status.port = None;
+                status.healthy_since = None;
+                status.last_error = Some(failure);
+            });
+            return;
+        };
+        consecutive_restarts = restart;
+
+        let delay = sidecar_restart_delay(consecutive_restarts);
+        set_sidecar_status(&app, SidecarLifecycle::Restarting, |status| {
+            status.port = None;
+            status.healthy_since = None;
+            status.last_error = Some(failure);
+            status.restarts = consecutive_restarts;
+        });
+        info!(
+            "Restarting sidecar in {}ms (restart {}/{})",
+            delay.as_millis(),
+            consecutive_restarts,
+            SIDECAR_MAX_CONSECUTIVE_RESTARTS
+        );
+        tokio::time::sleep(delay).await;
+        if is_shutting_down(shutting_down.as_ref()) {
+            return;
+        }
+        command = "sidecar_restart";
+    }
+}
+
+/// How long a running sidecar kept answering health checks, so one that hangs or crashes
+/// soon after starting is not mistaken for one that stayed up
+#[derive(Debug, Clone, Copy)]
+struct SidecarUptime {
+    /// Start of the current run of successful health checks
+    healthy_since: tokio::time::Instant,
+    /// Last moment the sidecar was known to be healthy
+    healthy_until: tokio::time::Instant,
+    responding: bool,
+}
+
+impl SidecarUptime {
+    /// A sidecar that just passed its startup health check
+    fn started(now: tokio::time::Instant) -> Self {
+        Self {
+            healthy_since: now,
+            healthy_until: now,
+            responding: true,
+        }
+    }
+
+    fn record_check(&mut self, responded: bool, now: tokio::time::Instant) {
+        if responded {
+            if !self.responding {
+                self.healthy_since = now;
+            }
+            self.healthy_until = now;
+        }
+        self.responding = responded;
+    }
+
+    /// The process went away; it was healthy until then unless its health checks were failing
+    fn record_exit(&mut self, now: tokio::time::Instant) {
+        if self.responding {
+            self.healthy_until = now;
+        }
+    }
+
+    fn was_stable(&self) -> bool {
+        self.healthy_until.duration_since(self.healthy_since)
+            >= tokio::time::Duration::from_millis(SIDECAR_STABLE_UPTIME_MS)
+    }
+}
+
+/// Restarts in a row after the sidecar went down again, given the ones before and how long
+/// it stayed healthy (`None` if it never started). `None` once it is crash looping.
+fn next_sidecar_restart(consecutive_restarts: u32, uptime: Option<&SidecarUptime>) -> Option<u32> {
+    let restart = if uptime.is_some_and(SidecarUptime::was_stable) {
+        1
+    } else {
+        consecutive_restarts.saturating_add(1)
+    };
+    (restart <= SIDECAR_MAX_CONSECUTIVE_RESTARTS).then_some(restart)
+}
+
+/// Poll `/health` of a running sidecar, reporting it as degraded after a few failed checks in
+/// a row. Returns once it has not responded for so long that it should be restarted.
+async fn watch_sidecar_liveness(
+    app: &tauri::AppHandle,
+    endpoint: &SidecarEndpoint,
+    startup_token: &str,
+    uptime: &mut SidecarUptime,
+) -> String {
+    let shutting_down = Arc::clone(&app.state::<SidecarState>().shutting_down);
+    let interval = load_sidecar_watchdog_interval(app);
+    let health_target = match SidecarHealthTarget::new(endpoint) {
+        Ok(target) => target,
+        Err(e) => {
+            error!("Sidecar watchdog disabled: {}", e);
+            return std::future::pending().await;
+        }
+    };
+    let mut degraded = false;
+    // Whether the sidecar was still missing its config when it stopped responding
+    let mut config_not_applied = false;
+
+    check_sidecar_liveness(
+        &health_target,
+        startup_token,
+        interval,
+        shutting_down.as_ref(),
+        uptime,
+        |result, consecutive_failures| {
+            record_sidecar_watchdog_check(app, result.as_ref().ok().copied(), consecutive_failures);
+            match result {
+                Ok(_) if degraded => {
+                    degraded = false;
+                    info!("Sidecar responds to health checks again");
+                    if config_not_applied || is_config_not_applied(app) {
+                        config_not_applied = false;
+                        // Stays degraded until the config is acknowledged
+                        set_sidecar_status(app, SidecarLifecycle::Degraded, |status| {
+                            status.degraded_reason = Some(SidecarDegradedReason::ConfigNotApplied);
+                        });
+                        let app = app.clone();
+                        tauri::async_runtime::spawn(async move {
+                            push_config_or_degrade(&app, "sidecar_watchdog").await;
+                        });
+                    } else {
+                        set_sidecar_status(app, SidecarLifecycle::Healthy, |status| {
+                            status.last_error = None;
+                        });
+                    }
+                }
+                Err(e)
+                    if consecutive_failures >= SIDECAR_WATCHDOG_DEGRADED_AFTER_FAILURES
+                        && !degraded =>
+                {
+                    degraded = true;
+                    config_not_applied = is_config_not_applied(app);
+                    set_sidecar_status(app, SidecarLifecycle::Degraded, |status| {
+                        status.last_error = Some(format!("Sidecar is not responding: {}", e));
+                        status.degraded_reason = Some(SidecarDegradedReason::Unresponsive);
+                    });
+                }
+                _ => {}
+            }
+        },
+    )
+    .await
+}
+
+/// Check `/health` every `interval`, passing the latency or error of each check and the
+/// failures in a row so far to `on_check`, until it failed often enough in a row that the
+/// sidecar should be restarted
+async fn check_sidecar_liveness(
+    health_target: &SidecarHealthTarget,
+    startup_token: &str,
+    interval: tokio::time::Duration,
+    shutting_down: &AtomicBool,
+    uptime: &mut SidecarUptime,
+    mut on_check: impl FnMut(&Result<u64, String>, u32),
+) -> String {
+    let request_timeout = interval.min(tokio::time::Duration::from_millis(
+        SIDECAR_WATCHDOG_REQUEST_TIMEOUT_MS,
+    ));
+    let mut consecutive_failures: u32 = 0;
+
+    loop {
+        tokio::time::sleep(interval).await;
+        // Exiting the app terminates the process, which ends supervision
+        if is_shutting_down(shutting_down) {
+            return std::future::pending().await;
+        }
+
+        let started_at = tokio::time::Instant::now();
+        let result = tokio::time::timeout(
+            request_timeout,
+            probe_sidecar_health(health_target, startup_token),
+        )
+        .await
+        .unwrap_or_else(|_| {
+            Err(format!(
+                "Health endpoint did not respond within {}ms",
+                request_timeout.as_millis()
+            ))
+        })
+        .map(|()| started_at.elapsed().as_millis() as u64);
+        uptime.record_check(result.is_ok(), tokio::time::Instant::now());
+
+        match &result {
+            Ok(_) => consecutive_failures = 0,
+            Err(e) => {
+                consecutive_failures += 1;
+                warn!(
+                    "Sidecar liveness check failed ({} in a row): {}",
+                    consecutive_failures, e
+                );
+            }
+        }
+        on_check(&result, consecutive_failures);
+
+        if let Err(e) = result {
+            if consecutive_failures >= SIDECAR_WATCHDOG_RESTART_AFTER_FAILURES {
+                return format!(
+                    "Sidecar did not respond to {} health checks in a row: {}",
+                    consecutive_failures, e
+                );
+            }
+        }
     }
-
-    start_sidecar_internal(app, child_ref, port_ref, shutting_down).await
 }
 
 fn resolve_sidecar_app_support_dir() -> Result<String, String> {
@@ -278,7 +1398,9 @@ fn append_host_log_line(host_log_path: &Path, level: &str, message: &str) {
     }
 
     let timestamp = current_timestamp_millis();
-    let normalized_message = message.trim_end();
+    // Sidecar output can echo the config pushed over stdin
+    let redacted_message = crate::redact::redact(message);
+    let normalized_message = redacted_message.trim_end();
     match fs::OpenOptions::new()
         .create(true)
         .append(true)
@@ -304,13 +1426,59 @@ fn append_host_log_line(host_log_path: &Path, level: &str, message: &str) {
     }
 }
 
-fn should_persist_host_stdout(message: &str) -> bool {
-    let trimmed = message.trim_start();
-    trimmed.starts_with("Sidecar running on http://localhost:")
-        || trimmed.starts_with("API endpoint: http://localhost:")
-        || trimmed.starts_with("Shutting down gracefully...")
-        || trimmed.starts_with("Server closed, port released")
-        || trimmed.starts_with("Sidecar process exiting...")
+/// Hand replies to the request waiting for them and record everything else in the host log
+fn handle_sidecar_message(
+    pending_replies: &PendingReplies,
+    listening_tx: &tokio::sync::watch::Sender<bool>,
+    host_log_path: &Path,
+    message: SidecarMessage,
+) {
+    if let Some(id) = message.reply_id() {
+        let reply_tx = match pending_replies.lock() {
+            Ok(mut guard) => guard.remove(&id),
+            Err(e) => {
+                error!("Failed to acquire sidecar replies lock: {}", e);
+                None
+            }
+        };
+        match reply_tx {
+            Some(reply_tx) => drop(reply_tx.send(message)),
+            None => debug!("Ignoring late sidecar reply to request {}", id),
+        }
+        return;
+    }
+
+    match message {
+        SidecarMessage::Log { level, message } => {
+            log::log!(level.into(), "[Sidecar] {}", message);
+            if matches!(
+                level,
+                protocol::SidecarLogLevel::Warn | protocol::SidecarLogLevel::Error
+            ) {
+                append_host_log_line(host_log_path, "LOG", &message);
+            }
+        }
+        SidecarMessage::Status { event, message } => {
+            if event == protocol::SidecarStatusEvent::Listening {
+                listening_tx.send_replace(true);
+            }
+            let text = match message {
+                Some(message) => format!("{:?}: {}", event, message),
+                None => format!("{:?}", event),
+            };
+            info!("[Sidecar] {}", text);
+            append_host_log_line(host_log_path, "STATUS", &text);
+        }
+        SidecarMessage::Error { code, message, .. } => {
+            let text = format!("{:?}: {}", code, message);
+            error!("[Sidecar Protocol Error] {}", text);
+            append_host_log_line(host_log_path, "PROTOCOL_ERROR", &text);
+        }
+        // Replies always carry the ID of their request
+        SidecarMessage::Hello { .. }
+        | SidecarMessage::ConfigAck { .. }
+        | SidecarMessage::Pong { .. } => {}
+    }
 }
 
 fn load_workspace_state(state_path: &Path) -> Result<WorkspaceState, String> {
@@ -588,9 +1756,36 @@ enum SidecarAttemptError {
     Terminated(SidecarTermination),
 }
 
+/// Sidecar that passed its health check
+struct RunningSidecar {
+    endpoint: SidecarEndpoint,
+    /// Expected in `/health` responses of this process
+    startup_token: String,
+    terminated_rx: TerminationReceiver,
+}
+
+/// Holds `Some` once the process exited or its event stream closed
+type TerminationReceiver = tokio::sync::watch::Receiver<Option<SidecarTermination>>;
+
+/// Wait until the process behind `terminated_rx` has exited
+async fn wait_for_termination(terminated_rx: &mut TerminationReceiver) -> SidecarTermination {
+    let termination = terminated_rx
+        .wait_for(Option::is_some)
+        .await
+        .ok()
+        .and_then(|termination| termination.clone());
+    termination.unwrap_or_else(|| SidecarTermination {
+        code: None,
+        signal: None,
+        reason: "Sidecar termination signal channel dropped".to_string(),
+    })
+}
+
 struct SidecarAttemptMonitor {
     stderr_output: Arc<Mutex<String>>,
-    terminated_rx: tokio::sync::oneshot::Receiver<SidecarTermination>,
+    terminated_rx: TerminationReceiver,
+    /// Becomes `true` once the sidecar reported that its server is listening
+    listening_rx: tokio::sync::watch::Receiver<bool>,
 }
 
 fn append_stderr_output(stderr_output: &Arc<Mutex<String>>, chunk: &str) {
@@ -680,15 +1875,15 @@ fn should_fallback_to_random_port(attempt: u8, failure_kind: SidecarStartupFailu
 }
 
 fn format_attempt_failure(
-    port: u16,
+    endpoint: &SidecarEndpoint,
     attempt_error: &SidecarAttemptError,
     stderr_output: &str,
 ) -> String {
     let mut message = match attempt_error {
         SidecarAttemptError::HealthCheck(err) => err.clone(),
         SidecarAttemptError::Terminated(termination) => format!(
-            "Sidecar terminated before becoming healthy on port {} (code: {:?}, signal: {:?}, reason: {})",
-            port, termination.code, termination.signal, termination.reason
+            "Sidecar terminated before becoming healthy on {} (code: {:?}, signal: {:?}, reason: {})",
+            endpoint, termination.code, termination.signal, termination.reason
         ),
     };
 
@@ -699,12 +1894,26 @@ fn format_attempt_failure(
     message
 }
 
-fn generate_sidecar_startup_token(attempt: u8, port: u16) -> String {
+fn generate_sidecar_api_token() -> String {
+    let mut bytes = [0u8; SIDECAR_API_TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    let token = general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+    crate::redact::register_secrets([token.as_str()]);
+    token
+}
+
+fn generate_sidecar_startup_token(attempt: u8, endpoint: &SidecarEndpoint) -> String {
     let nanos = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map(|duration| duration.as_nanos())
         .unwrap_or_default();
-    format!("{}-{}-{}-{}", std::process::id(), attempt, port, nanos)
+    format!(
+        "{}-{}-{}-{}",
+        std::process::id(),
+        attempt,
+        endpoint.port().unwrap_or_default(),
+        nanos
+    )
 }
 
 fn build_sidecar_health_client() -> Result<reqwest::Client, String> {
@@ -729,10 +1938,12 @@ fn is_expected_health_payload(payload: &serde_json::Value, expected_startup_toke
 fn spawn_sidecar_event_monitor(
     mut rx: tauri::async_runtime::Receiver<CommandEvent>,
     host_log_path: PathBuf,
+    pending_replies: PendingReplies,
 ) -> SidecarAttemptMonitor {
     let stderr_output = Arc::new(Mutex::new(String::new()));
     let stderr_output_for_task = Arc::clone(&stderr_output);
-    let (terminated_tx, terminated_rx) = tokio::sync::oneshot::channel::<SidecarTermination>();
+    let (terminated_tx, terminated_rx) = tokio::sync::watch::channel(None);
+    let (listening_tx, listening_rx) = tokio::sync::watch::channel(false);
 
     tauri::async_runtime::spawn(async move {
         let mut terminated_tx = Some(terminated_tx);
@@ -741,9 +1952,14 @@ fn spawn_sidecar_event_monitor(
             match event {
                 CommandEvent::Stdout(line) => {
                     let text = String::from_utf8_lossy(&line).into_owned();
-                    debug!("[Sidecar] {}", text);
-                    if should_persist_host_stdout(&text) {
-                        append_host_log_line(&host_log_path, "STDOUT", &text);
+                    match protocol::parse_sidecar_message(&text) {
+                        Some(message) => handle_sidecar_message(
+                            &pending_replies,
+                            &listening_tx,
+                            &host_log_path,
+                            message,
+                        ),
+                        None => debug!("[Sidecar] {}", text),
                     }
                 }
                 CommandEvent::Stderr(line) => {
@@ -772,7 +1988,7 @@ fn spawn_sidecar_event_monitor(
                         ),
                     );
                     if let Some(tx) = terminated_tx.take() {
-                        let _ = tx.send(termination);
+                        tx.send_replace(Some(termination));
                     }
                 }
                 _ => {}
@@ -785,49 +2001,41 @@ fn spawn_sidecar_event_monitor(
                 "TERMINATED",
                 "Sidecar process event stream closed",
             );
-            let _ = tx.send(SidecarTermination {
+            tx.send_replace(Some(SidecarTermination {
                 code: None,
                 signal: None,
                 reason: "Sidecar process event stream closed".to_string(),
-            });
+            }));
         }
     });
 
     SidecarAttemptMonitor {
         stderr_output,
         terminated_rx,
+        listening_rx,
     }
 }
 
 async fn wait_for_sidecar_ready(
-    port: u16,
+    endpoint: &SidecarEndpoint,
     timeout: tokio::time::Duration,
-    interval: tokio::time::Duration,
-    terminated_rx: tokio::sync::oneshot::Receiver<SidecarTermination>,
+    monitor: &mut SidecarAttemptMonitor,
     expected_startup_token: String,
     shutting_down: Arc<AtomicBool>,
 ) -> Result<(), SidecarAttemptError> {
     let health_check = wait_for_sidecar_health(
-        port,
+        endpoint,
         timeout,
-        interval,
+        &mut monitor.listening_rx,
         &expected_startup_token,
         shutting_down,
     );
     tokio::pin!(health_check);
-    let mut terminated_rx = terminated_rx;
 
     tokio::select! {
         health_result = &mut health_check => health_result.map_err(SidecarAttemptError::HealthCheck),
-        termination_result = &mut terminated_rx => {
-            match termination_result {
-                Ok(termination) => Err(SidecarAttemptError::Terminated(termination)),
-                Err(_) => Err(SidecarAttemptError::Terminated(SidecarTermination {
-                    code: None,
-                    signal: None,
-                    reason: "Sidecar termination signal channel dropped".to_string(),
-                })),
-            }
+        termination = wait_for_termination(&mut monitor.terminated_rx) => {
+            Err(SidecarAttemptError::Terminated(termination))
         }
     }
 }
@@ -846,22 +2054,38 @@ fn sidecar_health_url(port: u16) -> String {
     format!("http://127.0.0.1:{}/health", port)
 }
 
-fn clear_sidecar_port(port_ref: &Arc<Mutex<Option<u16>>>) {
-    if let Ok(mut guard) = port_ref.lock() {
-        *guard = None;
-    } else {
-        error!("Failed to clear sidecar port state");
-    }
+/// How health checks reach the sidecar at an endpoint
+enum SidecarHealthTarget {
+    Http {
+        client: reqwest::Client,
+        url: String,
+    },
+    Unix(PathBuf),
 }
 
-fn set_sidecar_port(port_ref: &Arc<Mutex<Option<u16>>>, port: u16) {
-    if let Ok(mut guard) = port_ref.lock() {
-        *guard = Some(port);
-    } else {
-        error!("Failed to set sidecar port state");
+impl SidecarHealthTarget {
+    fn new(endpoint: &SidecarEndpoint) -> Result<Self, String> {
+        match endpoint {
+            SidecarEndpoint::Tcp(port) => Ok(Self::Http {
+                client: build_sidecar_health_client()?,
+                url: sidecar_health_url(*port),
+            }),
+            SidecarEndpoint::Unix(socket_path) => Ok(Self::Unix(socket_path.clone())),
+        }
     }
 }
 
+fn clear_sidecar_endpoint(endpoint_ref: &tokio::sync::watch::Sender<Option<SidecarEndpoint>>) {
+    endpoint_ref.send_replace(None);
+}
+
+fn set_sidecar_endpoint(
+    endpoint_ref: &tokio::sync::watch::Sender<Option<SidecarEndpoint>>,
+    endpoint: SidecarEndpoint,
+) {
+    endpoint_ref.send_replace(Some(endpoint));
+}
+
 fn kill_sidecar_process(child_ref: &Arc<Mutex<Option<CommandChild>>>) {
     if let Ok(mut guard) = child_ref.lock() {
         if let Some(child) = guard.take() {
@@ -874,67 +2098,99 @@ fn kill_sidecar_process(child_ref: &Arc<Mutex<Option<CommandChild>>>) {
     }
 }
 
+/// Wait until the sidecar reports that it is listening, then check `/health` to make sure
+/// the endpoint is served by this process
 async fn wait_for_sidecar_health(
-    port: u16,
+    endpoint: &SidecarEndpoint,
     timeout: tokio::time::Duration,
-    interval: tokio::time::Duration,
+    listening_rx: &mut tokio::sync::watch::Receiver<bool>,
     expected_startup_token: &str,
     shutting_down: Arc<AtomicBool>,
 ) -> Result<(), String> {
     let started_at = tokio::time::Instant::now();
-    let health_url = sidecar_health_url(port);
-    let health_client = build_sidecar_health_client()?;
-    let mut last_error = String::from("Sidecar did not respond yet");
+    let health_target = SidecarHealthTarget::new(endpoint)?;
 
-    loop {
-        if is_shutting_down(shutting_down.as_ref()) {
-            return Err(sidecar_shutdown_error());
+    let listening = tokio::time::timeout(timeout, listening_rx.wait_for(|listening| *listening))
+        .await
+        .map(|listening| listening.is_ok());
+    match listening {
+        Ok(true) => {}
+        Ok(false) => return Err("Sidecar output closed before it started listening".to_string()),
+        Err(_) => {
+            return Err(format!(
+                "Sidecar did not start listening on {} within {}ms",
+                endpoint,
+                timeout.as_millis()
+            ))
         }
+    }
+    if is_shutting_down(shutting_down.as_ref()) {
+        return Err(sidecar_shutdown_error());
+    }
 
-        if started_at.elapsed() >= timeout {
-            return Err(format!(
-                "Sidecar health check timed out on port {} after {}ms: {}",
-                port,
-                timeout.as_millis(),
-                last_error
-            ));
-        }
-
-        match health_client.get(&health_url).send().await {
-            Ok(resp) if resp.status().is_success() => {
-                match resp.json::<serde_json::Value>().await {
-                    Ok(payload) if is_expected_health_payload(&payload, expected_startup_token) => {
-                        info!("Sidecar health check passed on port {}", port);
-                        return Ok(());
-                    }
-                    Ok(payload) => {
-                        let service = payload
-                            .get("service")
-                            .and_then(serde_json::Value::as_str)
-                            .unwrap_or("<missing>");
-                        let startup_token = payload
-                            .get("startupToken")
-                            .and_then(serde_json::Value::as_str)
-                            .unwrap_or("<missing>");
-                        last_error = format!(
-                        "Health endpoint returned unexpected payload (service={}, startupToken={})",
-                        service, startup_token
-                    );
-                    }
-                    Err(e) => {
-                        last_error = format!("Failed to parse health endpoint response: {}", e);
-                    }
-                }
-            }
-            Ok(resp) => {
-                last_error = format!("Health endpoint returned {}", resp.status());
-            }
-            Err(e) => {
-                last_error = format!("Failed to connect to sidecar: {}", e);
+    tokio::time::timeout(
+        timeout.saturating_sub(started_at.elapsed()),
+        probe_sidecar_health(&health_target, expected_startup_token),
+    )
+    .await
+    .unwrap_or_else(|_| {
+        Err(format!(
+            "Sidecar health check timed out on {} after {}ms",
+            endpoint,
+            timeout.as_millis()
+        ))
+    })?;
+    info!("Sidecar health check passed on {}", endpoint);
+    Ok(())
+}
+
+/// Request `/health` once and check that it was answered by the expected sidecar process
+async fn probe_sidecar_health(
+    health_target: &SidecarHealthTarget,
+    expected_startup_token: &str,
+) -> Result<(), String> {
+    let payload = fetch_sidecar_health_payload(health_target).await?;
+    if is_expected_health_payload(&payload, expected_startup_token) {
+        return Ok(());
+    }
+
+    let service = payload
+        .get("service")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or("<missing>");
+    let startup_token = payload
+        .get("startupToken")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or("<missing>");
+    Err(format!(
+        "Health endpoint returned unexpected payload (service={}, startupToken={})",
+        service, startup_token
+    ))
+}
+
+async fn fetch_sidecar_health_payload(
+    health_target: &SidecarHealthTarget,
+) -> Result<serde_json::Value, String> {
+    match health_target {
+        SidecarHealthTarget::Http { client, url } => match client.get(url).send().await {
+            Ok(resp) if resp.status().is_success() => resp
+                .json::<serde_json::Value>()
+                .await
+                .map_err(|e| format!("Failed to parse health endpoint response: {}", e)),
+            Ok(resp) => Err(format!("Health endpoint returned {}", resp.status())),
+            Err(e) => Err(format!("Failed to connect to sidecar: {}", e)),
+        },
+        #[cfg(unix)]
+        SidecarHealthTarget::Unix(socket_path) => {
+            let resp = unix_socket::get(socket_path, "/health").await?;
+            if !resp.status().is_success() {
+                return Err(format!("Health endpoint returned {}", resp.status()));
             }
+            serde_json::from_slice(resp.body())
+                .map_err(|e| format!("Failed to parse health endpoint response: {}", e))
         }
-
-        tokio::time::sleep(interval).await;
+        #[cfg(not(unix))]
+        SidecarHealthTarget::Unix(_) => Err(unix_socket_unsupported_error()),
     }
 }
 
@@ -942,11 +2198,20 @@ async fn wait_for_sidecar_health(
 async fn start_sidecar_internal(
     app: tauri::AppHandle,
     child_ref: Arc<Mutex<Option<CommandChild>>>,
-    port_ref: Arc<Mutex<Option<u16>>>,
+    endpoint_ref: tokio::sync::watch::Sender<Option<SidecarEndpoint>>,
     shutting_down: Arc<AtomicBool>,
-) -> Result<u16, String> {
-    clear_sidecar_port(&port_ref);
+) -> Result<RunningSidecar, String> {
+    clear_sidecar_endpoint(&endpoint_ref);
+    set_sidecar_status(&app, SidecarLifecycle::InstallingAssets, |status| {
+        status.attempt = 0;
+        status.port = None;
+        status.healthy_since = None;
+        status.watchdog = SidecarWatchdogMetrics::default();
+        status.protocol_version = None;
+    });
     let configured_proxy_url = load_sidecar_proxy_url(&app);
+    let transport = load_sidecar_transport(&app);
+    let api_token = get_sidecar_api_token(&app);
 
     if is_shutting_down(shutting_down.as_ref()) {
         return Err(sidecar_shutdown_error());
@@ -980,27 +2245,33 @@ async fn start_sidecar_internal(
     }
 
     if is_shutting_down(shutting_down.as_ref()) {
-        clear_sidecar_port(&port_ref);
+        clear_sidecar_endpoint(&endpoint_ref);
         return Err(sidecar_shutdown_error());
     }
 
+    let socket_path = resolve_sidecar_socket_path(transport, &app_support_dir);
+
     for attempt in 1..=SIDECAR_START_MAX_ATTEMPTS {
         if is_shutting_down(shutting_down.as_ref()) {
-            clear_sidecar_port(&port_ref);
+            clear_sidecar_endpoint(&endpoint_ref);
             return Err(sidecar_shutdown_error());
         }
 
-        let use_preferred_port = attempt == 1;
-        let port = if use_preferred_port {
-            PREFERRED_SIDECAR_PORT
-        } else {
-            pick_random_available_port()?
+        let is_first_attempt = attempt == 1;
+        let endpoint = match &socket_path {
+            Some(socket_path) => SidecarEndpoint::Unix(socket_path.clone()),
+            None if is_first_attempt => SidecarEndpoint::Tcp(PREFERRED_SIDECAR_PORT),
+            None => SidecarEndpoint::Tcp(pick_random_available_port()?),
         };
-        let startup_token = generate_sidecar_startup_token(attempt, port);
+        let startup_token = generate_sidecar_startup_token(attempt, &endpoint);
+        set_sidecar_status(&app, SidecarLifecycle::Spawning, |status| {
+            status.attempt = attempt;
+            status.port = endpoint.port();
+        });
 
         info!(
-            "Starting sidecar attempt {}/{} on port {}...",
-            attempt, SIDECAR_START_MAX_ATTEMPTS, port
+            "Starting sidecar attempt {}/{} on {}...",
+            attempt, SIDECAR_START_MAX_ATTEMPTS, endpoint
         );
 
         // Use shell plugin to start sidecar
@@ -1012,9 +2283,15 @@ async fn start_sidecar_internal(
                 error!("{}", err_msg);
                 err_msg
             })?
-            .env("SIDECAR_PORT", port.to_string())
             .env(SIDECAR_STARTUP_TOKEN_ENV_KEY, startup_token.clone())
-            .env(MINDFLAYER_APP_SUPPORT_DIR_ENV_KEY, app_support_dir.clone());
+            .env(MINDFLAYER_APP_SUPPORT_DIR_ENV_KEY, app_support_dir.clone())
+            .env(SIDECAR_API_TOKEN_ENV_KEY, api_token.clone());
+        let sidecar_command = match &endpoint {
+            SidecarEndpoint::Tcp(port) => sidecar_command.env("SIDECAR_PORT", port.to_string()),
+            SidecarEndpoint::Unix(socket_path) => {
+                sidecar_command.env(SIDECAR_SOCKET_PATH_ENV_KEY, socket_path)
+            }
+        };
         let sidecar_command = if let Some(proxy_url) = configured_proxy_url.as_deref() {
             info!("Using proxy from app settings for sidecar startup");
             sidecar_command.env(MINDFLAYER_PROXY_URL_ENV_KEY, proxy_url)
@@ -1022,16 +2299,16 @@ async fn start_sidecar_internal(
             sidecar_command
         };
 
-        debug!("Sidecar command created for port {}", port);
+        debug!("Sidecar command created for {}", endpoint);
 
         // Start process
         let (rx, child) = match sidecar_command.spawn() {
             Ok(result) => result,
             Err(e) => {
-                last_error = format!("Failed to spawn sidecar on port {}: {}", port, e);
+                last_error = format!("Failed to spawn sidecar on {}: {}", endpoint, e);
                 error!("{}", last_error);
-                if use_preferred_port {
-                    clear_sidecar_port(&port_ref);
+                if is_first_attempt {
+                    clear_sidecar_endpoint(&endpoint_ref);
                     return Err(last_error);
                 }
                 continue;
@@ -1042,7 +2319,7 @@ async fn start_sidecar_internal(
             if let Err(e) = child.kill() {
                 error!("Failed to kill sidecar during shutdown: {}", e);
             }
-            clear_sidecar_port(&port_ref);
+            clear_sidecar_endpoint(&endpoint_ref);
             return Err(sidecar_shutdown_error());
         }
 
@@ -1058,29 +2335,55 @@ async fn start_sidecar_internal(
                     error!("Failed to kill orphaned sidecar process: {}", kill_err);
                 }
                 error!("{}", last_error);
-                if use_preferred_port {
-                    clear_sidecar_port(&port_ref);
+                if is_first_attempt {
+                    clear_sidecar_endpoint(&endpoint_ref);
                     return Err(last_error);
                 }
                 continue;
             }
         }
 
-        let monitor = spawn_sidecar_event_monitor(rx, get_host_log_path(&app_support_dir));
+        let mut monitor = spawn_sidecar_event_monitor(
+            rx,
+            get_host_log_path(&app_support_dir),
+            Arc::clone(&app.state::<SidecarState>().pending_replies),
+        );
+        match app.state::<SidecarState>().terminated_rx.lock() {
+            Ok(mut guard) => *guard = Some(monitor.terminated_rx.clone()),
+            Err(e) => error!("Failed to store sidecar termination receiver: {}", e),
+        }
+        set_sidecar_status(&app, SidecarLifecycle::WaitingForHealth, |_| {});
 
         match wait_for_sidecar_ready(
-            port,
+            &endpoint,
             tokio::time::Duration::from_millis(SIDECAR_HEALTH_CHECK_TIMEOUT_MS),
-            tokio::time::Duration::from_millis(SIDECAR_HEALTH_CHECK_INTERVAL_MS),
-            monitor.terminated_rx,
-            startup_token,
+            &mut monitor,
+            startup_token.clone(),
             Arc::clone(&shutting_down),
         )
         .await
         {
             Ok(()) => {
-                set_sidecar_port(&port_ref, port);
-                return Ok(port);
+                let protocol_version = match negotiate_sidecar_protocol(&app).await {
+                    Ok(protocol_version) => protocol_version,
+                    Err(e) => {
+                        error!("{}", e);
+                        kill_sidecar_process(&child_ref);
+                        clear_sidecar_endpoint(&endpoint_ref);
+                        return Err(e);
+                    }
+                };
+                set_sidecar_endpoint(&endpoint_ref, endpoint.clone());
+                set_sidecar_status(&app, SidecarLifecycle::Healthy, |status| {
+                    status.last_error = None;
+                    status.healthy_since = Some(current_timestamp_millis());
+                    status.protocol_version = Some(protocol_version);
+                });
+                return Ok(RunningSidecar {
+                    endpoint,
+                    startup_token,
+                    terminated_rx: monitor.terminated_rx,
+                });
             }
             Err(attempt_error) => {
                 kill_sidecar_process(&child_ref);
@@ -1088,19 +2391,19 @@ async fn start_sidecar_internal(
                     .await;
 
                 if is_shutting_down(shutting_down.as_ref()) {
-                    clear_sidecar_port(&port_ref);
+                    clear_sidecar_endpoint(&endpoint_ref);
                     return Err(sidecar_shutdown_error());
                 }
 
                 let stderr_output = snapshot_stderr_output(&monitor.stderr_output);
                 let failure_kind = classify_startup_failure(&stderr_output);
-                last_error = format_attempt_failure(port, &attempt_error, &stderr_output);
+                last_error = format_attempt_failure(&endpoint, &attempt_error, &stderr_output);
                 warn!(
                     "Sidecar failed to become healthy on attempt {}/{}: {}",
                     attempt, SIDECAR_START_MAX_ATTEMPTS, last_error
                 );
 
-                if should_fallback_to_random_port(attempt, failure_kind) {
+                if socket_path.is_none() && should_fallback_to_random_port(attempt, failure_kind) {
                     info!(
                         "Preferred sidecar port {} is already in use, falling back to random port",
                         PREFERRED_SIDECAR_PORT
@@ -1108,52 +2411,268 @@ async fn start_sidecar_internal(
                     continue;
                 }
 
-                if use_preferred_port {
-                    clear_sidecar_port(&port_ref);
+                if is_first_attempt {
+                    clear_sidecar_endpoint(&endpoint_ref);
                     return Err(last_error);
                 }
             }
         }
     }
 
-    clear_sidecar_port(&port_ref);
+    clear_sidecar_endpoint(&endpoint_ref);
     Err(last_error)
 }
 
-pub async fn wait_for_sidecar_port(
+/// Wait until the sidecar passed its health check and return the origin of its API. Fails as
+/// soon as the sidecar crashed for good or was stopped, with the error that caused it.
+pub async fn wait_for_sidecar_origin(
     app: tauri::AppHandle,
     timeout_ms: Option<u64>,
-) -> Result<u16, String> {
-    let timeout_ms = timeout_ms.unwrap_or(SIDECAR_PORT_WAIT_TIMEOUT_MS);
-    let timeout = tokio::time::Duration::from_millis(timeout_ms);
-    let poll_interval = tokio::time::Duration::from_millis(SIDECAR_PORT_WAIT_INTERVAL_MS);
-    let started_at = tokio::time::Instant::now();
+) -> Result<String, String> {
+    let timeout_ms = timeout_ms.unwrap_or(SIDECAR_ORIGIN_WAIT_TIMEOUT_MS);
+    let (mut endpoint_rx, mut status_rx) = {
+        let state = app.state::<SidecarState>();
+        (state.endpoint.subscribe(), state.status.subscribe())
+    };
+
+    tokio::time::timeout(
+        tokio::time::Duration::from_millis(timeout_ms),
+        wait_for_sidecar_endpoint(&mut endpoint_rx, &mut status_rx),
+    )
+    .await
+    .unwrap_or_else(|_| {
+        Err(format!(
+            "Timed out waiting for sidecar after {}ms",
+            timeout_ms
+        ))
+    })
+    .map(|endpoint| endpoint.origin())
+}
 
+/// Wait for the endpoint of a running sidecar, or the error that keeps it from running
+async fn wait_for_sidecar_endpoint(
+    endpoint_rx: &mut tokio::sync::watch::Receiver<Option<SidecarEndpoint>>,
+    status_rx: &mut tokio::sync::watch::Receiver<SidecarStatus>,
+) -> Result<SidecarEndpoint, String> {
     loop {
-        let sidecar_port = {
-            let state = app.state::<SidecarState>();
-            let guard = state
-                .port
-                .lock()
-                .map_err(|e| format!("Failed to acquire sidecar port lock: {}", e))?;
-            *guard
+        if let Some(endpoint) = endpoint_rx.borrow_and_update().clone() {
+            return Ok(endpoint);
+        }
+        if let Some(error) = sidecar_failure_error(&status_rx.borrow_and_update()) {
+            return Err(error);
+        }
+
+        let changed = tokio::select! {
+            changed = endpoint_rx.changed() => changed,
+            changed = status_rx.changed() => changed,
         };
+        changed.map_err(|_| "Sidecar state was dropped".to_string())?;
+    }
+}
 
-        if let Some(port) = sidecar_port {
-            return Ok(port);
+/// Why a sidecar in `status` will not become ready without being started again
+fn sidecar_failure_error(status: &SidecarStatus) -> Option<String> {
+    match status.state {
+        SidecarLifecycle::Crashed => Some(
+            status
+                .last_error
+                .clone()
+                .unwrap_or_else(|| "Sidecar stopped unexpectedly".to_string()),
+        ),
+        SidecarLifecycle::Stopped => Some(SIDECAR_NOT_RUNNING_MESSAGE.to_string()),
+        _ => None,
+    }
+}
+
+/// Answer a `sidecar://` request from the webview by forwarding it to the sidecar socket.
+/// Only used for resources loaded by URL; `fetch()` goes through
+/// [`start_sidecar_socket_request`] so responses are streamed and can be aborted
+pub async fn proxy_sidecar_request(
+    app: &tauri::AppHandle,
+    request: tauri::http::Request<Vec<u8>>,
+) -> tauri::http::Response<Vec<u8>> {
+    let endpoint = app.state::<SidecarState>().endpoint.borrow().clone();
+    let result = match endpoint {
+        #[cfg(unix)]
+        Some(SidecarEndpoint::Unix(socket_path)) => {
+            unix_socket::proxy_request(&socket_path, request).await
         }
+        #[cfg(not(unix))]
+        Some(SidecarEndpoint::Unix(_)) => Err(unix_socket_unsupported_error()),
+        Some(SidecarEndpoint::Tcp(_)) | None => {
+            Err("Sidecar is not listening on a Unix socket".to_string())
+        }
+    };
 
-        if started_at.elapsed() >= timeout {
-            return Err(format!(
-                "Timed out waiting for sidecar port after {}ms",
-                timeout_ms
-            ));
+    result.unwrap_or_else(|e| {
+        warn!("Failed to proxy sidecar request: {}", e);
+        let body = serde_json::json!({ "error": e, "code": "SIDECAR_UNAVAILABLE" });
+        tauri::http::Response::builder()
+            .status(tauri::http::StatusCode::BAD_GATEWAY)
+            .header(tauri::http::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string().into_bytes())
+            .unwrap_or_default()
+    })
+}
+
+/// `fetch()` from the webview to a sidecar listening on a Unix socket
+#[derive(Debug, Deserialize)]
+pub struct SidecarSocketRequest {
+    method: String,
+    /// Path and query of the sidecar API route
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+}
+
+/// Progress of a socket request, sent on its channel around the raw body chunks
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+enum SidecarSocketEvent {
+    Response {
+        status: u16,
+        headers: Vec<(String, String)>,
+    },
+    End,
+    Error {
+        message: String,
+    },
+}
+
+/// Send `request` to the sidecar socket and stream the response over `channel`: a `response`
+/// event with the status and headers, the body as raw chunks, then `end` or `error`.
+/// Returns the ID to pass to [`abort_sidecar_socket_request`]
+pub fn start_sidecar_socket_request(
+    app: &tauri::AppHandle,
+    request: SidecarSocketRequest,
+    channel: tauri::ipc::Channel<tauri::ipc::InvokeResponseBody>,
+) -> Result<u64, String> {
+    let state = app.state::<SidecarState>();
+    let socket_path = match state.endpoint.borrow().clone() {
+        Some(SidecarEndpoint::Unix(socket_path)) => socket_path,
+        Some(SidecarEndpoint::Tcp(_)) | None => {
+            return Err("Sidecar is not listening on a Unix socket".to_string())
+        }
+    };
+    let mut builder = tauri::http::Request::builder()
+        .method(request.method.as_str())
+        .uri(request.path.as_str());
+    for (name, value) in &request.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    let request = builder
+        .body(request.body.unwrap_or_default().into_bytes())
+        .map_err(|e| format!("Invalid sidecar request: {}", e))?;
+
+    let request_id = state.socket_request_id.fetch_add(1, Ordering::SeqCst) + 1;
+    let socket_requests = Arc::clone(&state.socket_requests);
+    // Held until the task is registered, so a request that finishes right away cannot
+    // remove its entry before it was added
+    let mut requests = state
+        .socket_requests
+        .lock()
+        .map_err(|e| format!("Failed to lock sidecar socket requests: {}", e))?;
+    let task = tauri::async_runtime::spawn(async move {
+        stream_sidecar_socket_response(&socket_path, request, &channel).await;
+        if let Ok(mut requests) = socket_requests.lock() {
+            requests.remove(&request_id);
+        }
+    });
+    requests.insert(request_id, task);
+    Ok(request_id)
+}
+
+/// Cancel a request started with [`start_sidecar_socket_request`]. Closing its connection
+/// aborts the request in the sidecar, e.g. the model call behind a chat stream.
+pub fn abort_sidecar_socket_request(app: &tauri::AppHandle, request_id: u64) {
+    let task = app
+        .state::<SidecarState>()
+        .socket_requests
+        .lock()
+        .ok()
+        .and_then(|mut requests| requests.remove(&request_id));
+    if let Some(task) = task {
+        debug!("Aborting sidecar socket request {}", request_id);
+        task.abort();
+    }
+}
+
+#[cfg(unix)]
+async fn stream_sidecar_socket_response(
+    socket_path: &Path,
+    request: tauri::http::Request<Vec<u8>>,
+    channel: &tauri::ipc::Channel<tauri::ipc::InvokeResponseBody>,
+) {
+    let send_event = |event: SidecarSocketEvent| {
+        let event = serde_json::to_string(&event).map_err(|e| e.to_string())?;
+        channel
+            .send(tauri::ipc::InvokeResponseBody::Json(event))
+            .map_err(|e| e.to_string())
+    };
+
+    let mut response = match unix_socket::stream_request(socket_path, request).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Failed to proxy sidecar request: {}", e);
+            let _ = send_event(SidecarSocketEvent::Error { message: e });
+            return;
+        }
+    };
+    let headers = response
+        .response()
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let head = SidecarSocketEvent::Response {
+        status: response.response().status().as_u16(),
+        headers,
+    };
+    if send_event(head).is_err() {
+        return;
+    }
+
+    loop {
+        let sent = match response.next_chunk().await {
+            Some(Ok(chunk)) => channel
+                .send(tauri::ipc::InvokeResponseBody::Raw(chunk.to_vec()))
+                .map_err(|e| e.to_string()),
+            Some(Err(e)) => {
+                let _ = send_event(SidecarSocketEvent::Error { message: e });
+                return;
+            }
+            None => {
+                let _ = send_event(SidecarSocketEvent::End);
+                return;
+            }
+        };
+        // The webview is gone; dropping the response closes the connection
+        if let Err(e) = sent {
+            debug!("Stopped streaming sidecar response: {}", e);
+            return;
         }
+    }
+}
 
-        tokio::time::sleep(poll_interval).await;
+#[cfg(not(unix))]
+async fn stream_sidecar_socket_response(
+    _socket_path: &Path,
+    _request: tauri::http::Request<Vec<u8>>,
+    channel: &tauri::ipc::Channel<tauri::ipc::InvokeResponseBody>,
+) {
+    let event = SidecarSocketEvent::Error {
+        message: unix_socket_unsupported_error(),
+    };
+    if let Ok(event) = serde_json::to_string(&event) {
+        let _ = channel.send(tauri::ipc::InvokeResponseBody::Json(event));
     }
 }
 
+#[cfg(not(unix))]
+fn unix_socket_unsupported_error() -> String {
+    "Unix socket sidecar transport is not supported on this platform".to_string()
+}
+
 /// Cleanup function: gracefully shutdown sidecar
 pub async fn cleanup_sidecar(app: tauri::AppHandle) {
     let state = app.state::<SidecarState>();
@@ -1162,14 +2681,18 @@ pub async fn cleanup_sidecar(app: tauri::AppHandle) {
     let _startup_guard = state.startup_lock.lock().await;
 
     info!("Cleaning up sidecar...");
-
-    let port_to_cleanup = match state.port.lock() {
-        Ok(mut guard) => guard.take(),
-        Err(e) => {
-            error!("Failed to acquire sidecar port lock: {}", e);
-            None
+    if let Ok(mut guard) = state.supervisor.lock() {
+        if let Some(supervisor) = guard.take() {
+            supervisor.abort();
         }
-    };
+    }
+    // Lets in-flight chat streams report why they end before the process is terminated
+    send_sidecar_shutdown_message(&state, SIDECAR_APP_EXIT_REASON);
+
+    let port_to_cleanup = state
+        .endpoint
+        .send_replace(None)
+        .and_then(|endpoint| endpoint.port());
 
     // Kill the sidecar process (sends SIGTERM, which triggers graceful shutdown)
     let (sidecar_pid, sidecar_terminated) = if let Ok(mut guard) = state.child.lock() {
@@ -1213,6 +2736,10 @@ pub async fn cleanup_sidecar(app: tauri::AppHandle) {
         }
     }
 
+    set_sidecar_status(&app, SidecarLifecycle::Stopped, |status| {
+        status.port = None;
+        status.healthy_since = None;
+    });
     info!("Sidecar cleanup completed");
 }
 
@@ -1328,6 +2855,88 @@ mod tests {
         assert!(!is_addr_in_use_error(stderr));
     }
 
+    #[test]
+    fn restart_delay_backs_off_exponentially_up_to_cap() {
+        assert_eq!(sidecar_restart_delay(1), Duration::from_millis(500));
+        assert_eq!(sidecar_restart_delay(2), Duration::from_millis(1_000));
+        assert_eq!(sidecar_restart_delay(4), Duration::from_millis(4_000));
+        assert_eq!(
+            sidecar_restart_delay(10),
+            Duration::from_millis(SIDECAR_RESTART_MAX_DELAY_MS)
+        );
+        assert_eq!(
+            sidecar_restart_delay(u32::MAX),
+            Duration::from_millis(SIDECAR_RESTART_MAX_DELAY_MS)
+        );
+    }
+
+    #[test]
+    fn restart_count_resets_only_after_the_sidecar_stayed_healthy() {
+        let stable = Duration::from_millis(SIDECAR_STABLE_UPTIME_MS);
+        let started_at = tokio::time::Instant::now();
+
+        let mut stayed_up = SidecarUptime::started(started_at);
+        stayed_up.record_check(true, started_at + stable);
+        stayed_up.record_check(false, started_at + stable * 2);
+        assert_eq!(next_sidecar_restart(4, Some(&stayed_up)), Some(1));
+
+        // Up for a long time, but it stopped answering right after it started
+        let mut hung = SidecarUptime::started(started_at);
+        hung.record_check(false, started_at + Duration::from_secs(5));
+        hung.record_exit(started_at + stable * 2);
+        assert_eq!(next_sidecar_restart(4, Some(&hung)), Some(5));
+        assert_eq!(
+            next_sidecar_restart(SIDECAR_MAX_CONSECUTIVE_RESTARTS, Some(&hung)),
+            None
+        );
+
+        let mut crashed = SidecarUptime::started(started_at);
+        crashed.record_exit(started_at + stable);
+        assert_eq!(next_sidecar_restart(4, Some(&crashed)), Some(1));
+        assert_eq!(next_sidecar_restart(4, None), Some(5));
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_a_sidecar_that_never_answers_health_checks() {
+        // Connections are queued by the OS but never accepted or answered
+        let listener = TcpListener::bind("127.0.0.1:0").expect("listener should bind");
+        let port = listener.local_addr().expect("listener address").port();
+        let health_target = SidecarHealthTarget::new(&SidecarEndpoint::Tcp(port))
+            .expect("health target should be created");
+        let shutting_down = AtomicBool::new(false);
+        let mut consecutive_restarts = 0;
+        let mut kills = 0;
+
+        loop {
+            let mut uptime = SidecarUptime::started(tokio::time::Instant::now());
+            let mut failed_checks = 0;
+            let reason = check_sidecar_liveness(
+                &health_target,
+                "startup-token",
+                Duration::from_millis(10),
+                &shutting_down,
+                &mut uptime,
+                |result, consecutive_failures| {
+                    assert!(result.is_err());
+                    failed_checks += 1;
+                    assert_eq!(consecutive_failures, failed_checks);
+                },
+            )
+            .await;
+            assert_eq!(failed_checks, SIDECAR_WATCHDOG_RESTART_AFTER_FAILURES);
+            assert!(reason.contains("did not respond"), "{}", reason);
+
+            kills += 1;
+            match next_sidecar_restart(consecutive_restarts, Some(&uptime)) {
+                Some(restart) => consecutive_restarts = restart,
+                None => break,
+            }
+        }
+
+        assert_eq!(kills, SIDECAR_MAX_CONSECUTIVE_RESTARTS + 1);
+        drop(listener);
+    }
+
     #[test]
     fn fallback_policy_only_allows_first_attempt_addr_in_use() {
         assert!(should_fallback_to_random_port(
@@ -1367,6 +2976,17 @@ mod tests {
         assert!(!is_expected_health_payload(&payload, "token-1"));
     }
 
+    #[test]
+    fn api_tokens_are_random_and_url_safe() {
+        let token = generate_sidecar_api_token();
+
+        assert_eq!(token.len(), 43);
+        assert!(token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_ne!(token, generate_sidecar_api_token());
+    }
+
     #[test]
     fn parses_proxy_url_from_settings_store() {
         let settings_json = r#"{"theme":"system","proxyUrl":"localhost:7897"}"#;
@@ -1400,6 +3020,127 @@ mod tests {
         );
     }
 
+    #[test]
+    fn reads_watchdog_interval_from_settings_store() {
+        let interval = |settings_json: &str| {
+            parse_sidecar_watchdog_interval_from_settings_json(settings_json)
+                .expect("settings should parse")
+        };
+
+        assert_eq!(
+            interval(r#"{"sidecarHealthCheckIntervalMs":10000}"#),
+            Duration::from_millis(10_000)
+        );
+        assert_eq!(
+            interval(r#"{"theme":"system"}"#),
+            Duration::from_millis(DEFAULT_SIDECAR_WATCHDOG_INTERVAL_MS)
+        );
+        assert_eq!(
+            interval(r#"{"sidecarHealthCheckIntervalMs":0}"#),
+            Duration::from_millis(SIDECAR_WATCHDOG_MIN_INTERVAL_MS)
+        );
+    }
+
+    fn sidecar_status(state: SidecarLifecycle, last_error: Option<&str>) -> SidecarStatus {
+        SidecarStatus {
+            state,
+            attempt: 1,
+            restarts: 0,
+            port: None,
+            last_error: last_error.map(str::to_string),
+            changed_at: 0,
+            healthy_since: None,
+            watchdog: SidecarWatchdogMetrics::default(),
+            last_applied_config_seq: None,
+            protocol_version: None,
+            degraded_reason: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn endpoint_waiters_wake_up_when_the_sidecar_becomes_healthy() {
+        let endpoint_tx = tokio::sync::watch::Sender::new(None);
+        let status_tx =
+            tokio::sync::watch::Sender::new(sidecar_status(SidecarLifecycle::Spawning, None));
+        let mut endpoint_rx = endpoint_tx.subscribe();
+        let mut status_rx = status_tx.subscribe();
+
+        let waiter = tokio::spawn(async move {
+            wait_for_sidecar_endpoint(&mut endpoint_rx, &mut status_rx).await
+        });
+        tokio::task::yield_now().await;
+        endpoint_tx.send_replace(Some(SidecarEndpoint::Tcp(PREFERRED_SIDECAR_PORT)));
+
+        assert_eq!(
+            waiter.await.expect("waiter should finish"),
+            Ok(SidecarEndpoint::Tcp(PREFERRED_SIDECAR_PORT))
+        );
+    }
+
+    #[tokio::test]
+    async fn endpoint_waiters_fail_with_the_startup_error() {
+        let endpoint_tx = tokio::sync::watch::Sender::new(None);
+        let status_tx = tokio::sync::watch::Sender::new(sidecar_status(
+            SidecarLifecycle::WaitingForHealth,
+            None,
+        ));
+        let mut endpoint_rx = endpoint_tx.subscribe();
+        let mut status_rx = status_tx.subscribe();
+
+        let waiter = tokio::spawn(async move {
+            wait_for_sidecar_endpoint(&mut endpoint_rx, &mut status_rx).await
+        });
+        tokio::task::yield_now().await;
+        status_tx.send_replace(sidecar_status(
+            SidecarLifecycle::Crashed,
+            Some("Sidecar health check timed out"),
+        ));
+
+        assert_eq!(
+            waiter.await.expect("waiter should finish"),
+            Err("Sidecar health check timed out".to_string())
+        );
+        assert_eq!(
+            sidecar_failure_error(&sidecar_status(SidecarLifecycle::Restarting, Some("exit"))),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_sidecar_transport_from_settings_store() {
+        let transport =
+            |settings_json: &str| parse_sidecar_transport_from_settings_json(settings_json);
+
+        assert_eq!(
+            transport(r#"{"theme":"system"}"#),
+            Ok(SidecarTransport::Tcp)
+        );
+        assert_eq!(
+            transport(r#"{"sidecarTransport":"unix"}"#),
+            Ok(SidecarTransport::Unix)
+        );
+        assert!(transport(r#"{"sidecarTransport":"pipe"}"#).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn places_sidecar_socket_in_app_support_dir_unless_path_is_too_long() {
+        assert_eq!(
+            resolve_sidecar_socket_path(SidecarTransport::Unix, "/tmp/mind-flayer"),
+            Some(PathBuf::from("/tmp/mind-flayer/sidecar.sock"))
+        );
+        assert_eq!(
+            resolve_sidecar_socket_path(SidecarTransport::Tcp, "/tmp/mind-flayer"),
+            None
+        );
+
+        let long_dir = format!("/tmp/{}", "a".repeat(SIDECAR_SOCKET_PATH_MAX_BYTES));
+        assert_eq!(
+            resolve_sidecar_socket_path(SidecarTransport::Unix, &long_dir),
+            None
+        );
+    }
+
     #[test]
     fn installs_bundled_smoke_test_skill_when_missing() {
         let app_support_dir = create_temp_dir("mind-flayer-bundled-skill-install");
@@ -1720,4 +3461,66 @@ mod tests {
 
         let _ = fs::remove_dir_all(app_support_dir);
     }
+
+    #[test]
+    fn host_log_never_contains_configured_keys() {
+        let app_support_dir = create_temp_dir("mind-flayer-host-log-redaction");
+        let host_log_path = get_host_log_path(
+            app_support_dir
+                .to_str()
+                .expect("temp dir should be valid utf-8"),
+        );
+        crate::redact::register_secrets(["team-gateway-key-9a8b7c", "unprefixed0secret1value"]);
+
+        // What the sidecar prints when it echoes a config update at debug level
+        let echoed_config = serde_json::json!({
+            "type": "config_update",
+            "configs": {
+                "openai": {
+                    "apiKey": "sk-proj-abcdefghijklmnop1234",
+                    "credentials": [{"label": "custom", "apiKey": "unprefixed0secret1value"}],
+                    "extraHeaders": {"X-Gateway-Key": "team-gateway-key-9a8b7c"}
+                }
+            }
+        });
+        append_host_log_line(
+            &host_log_path,
+            "STDOUT",
+            &format!("[sidecar] Received stdin data: {}", echoed_config),
+        );
+        append_host_log_line(
+            &host_log_path,
+            "STDERR",
+            "401 for key unprefixed0secret1value",
+        );
+
+        // Too short to register and under a header name no pattern knows
+        let config_update = HostMessage::ConfigUpdate {
+            id: 4,
+            seq: 2,
+            configs: serde_json::from_value(serde_json::json!({
+                "openai": {"extraHeaders": {"X-Team": "t3am42"}}
+            }))
+            .expect("configs should be a JSON object"),
+            locked: false,
+        };
+        append_host_log_line(
+            &host_log_path,
+            "DEBUG",
+            &format!("Writing sidecar message: {}", config_update.log_summary()),
+        );
+
+        let host_log = fs::read_to_string(&host_log_path).expect("host log should be readable");
+        assert!(host_log.contains("[REDACTED]"));
+        for secret in [
+            "sk-proj-abcdefghijklmnop1234",
+            "unprefixed0secret1value",
+            "team-gateway-key-9a8b7c",
+            "t3am42",
+        ] {
+            assert!(!host_log.contains(secret), "host.log leaked {}", secret);
+        }
+
+        let _ = fs::remove_dir_all(app_support_dir);
+    }
 }
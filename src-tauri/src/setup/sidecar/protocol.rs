@@ -0,0 +1,270 @@
+//! JSON-lines protocol between the host and the sidecar.
+//!
+//! The host writes [`HostMessage`]s to the sidecar stdin, the sidecar prints
+//! [`SidecarMessage`]s to its stdout, one JSON object per line. Requests carry an `id` that
+//! the sidecar echoes in its reply. Anything else the sidecar prints is plain console output.
+
+use serde::{Deserialize, Serialize};
+
+/// Protocol version spoken by this host
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest sidecar protocol version this host still understands
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+const INCOMPATIBLE_PROTOCOL_PREFIX: &str = "Incompatible sidecar protocol";
+
+/// Messages the host writes to the sidecar stdin
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum HostMessage {
+    /// First message after startup, answered with the version the sidecar picked
+    Hello {
+        id: u64,
+        protocol_version: u32,
+        min_protocol_version: u32,
+    },
+    /// Provider configs, applied in `seq` order and acknowledged with `config_ack`
+    ConfigUpdate {
+        id: u64,
+        seq: u64,
+        configs: serde_json::Map<String, serde_json::Value>,
+        locked: bool,
+    },
+    /// Finish in-flight requests with `reason` and exit
+    Shutdown {
+        id: u64,
+        reason: String,
+    },
+    Ping {
+        id: u64,
+    },
+}
+
+impl HostMessage {
+    /// Type, ID and sequence number, safe to log. The encoded line is never logged because a
+    /// config update carries every key and header value.
+    pub fn log_summary(&self) -> String {
+        match self {
+            Self::Hello { id, .. } => format!("hello (id {})", id),
+            Self::ConfigUpdate { id, seq, .. } => format!("config_update (id {}, seq {})", id, seq),
+            Self::Shutdown { id, .. } => format!("shutdown (id {})", id),
+            Self::Ping { id } => format!("ping (id {})", id),
+        }
+    }
+}
+
+/// Messages the sidecar prints to its stdout
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum SidecarMessage {
+    Hello {
+        id: u64,
+        protocol_version: u32,
+    },
+    ConfigAck {
+        id: u64,
+        seq: u64,
+        /// Why the sidecar could not apply the update
+        #[serde(default)]
+        error: Option<String>,
+    },
+    Pong {
+        id: u64,
+    },
+    /// Log record the host writes to its own log
+    Log {
+        level: SidecarLogLevel,
+        message: String,
+    },
+    /// Lifecycle event of the sidecar server
+    Status {
+        event: SidecarStatusEvent,
+        #[serde(default)]
+        message: Option<String>,
+    },
+    /// Failed request, or a failure unrelated to a request when `id` is missing
+    Error {
+        #[serde(default)]
+        id: Option<u64>,
+        code: ProtocolErrorCode,
+        message: String,
+    },
+}
+
+impl SidecarMessage {
+    /// ID of the host request this message answers
+    pub fn reply_id(&self) -> Option<u64> {
+        match self {
+            Self::Hello { id, .. }
+            | Self::ConfigAck { id, .. }
+            | Self::Pong { id }
+            | Self::Error { id: Some(id), .. } => Some(*id),
+            Self::Log { .. } | Self::Status { .. } | Self::Error { id: None, .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarLogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<SidecarLogLevel> for log::Level {
+    fn from(level: SidecarLogLevel) -> Self {
+        match level {
+            SidecarLogLevel::Debug => log::Level::Debug,
+            SidecarLogLevel::Info => log::Level::Info,
+            SidecarLogLevel::Warn => log::Level::Warn,
+            SidecarLogLevel::Error => log::Level::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SidecarStatusEvent {
+    Listening,
+    ShuttingDown,
+    ServerClosed,
+    Exiting,
+    /// Event added by a newer sidecar
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtocolErrorCode {
+    IncompatibleProtocol,
+    InvalidMessage,
+    UnknownMessage,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Serialize `message` as a single line for the sidecar stdin
+pub fn encode_host_message(message: &HostMessage) -> Result<String, String> {
+    serde_json::to_string(message)
+        .map(|line| format!("{}\n", line))
+        .map_err(|e| format!("Failed to serialize sidecar message: {}", e))
+}
+
+/// Parse a line of sidecar stdout, or `None` for plain console output
+pub fn parse_sidecar_message(line: &str) -> Option<SidecarMessage> {
+    let trimmed = line.trim();
+    if !trimmed.starts_with('{') {
+        return None;
+    }
+    serde_json::from_str(trimmed).ok()
+}
+
+/// Check the version the sidecar picked in its `hello` reply
+pub fn check_protocol_version(sidecar_version: u32) -> Result<u32, String> {
+    if (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&sidecar_version) {
+        Ok(sidecar_version)
+    } else {
+        Err(incompatible_protocol_error(&format!(
+            "the sidecar speaks v{}, this app supports v{} to v{}",
+            sidecar_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+        )))
+    }
+}
+
+pub fn incompatible_protocol_error(detail: &str) -> String {
+    format!("{}: {}", INCOMPATIBLE_PROTOCOL_PREFIX, detail)
+}
+
+/// Restarting a sidecar that does not speak our protocol cannot succeed
+pub fn is_incompatible_protocol_error(error: &str) -> bool {
+    error.starts_with(INCOMPATIBLE_PROTOCOL_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_host_messages_as_tagged_json_lines() {
+        let line = encode_host_message(&HostMessage::Hello {
+            id: 1,
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+        })
+        .expect("hello should serialize");
+        assert!(line.ends_with('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line).expect("line should be JSON"),
+            serde_json::json!({
+                "type": "hello",
+                "id": 1,
+                "protocolVersion": PROTOCOL_VERSION,
+                "minProtocolVersion": MIN_PROTOCOL_VERSION
+            })
+        );
+
+        let line = encode_host_message(&HostMessage::Shutdown {
+            id: 2,
+            reason: "The app is quitting".to_string(),
+        })
+        .expect("shutdown should serialize");
+        assert_eq!(
+            line,
+            "{\"type\":\"shutdown\",\"id\":2,\"reason\":\"The app is quitting\"}\n"
+        );
+    }
+
+    #[test]
+    fn parses_sidecar_messages_and_ignores_console_output() {
+        assert_eq!(
+            parse_sidecar_message(r#"{"type":"config_ack","id":3,"seq":7}"#),
+            Some(SidecarMessage::ConfigAck {
+                id: 3,
+                seq: 7,
+                error: None
+            })
+        );
+        assert_eq!(
+            parse_sidecar_message(r#"{"type":"status","event":"restarting_soon"}"#),
+            Some(SidecarMessage::Status {
+                event: SidecarStatusEvent::Unknown,
+                message: None
+            })
+        );
+        let error = parse_sidecar_message(
+            r#"{"type":"error","code":"invalid_message","message":"bad JSON"}"#,
+        )
+        .expect("error should parse");
+        assert_eq!(error.reply_id(), None);
+        assert_eq!(parse_sidecar_message(r#"{"type":"config_ack"}"#), None);
+        assert_eq!(
+            parse_sidecar_message("Sidecar running on http://localhost:3737"),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_sidecar_protocol_versions_outside_supported_range() {
+        assert_eq!(
+            check_protocol_version(PROTOCOL_VERSION),
+            Ok(PROTOCOL_VERSION)
+        );
+
+        let error = check_protocol_version(PROTOCOL_VERSION + 1).unwrap_err();
+        assert!(is_incompatible_protocol_error(&error));
+        assert!(!is_incompatible_protocol_error(
+            "Sidecar process not running"
+        ));
+    }
+}
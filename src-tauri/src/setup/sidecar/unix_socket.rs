@@ -0,0 +1,294 @@
+//! HTTP over the Unix domain socket the sidecar listens on with the `unix` transport.
+//!
+//! The webview cannot open a socket itself, so the host forwards its sidecar requests here.
+//! `fetch()` calls go through [`stream_request`], whose body is passed on chunk by chunk so
+//! streamed chat output arrives as it is generated. Resources loaded by URL, such as images,
+//! use the `sidecar://` URI scheme and [`proxy_request`], which reads the whole response
+//! because a URI scheme handler can only answer with a complete body.
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper_util::rt::TokioIo;
+use log::debug;
+use std::path::Path;
+use tauri::http::{header, HeaderMap, HeaderValue, Request, Response, Version};
+
+/// Headers that only describe one connection and must not be forwarded
+const HOP_BY_HOP_HEADERS: [&str; 4] = ["connection", "keep-alive", "transfer-encoding", "upgrade"];
+
+/// Response whose body is still being received. Dropping it closes the connection, so the
+/// sidecar sees the request as aborted.
+pub struct StreamingResponse {
+    response: Response<Incoming>,
+    connection: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl StreamingResponse {
+    pub fn response(&self) -> &Response<Incoming> {
+        &self.response
+    }
+
+    /// Next chunk of the body, `None` once it is complete
+    pub async fn next_chunk(&mut self) -> Option<Result<Bytes, String>> {
+        loop {
+            match self.response.body_mut().frame().await? {
+                Ok(frame) => {
+                    if let Ok(data) = frame.into_data() {
+                        return Some(Ok(data));
+                    }
+                }
+                Err(e) => {
+                    return Some(Err(format!(
+                        "Failed to read sidecar socket response: {}",
+                        e
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for StreamingResponse {
+    fn drop(&mut self) {
+        self.connection.abort();
+    }
+}
+
+/// Send `request` over a new connection to `socket_path` and return once the response
+/// head arrived
+async fn open_request(
+    socket_path: &Path,
+    request: Request<Vec<u8>>,
+) -> Result<StreamingResponse, String> {
+    let stream = tokio::net::UnixStream::connect(socket_path)
+        .await
+        .map_err(|e| {
+            format!(
+                "Failed to connect to sidecar socket '{}': {}",
+                socket_path.display(),
+                e
+            )
+        })?;
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| format!("Failed to open HTTP connection to sidecar socket: {}", e))?;
+    let connection = tauri::async_runtime::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!("Sidecar socket connection closed with error: {}", e);
+        }
+    });
+
+    let (parts, body) = request.into_parts();
+    let response = sender
+        .send_request(Request::from_parts(parts, Full::new(Bytes::from(body))))
+        .await;
+    match response {
+        Ok(response) => Ok(StreamingResponse {
+            response,
+            connection,
+        }),
+        Err(e) => {
+            connection.abort();
+            Err(format!("Sidecar socket request failed: {}", e))
+        }
+    }
+}
+
+/// Send `request` over a new connection to `socket_path` and read the whole response
+async fn send_request(
+    socket_path: &Path,
+    request: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, String> {
+    let mut response = open_request(socket_path, request).await?;
+    let mut body = Vec::new();
+    while let Some(chunk) = response.next_chunk().await {
+        body.extend_from_slice(&chunk?);
+    }
+    let mut buffered = Response::new(body);
+    *buffered.status_mut() = response.response.status();
+    *buffered.version_mut() = response.response.version();
+    *buffered.headers_mut() = response.response.headers().clone();
+    Ok(buffered)
+}
+
+/// `GET path` from the sidecar listening on `socket_path`
+pub async fn get(socket_path: &Path, path: &str) -> Result<Response<Vec<u8>>, String> {
+    let request = Request::get(path)
+        .header(header::HOST, "localhost")
+        .body(Vec::new())
+        .map_err(|e| format!("Failed to build sidecar socket request: {}", e))?;
+    send_request(socket_path, request).await
+}
+
+/// Forward a `sidecar://` request from the webview to the sidecar listening on `socket_path`
+pub async fn proxy_request(
+    socket_path: &Path,
+    request: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, String> {
+    let mut response = send_request(socket_path, prepare_request(request)?).await?;
+    remove_hop_by_hop_headers(response.headers_mut());
+    Ok(response)
+}
+
+/// Forward a webview `fetch()` to the sidecar listening on `socket_path` without waiting
+/// for the response body
+pub async fn stream_request(
+    socket_path: &Path,
+    request: Request<Vec<u8>>,
+) -> Result<StreamingResponse, String> {
+    let mut response = open_request(socket_path, prepare_request(request)?).await?;
+    remove_hop_by_hop_headers(response.response.headers_mut());
+    Ok(response)
+}
+
+/// Turn a webview request into one for the sidecar: origin-form URI, HTTP/1.1 and no
+/// connection-specific headers
+fn prepare_request(request: Request<Vec<u8>>) -> Result<Request<Vec<u8>>, String> {
+    let (mut parts, body) = request.into_parts();
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+    parts.uri = path_and_query
+        .parse()
+        .map_err(|e| format!("Invalid sidecar request path '{}': {}", path_and_query, e))?;
+    parts.version = Version::HTTP_11;
+    remove_hop_by_hop_headers(&mut parts.headers);
+    parts
+        .headers
+        .insert(header::HOST, HeaderValue::from_static("localhost"));
+    Ok(Request::from_parts(parts, body))
+}
+
+fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn proxies_requests_to_the_socket_path() {
+        let socket_path =
+            std::env::temp_dir().join(format!("mind-flayer-proxy-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = tokio::net::UnixListener::bind(&socket_path).expect("socket should bind");
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("connection should arrive");
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\nhello") {
+                let read = stream
+                    .read(&mut buffer)
+                    .await
+                    .expect("request should be read");
+                assert!(
+                    read > 0,
+                    "connection closed before the request was complete"
+                );
+                request.extend_from_slice(&buffer[..read]);
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\
+                      Content-Type: text/plain\r\n\r\n2\r\nok\r\n0\r\n\r\n",
+                )
+                .await
+                .expect("response should be written");
+            String::from_utf8(request).expect("request should be UTF-8")
+        });
+
+        let request = Request::post("sidecar://localhost/api/chat?session=1")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .body(b"hello".to_vec())
+            .expect("request should build");
+        let response = proxy_request(&socket_path, request)
+            .await
+            .expect("request should be proxied");
+        let received = server.await.expect("server task should finish");
+        let _ = std::fs::remove_file(&socket_path);
+
+        assert!(received.starts_with("POST /api/chat?session=1 HTTP/1.1\r\n"));
+        assert!(received
+            .to_ascii_lowercase()
+            .contains("host: localhost\r\n"));
+        assert!(received.contains("authorization: Bearer secret\r\n"));
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.body(), b"ok");
+        assert!(response.headers().get(header::TRANSFER_ENCODING).is_none());
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain");
+    }
+
+    #[tokio::test]
+    async fn streams_chunks_before_the_response_ends_and_closes_on_drop() {
+        let socket_path =
+            std::env::temp_dir().join(format!("mind-flayer-stream-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = tokio::net::UnixListener::bind(&socket_path).expect("socket should bind");
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("connection should arrive");
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream
+                    .read(&mut buffer)
+                    .await
+                    .expect("request should be read");
+                assert!(
+                    read > 0,
+                    "connection closed before the request was complete"
+                );
+                request.extend_from_slice(&buffer[..read]);
+            }
+            // Send one event and leave the stream open, like a chat still being generated
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\
+                      Content-Type: text/event-stream\r\n\r\n\
+                      d\r\ndata: first\n\n\r\n",
+                )
+                .await
+                .expect("response should be written");
+            // Returns once the client closes the connection
+            stream
+                .read(&mut buffer)
+                .await
+                .expect("socket should stay readable")
+        });
+
+        let request = Request::get("sidecar://localhost/api/chat")
+            .body(Vec::new())
+            .expect("request should build");
+        let mut response = stream_request(&socket_path, request)
+            .await
+            .expect("response head should arrive");
+        assert_eq!(response.response().status(), 200);
+        assert!(response
+            .response()
+            .headers()
+            .get(header::TRANSFER_ENCODING)
+            .is_none());
+
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), response.next_chunk())
+            .await
+            .expect("first chunk should arrive while the response is open")
+            .expect("body should not be complete")
+            .expect("chunk should be read");
+        assert_eq!(&chunk[..], b"data: first\n\n");
+
+        drop(response);
+        let read_after_close = tokio::time::timeout(std::time::Duration::from_secs(5), server)
+            .await
+            .expect("dropping the response should close the connection")
+            .expect("server task should finish");
+        let _ = std::fs::remove_file(&socket_path);
+        assert_eq!(read_after_close, 0);
+    }
+}
@@ -88,7 +88,8 @@ import {
   useTooltipConstants
 } from "@/lib/constants"
 import { findModelPricing } from "@/lib/provider-constants"
-import { generateTitle, getSidecarUrl } from "@/lib/sidecar-client"
+import { fetchFromSidecar, generateTitle, getSidecarUrl } from "@/lib/sidecar-client"
+import { getSidecarAuthHeaders } from "@/lib/sidecar-token"
 import { cn } from "@/lib/utils"
 import { openSettingsWindow, SettingsSection } from "@/lib/window-manager"
 import type { ChatId, MessageId, Chat as StoredChat } from "@/types/chat"
@@ -258,7 +259,9 @@ const AppChatInner = ({
 
   const sidecarOrigin = useMemo(() => {
     try {
-      return new URL(sidecarApi).origin
+      // URL.origin is "null" for the sidecar:// scheme used with the Unix socket transport
+      const { protocol, host } = new URL(sidecarApi)
+      return `${protocol}//${host}`
     } catch {
       return undefined
     }
@@ -632,7 +635,9 @@ const AppChatInner = ({
           messages: options?.initialMessages ?? [],
           transport: new DefaultChatTransport({
             api: sidecarApi,
+            fetch: fetchFromSidecar,
             headers: () => ({
+              ...getSidecarAuthHeaders(),
               "X-Model-Provider": selectedModelRef.current?.provider ?? "",
               "X-Model-Provider-Label": selectedModelRef.current?.providerLabel ?? "",
               "X-Model-Id": selectedModelRef.current?.api_id ?? "",
@@ -3,7 +3,7 @@ import { nanoid } from "nanoid"
 import { useCallback, useEffect, useState } from "react"
 import { storedMessageToUI, uiMessageToStored } from "@/lib/chat-utils"
 import { getDatabase } from "@/lib/database"
-import { getSidecarUrl } from "@/lib/sidecar-client"
+import { getSidecarUrl, sidecarFetch } from "@/lib/sidecar-client"
 import type { Chat, ChatId, ChatRow, MessageRow } from "@/types/chat"
 
 /**
@@ -115,7 +115,7 @@ export function useChatStorage() {
         // Clean up bash execution sandbox via sidecar
         try {
           const cleanupUrl = await getSidecarUrl("/api/cleanup-sandbox")
-          const cleanupResponse = await fetch(cleanupUrl, {
+          const cleanupResponse = await sidecarFetch(cleanupUrl, {
             method: "POST",
             headers: { "Content-Type": "application/json" },
             body: JSON.stringify({ chatId })
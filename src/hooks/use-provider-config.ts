@@ -1,16 +1,219 @@
 import { invoke } from "@tauri-apps/api/core"
-import { useCallback, useState } from "react"
+import { listen } from "@tauri-apps/api/event"
+import { useCallback, useEffect, useState } from "react"
 
-export interface ProviderConfig {
+/** Bookkeeping stored with each credential; times are milliseconds since the Unix epoch */
+export interface CredentialMetadata {
+  createdAt?: number
+  updatedAt?: number
+  /** Last time the credential was handed to the sidecar */
+  lastPushedAt?: number
+  /** Last successful connection test with this credential */
+  lastValidatedAt?: number
+  expiresAt?: number
+  notes?: string
+}
+
+export interface ProviderCredential extends CredentialMetadata {
+  label: string
   apiKey: string
+  active: boolean
+}
+
+/** Credential sent when saving; without `apiKey` the stored key with the same label is kept */
+export interface CredentialUpdate extends Omit<ProviderCredential, "apiKey" | "active"> {
+  apiKey?: string
+  active?: boolean
+}
+
+/** Result of `get_expiring_credentials`, also the payload of `provider-credentials-expiring` */
+export interface ExpiringCredential {
+  provider: string
+  label: string
+  expiresAt: number
+  expired: boolean
+}
+
+/** Result of `test_provider_config`, also cached with the stored config */
+export interface ConnectionTestResult {
+  status: "valid" | "invalidKey" | "unreachable" | "tlsError" | "unexpectedResponse"
+  message?: string
+  httpStatus?: number
+  latencyMs: number
+  /** Model IDs listed by the provider, sorted */
+  models: string[]
+  /** Milliseconds since the Unix epoch */
+  testedAt: number
+}
+
+/** Provider key found in the environment or a `.env` file, shown before importing */
+export interface EnvKeyPreview {
+  provider: string
+  source: "environment" | "dotEnv"
+  /** Name of the variable holding the key, e.g. `OPENAI_API_KEY` */
+  variable: string
+  maskedApiKey: string
+  baseUrl: string | null
+  /** Importing would replace the primary key of an existing config */
+  alreadyConfigured: boolean
+  /** The key is already stored for this provider */
+  matchesStored: boolean
+}
+
+export type EnvImportSelection = Pick<EnvKeyPreview, "provider" | "source">
+
+export interface CredentialSummary extends CredentialMetadata {
+  label: string
+  maskedApiKey: string
+  active: boolean
+}
+
+/**
+ * Stored provider configuration with every API key masked.
+ * Plaintext keys are only returned by `revealApiKey`.
+ */
+export interface ProviderConfigSummary {
+  provider: string
+  /** Masked key of the first active credential, e.g. `sk-proj-...1234` */
+  maskedApiKey: string | null
+  /** All labelled keys, in the order the sidecar fails over between them */
+  credentials: CredentialSummary[]
+  baseUrl: string | null
+  extraHeaderNames: string[]
+  organization: string | null
+  project: string | null
+  requestTimeoutMs: number | null
+  defaultModel: string | null
+  apiVersion: string | null
+  /** Milliseconds since the Unix epoch */
+  createdAt: number | null
+  updatedAt: number | null
+  /** Set when the stored entry no longer passes validation */
+  validationError: KeychainError | null
+  /** Last connection test, cleared when the key or connection settings change */
+  lastTest: ConnectionTestResult | null
+}
+
+/** Unsaved provider settings, as accepted by `test_provider_config` */
+export interface ProviderConfigInput {
+  credentials: ProviderCredential[]
   baseUrl?: string
+  extraHeaders?: Record<string, string>
+  organization?: string
+  project?: string
+  requestTimeoutMs?: number
+  defaultModel?: string
+  apiVersion?: string
+}
+
+export type MergeStrategy = "overwrite" | "skipExisting" | "keepNewer"
+
+/** One provider of an export bundle and what importing does with it */
+export interface ImportDiffEntry {
+  provider: string
+  action: "add" | "overwrite" | "skip"
+  maskedApiKey: string | null
+}
+
+export interface ImportReport {
+  dryRun: boolean
+  entries: ImportDiffEntry[]
+}
+
+export type AuditAction =
+  | "created"
+  | "updated"
+  | "revealed"
+  | "exported"
+  | "deleted"
+  | "recovered"
+  | "pushedToSidecar"
+  | "passphraseChanged"
+  | "backendChanged"
+  | "logRepaired"
+
+/** Audit log entry; never contains key material */
+export interface AuditEvent {
+  seq: number
+  /** Milliseconds since the Unix epoch */
+  timestamp: number
+  action: AuditAction
+  provider?: string
+  /** Tauri command that caused the event */
+  command: string
+  prevHash: string
+  hash: string
+}
+
+export interface AuditFilter {
+  provider?: string
+  actions?: AuditAction[]
+  command?: string
+  since?: number
+  until?: number
+  /** Keep only the most recent matches */
+  limit?: number
+}
+
+export interface AuditVerification {
+  valid: boolean
+  verifiedEntries: number
+  brokenAtLine?: number
+  message?: string
+}
+
+/**
+ * Structured error returned by the provider config commands.
+ * `unreadable` means the stored keys were moved aside and can be recovered,
+ * `locked` means the master passphrase has to be entered first.
+ */
+export type KeychainError =
+  | { kind: "unreadable"; message: string; quarantinePath: string }
+  | { kind: "locked"; message: string }
+  | { kind: "invalid"; field: string; message: string }
+  | { kind: "other"; message: string }
+
+export function isKeychainError(err: unknown): err is KeychainError {
+  return typeof err === "object" && err !== null && "kind" in err && "message" in err
+}
+
+function getErrorMessage(err: unknown, fallback: string): string {
+  if (isKeychainError(err)) return err.message
+  if (err instanceof Error) return err.message
+  if (typeof err === "string") return err
+  return fallback
 }
 
 export interface UseProviderConfigReturn {
-  saveConfig: (provider: string, apiKey: string, baseUrl?: string) => Promise<void>
-  getConfig: (provider: string) => Promise<ProviderConfig | null>
+  /** Without an API key only the base URL of the stored entry is updated */
+  saveConfig: (provider: string, apiKey: string | undefined, baseUrl?: string) => Promise<void>
+  saveCredentials: (
+    provider: string,
+    credentials: CredentialUpdate[],
+    baseUrl?: string
+  ) => Promise<void>
+  getConfigSummary: (provider: string) => Promise<ProviderConfigSummary | null>
+  revealApiKey: (provider: string, label?: string, passphrase?: string) => Promise<string>
+  /** Test unsaved settings, or the stored config when `config` is omitted */
+  testConfig: (provider: string, config?: ProviderConfigInput) => Promise<ConnectionTestResult>
+  discoverEnvKeys: (envFile?: string) => Promise<EnvKeyPreview[]>
+  /** Returns the imported provider IDs */
+  importEnvKeys: (selections: EnvImportSelection[], envFile?: string) => Promise<string[]>
+  /** Returns the exported provider IDs; all providers when `providers` is omitted */
+  exportConfigs: (passphrase: string, path: string, providers?: string[]) => Promise<string[]>
+  /** With `dryRun` nothing is written and the report only previews the import */
+  importConfigs: (
+    passphrase: string,
+    path: string,
+    mergeStrategy: MergeStrategy,
+    options?: { providers?: string[]; dryRun?: boolean }
+  ) => Promise<ImportReport>
+  /** Oldest first */
+  listAuditEvents: (filter?: AuditFilter) => Promise<AuditEvent[]>
+  verifyAuditLog: () => Promise<AuditVerification>
   deleteConfig: (provider: string) => Promise<void>
   listProviders: () => Promise<string[]>
+  listConfigSummaries: () => Promise<ProviderConfigSummary[]>
   isLoading: boolean
   error: string | null
 }
@@ -23,21 +226,221 @@ export function useProviderConfig(): UseProviderConfigReturn {
   const [isLoading, setIsLoading] = useState(false)
   const [error, setError] = useState<string | null>(null)
 
-  const saveConfig = useCallback(async (provider: string, apiKey: string, baseUrl?: string) => {
-    setIsLoading(true)
+  const saveConfig = useCallback(
+    async (provider: string, apiKey: string | undefined, baseUrl?: string) => {
+      setIsLoading(true)
+      setError(null)
+
+      try {
+        // Save to system keychain via Tauri (Tauri will push to sidecar automatically)
+        await invoke("save_provider_config", {
+          provider,
+          apiKey: apiKey || null,
+          baseUrl: baseUrl || null
+        })
+
+        console.log(`[useProviderConfig] Saved config for ${provider}`)
+      } catch (err) {
+        const message = getErrorMessage(err, "Failed to save configuration")
+        setError(message)
+        throw err
+      } finally {
+        setIsLoading(false)
+      }
+    },
+    []
+  )
+
+  const saveCredentials = useCallback(
+    async (provider: string, credentials: CredentialUpdate[], baseUrl?: string) => {
+      setIsLoading(true)
+      setError(null)
+
+      try {
+        await invoke("save_provider_credentials", {
+          provider,
+          credentials,
+          baseUrl: baseUrl || null
+        })
+
+        console.log(`[useProviderConfig] Saved ${credentials.length} credentials for ${provider}`)
+      } catch (err) {
+        const message = getErrorMessage(err, "Failed to save credentials")
+        setError(message)
+        throw err
+      } finally {
+        setIsLoading(false)
+      }
+    },
+    []
+  )
+
+  const getConfigSummary = useCallback(
+    async (provider: string): Promise<ProviderConfigSummary | null> => {
+      setIsLoading(true)
+      setError(null)
+
+      try {
+        const summary = await invoke<ProviderConfigSummary | null>(
+          "get_provider_config_summary",
+          { provider }
+        )
+        if (summary) {
+          console.log(`[useProviderConfig] Retrieved config summary for ${provider}`)
+        } else {
+          console.log(`[useProviderConfig] No config found for ${provider}`)
+        }
+        return summary
+      } catch (err) {
+        // Unreadable or locked keys must be surfaced instead of looking like a missing config
+        setError(getErrorMessage(err, "Failed to load configuration"))
+        return null
+      } finally {
+        setIsLoading(false)
+      }
+    },
+    []
+  )
+
+  const revealApiKey = useCallback(
+    async (provider: string, label?: string, passphrase?: string): Promise<string> => {
+      setError(null)
+
+      try {
+        return await invoke<string>("reveal_provider_api_key", {
+          provider,
+          label: label ?? null,
+          passphrase: passphrase ?? null
+        })
+      } catch (err) {
+        const message = getErrorMessage(err, "Failed to reveal API key")
+        setError(message)
+        throw err
+      }
+    },
+    []
+  )
+
+  const testConfig = useCallback(
+    async (provider: string, config?: ProviderConfigInput): Promise<ConnectionTestResult> => {
+      setError(null)
+
+      try {
+        const result = await invoke<ConnectionTestResult>("test_provider_config", {
+          provider,
+          config: config ?? null
+        })
+        console.log(`[useProviderConfig] Tested ${provider}: ${result.status}`)
+        return result
+      } catch (err) {
+        const message = getErrorMessage(err, "Failed to test configuration")
+        setError(message)
+        throw err
+      }
+    },
+    []
+  )
+
+  const discoverEnvKeys = useCallback(async (envFile?: string): Promise<EnvKeyPreview[]> => {
     setError(null)
 
     try {
-      // Save to system keychain via Tauri (Tauri will push to sidecar automatically)
-      await invoke("save_provider_config", {
-        provider,
-        apiKey,
-        baseUrl: baseUrl || null
+      return await invoke<EnvKeyPreview[]>("discover_env_provider_keys", {
+        envFile: envFile ?? null
       })
+    } catch (err) {
+      const message = getErrorMessage(err, "Failed to scan environment for API keys")
+      setError(message)
+      throw err
+    }
+  }, [])
+
+  const importEnvKeys = useCallback(
+    async (selections: EnvImportSelection[], envFile?: string): Promise<string[]> => {
+      setIsLoading(true)
+      setError(null)
+
+      try {
+        // Keys are read again on the Rust side and pushed to the sidecar from there
+        const imported = await invoke<string[]>("import_env_provider_keys", {
+          envFile: envFile ?? null,
+          selections
+        })
+        console.log(`[useProviderConfig] Imported keys for ${imported.join(", ")}`)
+        return imported
+      } catch (err) {
+        const message = getErrorMessage(err, "Failed to import API keys")
+        setError(message)
+        throw err
+      } finally {
+        setIsLoading(false)
+      }
+    },
+    []
+  )
+
+  const exportConfigs = useCallback(
+    async (passphrase: string, path: string, providers?: string[]): Promise<string[]> => {
+      setIsLoading(true)
+      setError(null)
 
-      console.log(`[useProviderConfig] Saved config for ${provider}`)
+      try {
+        const exported = await invoke<string[]>("export_provider_configs", {
+          passphrase,
+          path,
+          providers: providers ?? null
+        })
+        console.log(`[useProviderConfig] Exported configs for ${exported.join(", ")}`)
+        return exported
+      } catch (err) {
+        const message = getErrorMessage(err, "Failed to export configurations")
+        setError(message)
+        throw err
+      } finally {
+        setIsLoading(false)
+      }
+    },
+    []
+  )
+
+  const importConfigs = useCallback(
+    async (
+      passphrase: string,
+      path: string,
+      mergeStrategy: MergeStrategy,
+      options?: { providers?: string[]; dryRun?: boolean }
+    ): Promise<ImportReport> => {
+      setIsLoading(true)
+      setError(null)
+
+      try {
+        // Tauri pushes to sidecar automatically unless this is a dry run
+        return await invoke<ImportReport>("import_provider_configs", {
+          passphrase,
+          path,
+          mergeStrategy,
+          providers: options?.providers ?? null,
+          dryRun: options?.dryRun ?? false
+        })
+      } catch (err) {
+        const message = getErrorMessage(err, "Failed to import configurations")
+        setError(message)
+        throw err
+      } finally {
+        setIsLoading(false)
+      }
+    },
+    []
+  )
+
+  const listAuditEvents = useCallback(async (filter?: AuditFilter): Promise<AuditEvent[]> => {
+    setIsLoading(true)
+    setError(null)
+
+    try {
+      return await invoke<AuditEvent[]>("list_audit_events", { filter: filter ?? null })
     } catch (err) {
-      const message = err instanceof Error ? err.message : "Failed to save configuration"
+      const message = getErrorMessage(err, "Failed to read audit log")
       setError(message)
       throw err
     } finally {
@@ -45,18 +448,16 @@ export function useProviderConfig(): UseProviderConfigReturn {
     }
   }, [])
 
-  const getConfig = useCallback(async (provider: string): Promise<ProviderConfig | null> => {
+  const verifyAuditLog = useCallback(async (): Promise<AuditVerification> => {
     setIsLoading(true)
     setError(null)
 
     try {
-      const config = await invoke<ProviderConfig>("get_provider_config", { provider })
-      console.log(`[useProviderConfig] Retrieved config for ${provider}`)
-      return config
-    } catch {
-      // Don't treat "not found" as an error, just return null
-      console.log(`[useProviderConfig] No config found for ${provider}`)
-      return null
+      return await invoke<AuditVerification>("verify_audit_log")
+    } catch (err) {
+      const message = getErrorMessage(err, "Failed to verify audit log")
+      setError(message)
+      throw err
     } finally {
       setIsLoading(false)
     }
@@ -72,7 +473,7 @@ export function useProviderConfig(): UseProviderConfigReturn {
 
       console.log(`[useProviderConfig] Deleted config for ${provider}`)
     } catch (err) {
-      const message = err instanceof Error ? err.message : "Failed to delete configuration"
+      const message = getErrorMessage(err, "Failed to delete configuration")
       setError(message)
       throw err
     } finally {
@@ -88,7 +489,22 @@ export function useProviderConfig(): UseProviderConfigReturn {
       const providers = await invoke<string[]>("list_all_providers")
       return providers
     } catch (err) {
-      const message = err instanceof Error ? err.message : "Failed to list providers"
+      const message = getErrorMessage(err, "Failed to list providers")
+      setError(message)
+      throw err
+    } finally {
+      setIsLoading(false)
+    }
+  }, [])
+
+  const listConfigSummaries = useCallback(async (): Promise<ProviderConfigSummary[]> => {
+    setIsLoading(true)
+    setError(null)
+
+    try {
+      return await invoke<ProviderConfigSummary[]>("list_provider_config_summaries")
+    } catch (err) {
+      const message = getErrorMessage(err, "Failed to list provider configurations")
       setError(message)
       throw err
     } finally {
@@ -98,10 +514,67 @@ export function useProviderConfig(): UseProviderConfigReturn {
 
   return {
     saveConfig,
-    getConfig,
+    saveCredentials,
+    getConfigSummary,
+    revealApiKey,
+    testConfig,
+    discoverEnvKeys,
+    importEnvKeys,
+    exportConfigs,
+    importConfigs,
+    listAuditEvents,
+    verifyAuditLog,
     deleteConfig,
     listProviders,
+    listConfigSummaries,
     isLoading,
     error
   }
 }
+
+/**
+ * Credentials expiring within a week, or already expired, soonest first.
+ * Loaded on mount and kept current with the `provider-credentials-expiring` event.
+ */
+export function useExpiringCredentials(): ExpiringCredential[] {
+  const [expiringCredentials, setExpiringCredentials] = useState<ExpiringCredential[]>([])
+
+  useEffect(() => {
+    let unlisten: (() => void) | undefined
+    let disposed = false
+
+    const setupListener = async () => {
+      const stopListening = await listen<ExpiringCredential[]>(
+        "provider-credentials-expiring",
+        event => {
+          setExpiringCredentials(event.payload)
+        }
+      )
+      if (disposed) {
+        stopListening()
+        return
+      }
+      unlisten = stopListening
+
+      try {
+        const expiring = await invoke<ExpiringCredential[]>("get_expiring_credentials")
+        if (!disposed) {
+          setExpiringCredentials(expiring)
+        }
+      } catch (err) {
+        console.error("[useExpiringCredentials] Failed to load expiring credentials:", err)
+      }
+    }
+
+    setupListener()
+
+    return () => {
+      disposed = true
+      if (unlisten) {
+        unlisten()
+      }
+    }
+  }, [])
+
+  return expiringCredentials
+}
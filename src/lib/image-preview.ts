@@ -1,4 +1,5 @@
 import { getLocalImagePath, resolveLocalImageUrl } from "@/lib/local-image-url"
+import { withSidecarApiToken } from "@/lib/sidecar-token"
 
 const REMOTE_IMAGE_PROXY_PATH_SUFFIX = "/api/remote-image"
 const HTTP_PROTOCOL_REGEX = /^https?:/i
@@ -79,7 +80,9 @@ export function resolveRemoteImageUrl(source: string, sidecarOrigin?: string): s
     return trimmedSource
   }
 
-  return `${trimTrailingSlashes(sidecarOrigin)}/api/remote-image?url=${encodeURIComponent(trimmedSource)}`
+  return withSidecarApiToken(
+    `${trimTrailingSlashes(sidecarOrigin)}/api/remote-image?url=${encodeURIComponent(trimmedSource)}`
+  )
 }
 
 export function getOriginalRemoteImageUrlFromProxyUrl(source: string): string | null {
@@ -1,3 +1,5 @@
+import { withSidecarApiToken } from "@/lib/sidecar-token"
+
 const LOCAL_IMAGE_EXTENSIONS = new Set(["png", "jpg", "jpeg", "webp", "gif", "bmp", "svg"])
 const LOCAL_IMAGE_PROXY_PATH_SUFFIX = "/api/local-image"
 const LOCAL_IMAGE_CACHE_BUST_PARAM = "_ts"
@@ -230,7 +232,9 @@ export function resolveLocalImageUrl(
   }
 
   const proxyOrigin = trimTrailingSlashes(localImageProxyOrigin)
-  const resolvedProxyUrl = `${proxyOrigin}/api/local-image?path=${encodeURIComponent(trimmedSource)}`
+  const resolvedProxyUrl = withSidecarApiToken(
+    `${proxyOrigin}/api/local-image?path=${encodeURIComponent(trimmedSource)}`
+  )
 
   if (!cacheBustKey) {
     return resolvedProxyUrl
@@ -1,37 +1,295 @@
-import { invoke } from "@tauri-apps/api/core"
+import { Channel, invoke } from "@tauri-apps/api/core"
+import { listen, type UnlistenFn } from "@tauri-apps/api/event"
 import type { LanguageModelUsage, UIMessage } from "ai"
+import { getSidecarAuthHeaders, setSidecarApiToken } from "@/lib/sidecar-token"
 
 const DEFAULT_WAIT_TIMEOUT_MS = 15_000
+/** Origin of the sidecar API when it listens on a Unix socket */
+const SIDECAR_SOCKET_URL_PREFIX = "sidecar://"
+/** Statuses whose responses cannot have a body */
+const NULL_BODY_STATUSES = new Set([101, 204, 205, 304])
+
+let cachedSidecarOrigin: string | null = null
+let sidecarOriginPromise: Promise<string> | null = null
+let sidecarApiTokenPromise: Promise<void> | null = null
+
+export type SidecarLifecycle =
+  | "installingAssets"
+  | "spawning"
+  | "waitingForHealth"
+  | "healthy"
+  | "degraded"
+  | "restarting"
+  | "crashed"
+  | "stopped"
+
+/** Result of `get_sidecar_status`, also the payload of `sidecar-status-changed` */
+export interface SidecarStatus {
+  state: SidecarLifecycle
+  /** Spawn attempt of the current start, starting at 1 */
+  attempt: number
+  /** Restarts in a row since the sidecar last stayed up */
+  restarts: number
+  port: number | null
+  lastError: string | null
+  /** Milliseconds since the Unix epoch */
+  changedAt: number
+  healthySince: number | null
+  watchdog: SidecarWatchdogMetrics
+  /** Sequence number of the last provider config update the sidecar acknowledged */
+  lastAppliedConfigSeq: number | null
+  /** Stdin/stdout protocol version agreed on with the running sidecar */
+  protocolVersion: number | null
+  /** Why the sidecar is `degraded`, null in every other state */
+  degradedReason: SidecarDegradedReason | null
+}
+
+/**
+ * `configNotApplied`: the provider config could not be pushed.
+ * `unresponsive`: health checks keep failing.
+ */
+export type SidecarDegradedReason = "configNotApplied" | "unresponsive"
+
+/** Liveness checks against the running sidecar */
+export interface SidecarWatchdogMetrics {
+  /** Response time of the last successful check */
+  lastLatencyMs: number | null
+  consecutiveFailures: number
+  lastCheckedAt: number | null
+}
+
+export async function getSidecarStatus(): Promise<SidecarStatus> {
+  return invoke<SidecarStatus>("get_sidecar_status")
+}
 
-let cachedSidecarPort: number | null = null
-let sidecarPortPromise: Promise<number> | null = null
+/** Round trip time in milliseconds of a ping over the sidecar stdin and stdout */
+export async function pingSidecar(): Promise<number> {
+  return invoke<number>("ping_sidecar")
+}
+
+/** Lifecycle states in which the sidecar serves requests */
+const RUNNING_SIDECAR_STATES = new Set<SidecarLifecycle>(["healthy", "degraded"])
+
+/**
+ * Start the sidecar if it is not running and return the origin of its API,
+ * `http://localhost:<port>`, or `sidecar://localhost` when it listens on a Unix socket
+ */
+export async function startSidecar(): Promise<string> {
+  cachedSidecarOrigin = await invoke<string>("start_sidecar")
+  return cachedSidecarOrigin
+}
+
+/** Stop the sidecar; in-flight chat streams end with a "sidecar was stopped" reason */
+export async function stopSidecar(): Promise<void> {
+  cachedSidecarOrigin = null
+  await invoke("stop_sidecar")
+}
+
+/**
+ * Restart the sidecar, e.g. to apply a changed proxy URL or transport, and return the origin
+ * of its API
+ */
+export async function restartSidecar(): Promise<string> {
+  cachedSidecarOrigin = null
+  cachedSidecarOrigin = await invoke<string>("restart_sidecar")
+  return cachedSidecarOrigin
+}
+
+/**
+ * Subscribe to sidecar lifecycle transitions.
+ * The cached origin is dropped whenever the sidecar stops serving requests.
+ */
+export async function onSidecarStatusChanged(
+  listener: (status: SidecarStatus) => void
+): Promise<UnlistenFn> {
+  return listen<SidecarStatus>("sidecar-status-changed", event => {
+    if (!RUNNING_SIDECAR_STATES.has(event.payload.state)) {
+      cachedSidecarOrigin = null
+    }
+    listener(event.payload)
+  })
+}
+
+/** Payload of the `sidecar-failed` event */
+export interface SidecarFailure {
+  error: string
+}
+
+/** Subscribe to sidecar startup failures that will not be retried */
+export async function onSidecarFailed(
+  listener: (failure: SidecarFailure) => void
+): Promise<UnlistenFn> {
+  return listen<SidecarFailure>("sidecar-failed", event => {
+    cachedSidecarOrigin = null
+    listener(event.payload)
+  })
+}
+
+/** Load the per-launch sidecar API token, which stays valid across sidecar restarts */
+function ensureSidecarApiToken(): Promise<void> {
+  if (!sidecarApiTokenPromise) {
+    sidecarApiTokenPromise = invoke<string>("get_sidecar_api_token")
+      .then(token => setSidecarApiToken(token))
+      .catch(error => {
+        sidecarApiTokenPromise = null
+        throw error
+      })
+  }
+
+  return sidecarApiTokenPromise
+}
+
+/** fetch() against the sidecar, authorized with its API token */
+export async function sidecarFetch(url: string, init: RequestInit = {}): Promise<Response> {
+  await ensureSidecarApiToken()
+  const headers = new Headers(init.headers)
+  for (const [name, value] of Object.entries(getSidecarAuthHeaders())) {
+    headers.set(name, value)
+  }
+  return fetchFromSidecar(url, { ...init, headers })
+}
+
+/** Progress of a socket request, sent around the raw body chunks */
+type SidecarSocketEvent =
+  | { event: "response"; status: number; headers: [string, string][] }
+  | { event: "end" }
+  | { event: "error"; message: string }
+
+/**
+ * fetch() over the transport the sidecar listens on. With the Unix socket transport the host
+ * streams the response body as it arrives, and aborting the request closes the connection to
+ * the sidecar.
+ */
+export const fetchFromSidecar: typeof fetch = async (input, init = {}) => {
+  if (input instanceof Request || !String(input).startsWith(SIDECAR_SOCKET_URL_PREFIX)) {
+    return fetch(input, init)
+  }
+
+  const signal = init.signal ?? undefined
+  signal?.throwIfAborted()
+  const { pathname, search } = new URL(String(input))
+  const request = {
+    method: init.method ?? "GET",
+    path: `${pathname}${search}`,
+    headers: [...new Headers(init.headers).entries()],
+    body: init.body == null ? null : await new Response(init.body).text()
+  }
+
+  return new Promise<Response>((resolve, reject) => {
+    const channel = new Channel<ArrayBuffer | SidecarSocketEvent>()
+    let requestId: number | null = null
+    let bodyController: ReadableStreamDefaultController<Uint8Array> | null = null
+    let responded = false
+    let aborted = false
+    // Set once the host ended the request, after which there is nothing left to abort
+    let completed = false
+    let finished = false
+
+    const abortRequest = () => {
+      aborted = true
+      if (requestId !== null && !completed) {
+        void invoke("abort_sidecar_socket_request", { requestId })
+      }
+    }
+    const finish = (error?: unknown) => {
+      if (finished) {
+        return
+      }
+      finished = true
+      signal?.removeEventListener("abort", onAbort)
+      if (!responded) {
+        reject(error)
+      } else if (error === undefined) {
+        bodyController?.close()
+      } else {
+        bodyController?.error(error)
+      }
+    }
+    const onAbort = () => {
+      abortRequest()
+      finish(signal?.reason)
+    }
+    const body = new ReadableStream<Uint8Array>({
+      start(controller) {
+        bodyController = controller
+      },
+      cancel() {
+        finished = true
+        signal?.removeEventListener("abort", onAbort)
+        abortRequest()
+      }
+    })
+
+    channel.onmessage = message => {
+      if (finished) {
+        return
+      }
+      if (message instanceof ArrayBuffer) {
+        bodyController?.enqueue(new Uint8Array(message))
+        return
+      }
+      switch (message.event) {
+        case "response":
+          responded = true
+          resolve(
+            new Response(NULL_BODY_STATUSES.has(message.status) ? null : body, {
+              status: message.status,
+              headers: message.headers
+            })
+          )
+          break
+        case "end":
+          completed = true
+          finish()
+          break
+        case "error":
+          completed = true
+          finish(new TypeError(message.message))
+          break
+      }
+    }
+
+    signal?.addEventListener("abort", onAbort, { once: true })
+    invoke<number>("start_sidecar_socket_request", { request, onEvent: channel })
+      .then(id => {
+        requestId = id
+        // Aborted while the request was being started
+        if (aborted) {
+          abortRequest()
+        }
+      })
+      .catch(error => finish(error instanceof Error ? error : new TypeError(String(error))))
+  })
+}
 
-export async function getSidecarPort(timeoutMs = DEFAULT_WAIT_TIMEOUT_MS): Promise<number> {
-  if (cachedSidecarPort !== null) {
-    return cachedSidecarPort
+/** Origin of the sidecar API, waiting for the sidecar to start */
+export async function getSidecarOrigin(timeoutMs = DEFAULT_WAIT_TIMEOUT_MS): Promise<string> {
+  await ensureSidecarApiToken()
+  if (cachedSidecarOrigin !== null) {
+    return cachedSidecarOrigin
   }
 
-  if (!sidecarPortPromise) {
-    sidecarPortPromise = invoke<number>("wait_for_sidecar_port", { timeoutMs })
-      .then(port => {
-        cachedSidecarPort = port
-        return port
+  if (!sidecarOriginPromise) {
+    sidecarOriginPromise = invoke<string>("wait_for_sidecar_origin", { timeoutMs })
+      .then(origin => {
+        cachedSidecarOrigin = origin
+        return origin
       })
       .finally(() => {
-        sidecarPortPromise = null
+        sidecarOriginPromise = null
       })
   }
 
-  return sidecarPortPromise
+  return sidecarOriginPromise
 }
 
 export async function getSidecarUrl(
   path: string,
   timeoutMs = DEFAULT_WAIT_TIMEOUT_MS
 ): Promise<string> {
-  const port = await getSidecarPort(timeoutMs)
+  const origin = await getSidecarOrigin(timeoutMs)
   const normalizedPath = path.startsWith("/") ? path : `/${path}`
-  return `http://localhost:${port}${normalizedPath}`
+  return `${origin}${normalizedPath}`
 }
 
 export interface RuntimeConfigPayload {
@@ -53,7 +311,7 @@ export interface RuntimeConfigPayload {
 export async function syncRuntimeConfig(payload: RuntimeConfigPayload): Promise<void> {
   const url = await getSidecarUrl("/api/channel-runtime-config")
 
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "POST",
     headers: {
       "Content-Type": "application/json"
@@ -100,7 +358,7 @@ async function readResponseError(response: Response, fallbackMessage: string): P
 
 export async function listSkills(): Promise<SkillListItem[]> {
   const url = await getSidecarUrl("/api/skills")
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "GET"
   })
 
@@ -119,7 +377,7 @@ export async function listSkills(): Promise<SkillListItem[]> {
 export async function getSkillDetail(skillId: string): Promise<SkillDetail> {
   const encodedSkillId = encodeURIComponent(skillId)
   const url = await getSidecarUrl(`/api/skills/${encodedSkillId}`)
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "GET"
   })
 
@@ -140,7 +398,7 @@ export async function getSkillDetail(skillId: string): Promise<SkillDetail> {
 export async function deleteSkill(skillId: string): Promise<void> {
   const encodedSkillId = encodeURIComponent(skillId)
   const url = await getSidecarUrl(`/api/skills/${encodedSkillId}`)
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "DELETE"
   })
 
@@ -160,7 +418,7 @@ export interface TelegramConnectionTestResult {
 
 export async function testTelegramConnection(): Promise<TelegramConnectionTestResult> {
   const url = await getSidecarUrl("/api/channels/telegram/test")
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "POST",
     headers: {
       "Content-Type": "application/json"
@@ -218,7 +476,7 @@ export interface TelegramChannelSessionsResult {
 
 export async function getTelegramChannelSessions(): Promise<TelegramChannelSessionsResult> {
   const url = await getSidecarUrl("/api/channels/telegram/sessions")
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "GET"
   })
 
@@ -246,7 +504,7 @@ export async function getTelegramChannelSessions(): Promise<TelegramChannelSessi
 export async function deleteTelegramChannelSession(sessionKey: string): Promise<void> {
   const encodedSessionKey = encodeURIComponent(sessionKey)
   const url = await getSidecarUrl(`/api/channels/telegram/sessions?sessionKey=${encodedSessionKey}`)
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "DELETE"
   })
 
@@ -280,7 +538,7 @@ export async function getTelegramChannelSessionMessages(
   const url = await getSidecarUrl(
     `/api/channels/telegram/session-messages?sessionKey=${encodedSessionKey}`
   )
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "GET"
   })
 
@@ -309,7 +567,7 @@ export async function getTelegramChannelSessionMessages(
 
 export async function getTelegramWhitelistRequests(): Promise<TelegramWhitelistRequest[]> {
   const url = await getSidecarUrl("/api/channels/telegram/whitelist-requests")
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "GET"
   })
 
@@ -337,7 +595,7 @@ export async function decideTelegramWhitelistRequest(
   decision: "approve" | "reject"
 ): Promise<void> {
   const url = await getSidecarUrl("/api/channels/telegram/whitelist-requests/decision")
-  const response = await fetch(url, {
+  const response = await sidecarFetch(url, {
     method: "POST",
     headers: {
       "Content-Type": "application/json"
@@ -371,7 +629,7 @@ export async function generateTitle(
 ): Promise<string | null> {
   try {
     const url = await getSidecarUrl("/api/title")
-    const res = await fetch(url, {
+    const res = await sidecarFetch(url, {
       method: "POST",
       headers: {
         "Content-Type": "application/json",
@@ -0,0 +1,26 @@
+/** Query parameter the sidecar accepts in place of the Authorization header on image requests */
+const SIDECAR_API_TOKEN_QUERY_PARAM = "token"
+
+let sidecarApiToken: string | null = null
+
+/** Per-launch token required by the sidecar API, set once it was loaded from Tauri */
+export function setSidecarApiToken(token: string | null) {
+  sidecarApiToken = token
+}
+
+export function getSidecarAuthHeaders(): Record<string, string> {
+  return sidecarApiToken ? { Authorization: `Bearer ${sidecarApiToken}` } : {}
+}
+
+/**
+ * Add the token to a sidecar URL that is loaded without custom headers, e.g. by an <img> tag.
+ * URLs are returned unchanged until the token was loaded.
+ */
+export function withSidecarApiToken(url: string): string {
+  if (!sidecarApiToken) {
+    return url
+  }
+
+  const separator = url.includes("?") ? "&" : "?"
+  return `${url}${separator}${SIDECAR_API_TOKEN_QUERY_PARAM}=${encodeURIComponent(sidecarApiToken)}`
+}
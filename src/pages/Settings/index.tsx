@@ -5,7 +5,7 @@ import { useCallback, useEffect, useRef, useState } from "react"
 import { useTranslation } from "react-i18next"
 import { toast } from "sonner"
 import { useLatest } from "@/hooks/use-latest"
-import { useProviderConfig } from "@/hooks/use-provider-config"
+import { useExpiringCredentials, useProviderConfig } from "@/hooks/use-provider-config"
 import { useSetting } from "@/hooks/use-settings-store"
 import {
   ALL_PROVIDERS,
@@ -47,9 +47,12 @@ export default function Settings() {
   const successTimeoutRef = useRef<ReturnType<typeof setTimeout> | null>(null)
   const [formData, setFormData] = useState<Record<string, ProviderFormData>>(DEFAULT_FORM_DATA)
   const [storedProviders, setStoredProviders] = useState<Record<string, boolean>>({})
+  // Stored keys are only loaded masked; the form shows the mask until a new key is typed
+  const [maskedApiKeys, setMaskedApiKeys] = useState<Record<string, string>>({})
 
-  const { saveConfig, getConfig, deleteConfig, isLoading, error } = useProviderConfig()
-  const getConfigRef = useLatest(getConfig)
+  const { saveConfig, getConfigSummary, deleteConfig, isLoading, error } = useProviderConfig()
+  const expiringCredentials = useExpiringCredentials()
+  const getConfigSummaryRef = useLatest(getConfigSummary)
   const [enabledProviders, setEnabledProviders] = useSetting("enabledProviders")
   const [enabledChannels, setEnabledChannels] = useSetting("enabledChannels")
   const [telegramAllowedUserIds, setTelegramAllowedUserIds] = useSetting("telegramAllowedUserIds")
@@ -102,6 +105,18 @@ export default function Settings() {
     }
   }, [])
 
+  // Warn about credentials that expire soon, including ones found before the window opened
+  useEffect(() => {
+    for (const credential of expiringCredentials) {
+      const message = credential.expired
+        ? "providers.toast.credentialExpired"
+        : "providers.toast.credentialExpiring"
+      toast.warning(t(message, { provider: credential.provider, label: credential.label }), {
+        id: `credential-expiry-${credential.provider}-${credential.label}`
+      })
+    }
+  }, [expiringCredentials, t])
+
   useEffect(() => {
     return () => {
       if (successTimeoutRef.current) {
@@ -111,16 +126,18 @@ export default function Settings() {
   }, [])
 
   // Load saved config when provider changes
-  // biome-ignore lint/correctness/useExhaustiveDependencies: getConfigRef is stable via useLatest
+  // biome-ignore lint/correctness/useExhaustiveDependencies: getConfigSummaryRef is stable via useLatest
   useEffect(() => {
     const loadConfig = async () => {
-      const config = await getConfigRef.current(activeProvider)
+      const config = await getConfigSummaryRef.current(activeProvider)
       if (config) {
+        const maskedApiKey = config.maskedApiKey ?? ""
+        setMaskedApiKeys(prev => ({ ...prev, [activeProvider]: maskedApiKey }))
         setFormData(prev => ({
           ...prev,
           [activeProvider]: {
             ...prev[activeProvider],
-            apiKey: config.apiKey,
+            apiKey: maskedApiKey,
             baseUrl: config.baseUrl || ""
           }
         }))
@@ -134,16 +151,18 @@ export default function Settings() {
   }, [activeProvider, resetSaveFeedback])
 
   // Load web search provider config
-  // biome-ignore lint/correctness/useExhaustiveDependencies: getConfigRef is stable via useLatest
+  // biome-ignore lint/correctness/useExhaustiveDependencies: getConfigSummaryRef is stable via useLatest
   useEffect(() => {
     const loadConfig = async () => {
-      const config = await getConfigRef.current(activeWebSearchProvider)
+      const config = await getConfigSummaryRef.current(activeWebSearchProvider)
       if (config) {
+        const maskedApiKey = config.maskedApiKey ?? ""
+        setMaskedApiKeys(prev => ({ ...prev, [activeWebSearchProvider]: maskedApiKey }))
         setFormData(prev => ({
           ...prev,
           [activeWebSearchProvider]: {
             ...prev[activeWebSearchProvider],
-            apiKey: config.apiKey,
+            apiKey: maskedApiKey,
             baseUrl:
               config.baseUrl ||
               ALL_PROVIDERS.find(p => p.id === activeWebSearchProvider)?.defaultBaseUrl ||
@@ -160,16 +179,18 @@ export default function Settings() {
   }, [activeWebSearchProvider, resetSaveFeedback])
 
   // Load channel provider config
-  // biome-ignore lint/correctness/useExhaustiveDependencies: getConfigRef is stable via useLatest
+  // biome-ignore lint/correctness/useExhaustiveDependencies: getConfigSummaryRef is stable via useLatest
   useEffect(() => {
     const loadConfig = async () => {
-      const config = await getConfigRef.current(activeChannelProvider)
+      const config = await getConfigSummaryRef.current(activeChannelProvider)
       if (config) {
+        const maskedApiKey = config.maskedApiKey ?? ""
+        setMaskedApiKeys(prev => ({ ...prev, [activeChannelProvider]: maskedApiKey }))
         setFormData(prev => ({
           ...prev,
           [activeChannelProvider]: {
             ...prev[activeChannelProvider],
-            apiKey: config.apiKey,
+            apiKey: maskedApiKey,
             baseUrl:
               config.baseUrl ||
               ALL_PROVIDERS.find(p => p.id === activeChannelProvider)?.defaultBaseUrl ||
@@ -185,6 +206,12 @@ export default function Settings() {
     resetSaveFeedback()
   }, [activeChannelProvider, resetSaveFeedback])
 
+  /** The key typed into the form, or undefined if it still shows the stored (masked) key */
+  const getChangedApiKey = (providerId: string) => {
+    const apiKey = formData[providerId].apiKey.trim()
+    return apiKey === maskedApiKeys[providerId] ? undefined : apiKey
+  }
+
   const handleSave = async (providerId: string) => {
     resetSaveFeedback()
     const data = formData[providerId]
@@ -200,7 +227,7 @@ export default function Settings() {
       if (successTimeoutRef.current) {
         clearTimeout(successTimeoutRef.current)
       }
-      await saveConfig(providerId, data.apiKey.trim(), data.baseUrl.trim() || undefined)
+      await saveConfig(providerId, getChangedApiKey(providerId), data.baseUrl.trim() || undefined)
       setStoredProviders(prev => ({ ...prev, [providerId]: true }))
       setActionFeedback("save", "success")
       toast.success(t("providers.toast.saved"))
@@ -299,7 +326,7 @@ export default function Settings() {
       setChannelTestStatus("testing")
 
       // Persist latest token/baseURL before test so sidecar receives updated config.
-      await saveConfig(providerId, data.apiKey.trim(), data.baseUrl.trim() || undefined)
+      await saveConfig(providerId, getChangedApiKey(providerId), data.baseUrl.trim() || undefined)
       setStoredProviders(prev => ({ ...prev, [providerId]: true }))
 
       await emit("provider-config-changed", {
@@ -4,6 +4,7 @@ export type AppearanceThemeId = (typeof APPEARANCE_THEME_IDS)[number]
 export type Language = "en" | "zh-CN" | "system"
 export type WebSearchMode = "auto" | "always"
 export type ReasoningEffort = "default" | "low" | "medium" | "high" | "xhigh"
+export type SidecarTransport = "tcp" | "unix"
 
 /**
  * Shortcut scope - global (system-wide) or local (app-only)
@@ -65,6 +66,13 @@ export interface AppSettings {
   // App settings
   autoLaunch: boolean
   proxyUrl: string
+  /** How often the host checks that the sidecar still responds */
+  sidecarHealthCheckIntervalMs: number
+  /**
+   * Loopback TCP port, or a Unix socket only the current user can open (macOS and Linux).
+   * Applied on the next sidecar start.
+   */
+  sidecarTransport: SidecarTransport
 
   // Keyboard shortcuts
   shortcuts: Record<ShortcutAction, ShortcutConfig>
@@ -92,6 +100,8 @@ export const DEFAULT_SETTINGS: AppSettings = {
   reasoningEffort: "default",
   autoLaunch: false,
   proxyUrl: "",
+  sidecarHealthCheckIntervalMs: 5_000,
+  sidecarTransport: "tcp",
   shortcuts: {
     [ShortcutAction.TOGGLE_WINDOW]: {
       id: ShortcutAction.TOGGLE_WINDOW,